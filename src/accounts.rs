@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AccountId, Balance, BalanceOverflow, LiquidationReport};
+
+/// The holdings of a single account.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct AccountBalance {
+    /// Bid asset the account can spend.
+    pub bid_asset: Balance,
+    /// Bid asset locked behind the account's open bids.
+    pub escrowed: Balance,
+    /// Collateral held by the account.
+    pub collateral: Balance,
+}
+
+/// Reasons a balance change is rejected.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum AccountError {
+    /// The account does not hold enough of the asset.
+    InsufficientFunds {
+        account: AccountId,
+        needed: Balance,
+        available: Balance,
+    },
+    /// The account's balance would exceed the maximum [`Balance`].
+    Overflow { account: AccountId },
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountError::InsufficientFunds {
+                account,
+                needed,
+                available,
+            } => write!(
+                f,
+                "account {} needs {} but only has {}",
+                account, needed, available
+            ),
+            AccountError::Overflow { account } => {
+                write!(f, "balance of account {} overflows", account)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+/// Bid-asset and collateral balances of every account in the simulation.
+///
+/// Assets only enter through [`Accounts::mint`]; every other operation moves
+/// them between accounts, so the totals are conserved.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Accounts {
+    balances: BTreeMap<AccountId, AccountBalance>,
+}
+
+impl Accounts {
+    /// Creates an empty set of accounts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The balance of `account`, zero if it has never held anything.
+    pub fn balance(&self, account: AccountId) -> AccountBalance {
+        self.balances.get(&account).copied().unwrap_or_default()
+    }
+
+    /// Every account that has held something, by id.
+    pub fn iter(&self) -> impl Iterator<Item = (&AccountId, &AccountBalance)> {
+        self.balances.iter()
+    }
+
+    /// Total bid asset in the system, escrowed or not.
+    pub fn total_bid_asset(&self) -> Result<Balance, BalanceOverflow> {
+        Balance::checked_sum(
+            self.balances
+                .values()
+                .flat_map(|b| [b.bid_asset, b.escrowed]),
+        )
+        .ok_or(BalanceOverflow)
+    }
+
+    /// Total collateral in the system.
+    pub fn total_collateral(&self) -> Result<Balance, BalanceOverflow> {
+        Balance::checked_sum(self.balances.values().map(|b| b.collateral)).ok_or(BalanceOverflow)
+    }
+
+    /// Creates new bid asset and collateral in `account`.
+    pub fn mint(
+        &mut self,
+        account: AccountId,
+        bid_asset: Balance,
+        collateral: Balance,
+    ) -> Result<(), AccountError> {
+        let mut balance = self.balance(account);
+        balance.bid_asset = add(account, balance.bid_asset, bid_asset)?;
+        balance.collateral = add(account, balance.collateral, collateral)?;
+        self.balances.insert(account, balance);
+        Ok(())
+    }
+
+    /// Locks `amount` of the account's bid asset behind a bid.
+    pub fn escrow(&mut self, account: AccountId, amount: Balance) -> Result<(), AccountError> {
+        let mut balance = self.balance(account);
+        balance.bid_asset = sub(account, balance.bid_asset, amount)?;
+        balance.escrowed = add(account, balance.escrowed, amount)?;
+        self.balances.insert(account, balance);
+        Ok(())
+    }
+
+    /// Returns `amount` of escrowed bid asset to the account.
+    pub fn release(&mut self, account: AccountId, amount: Balance) -> Result<(), AccountError> {
+        let mut balance = self.balance(account);
+        balance.escrowed = sub(account, balance.escrowed, amount)?;
+        balance.bid_asset = add(account, balance.bid_asset, amount)?;
+        self.balances.insert(account, balance);
+        Ok(())
+    }
+
+    /// Checks that the liquidated account and the bidders can cover every
+    /// fill of `report`, without changing any balance.
+    pub fn check_settlement(
+        &self,
+        report: &LiquidationReport,
+        liquidated: AccountId,
+    ) -> Result<(), AccountError> {
+        self.settled(report, liquidated).map(|_| ())
+    }
+
+    /// Settles the fills of a liquidation: the escrowed bid asset of each
+    /// bidder goes to the liquidated account, and the liquidated account's
+    /// collateral goes to the bidder. Dust left by a fill consuming a bid is
+    /// released back to the bidder.
+    ///
+    /// Balances are only updated if every fill can be settled.
+    pub fn settle(
+        &mut self,
+        report: &LiquidationReport,
+        liquidated: AccountId,
+    ) -> Result<(), AccountError> {
+        self.balances = self.settled(report, liquidated)?;
+        Ok(())
+    }
+
+    // the balances after settling every fill of the report
+    fn settled(
+        &self,
+        report: &LiquidationReport,
+        liquidated: AccountId,
+    ) -> Result<BTreeMap<AccountId, AccountBalance>, AccountError> {
+        let mut balances = self.balances.clone();
+        for fill in &report.fills {
+            let bidder = balances.entry(fill.bidder).or_default();
+            bidder.escrowed = sub(fill.bidder, bidder.escrowed, fill.bid_asset_spent)?;
+            bidder.collateral = add(fill.bidder, bidder.collateral, fill.collateral_received)?;
+            bidder.escrowed = sub(fill.bidder, bidder.escrowed, fill.dust)?;
+            bidder.bid_asset = add(fill.bidder, bidder.bid_asset, fill.dust)?;
+
+            let seller = balances.entry(liquidated).or_default();
+            seller.collateral = sub(liquidated, seller.collateral, fill.collateral_received)?;
+            seller.bid_asset = add(liquidated, seller.bid_asset, fill.bid_asset_spent)?;
+        }
+        Ok(balances)
+    }
+}
+
+fn add(account: AccountId, balance: Balance, amount: Balance) -> Result<Balance, AccountError> {
+    balance
+        .checked_add(amount)
+        .ok_or(AccountError::Overflow { account })
+}
+
+fn sub(account: AccountId, balance: Balance, amount: Balance) -> Result<Balance, AccountError> {
+    balance
+        .checked_sub(amount)
+        .ok_or(AccountError::InsufficientFunds {
+            account,
+            needed: amount,
+            available: balance,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Discount, Fill};
+
+    const ALICE: AccountId = 1;
+    const BOB: AccountId = 2;
+    const DAVE: AccountId = 4;
+
+    fn accounts() -> Accounts {
+        let mut accounts = Accounts::new();
+        accounts
+            .mint(ALICE, Balance::new(1_000), Balance::ZERO)
+            .unwrap();
+        accounts
+            .mint(DAVE, Balance::ZERO, Balance::new(5_000))
+            .unwrap();
+        accounts
+    }
+
+    // a fill of `bidder`'s bid paying `spent` for `collateral`
+    fn fill(bidder: AccountId, spent: u128, collateral: u128) -> Fill {
+        Fill {
+            bidder,
+            blocknumber: 0,
+            index: 0,
+            discount: Discount::percent(5),
+            bid_asset_spent: Balance::new(spent),
+            collateral_received: Balance::new(collateral),
+            partial: true,
+            remaining_amount: Balance::ZERO,
+            dust: Balance::ZERO,
+            rounded_off: Balance::ZERO,
+        }
+    }
+
+    fn report(fills: Vec<Fill>) -> LiquidationReport {
+        LiquidationReport {
+            fills,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_escrow_requires_funds() {
+        let mut accounts = accounts();
+        let before = accounts.balance(ALICE);
+
+        assert_eq!(
+            accounts.escrow(ALICE, Balance::new(1_500)),
+            Err(AccountError::InsufficientFunds {
+                account: ALICE,
+                needed: Balance::new(1_500),
+                available: Balance::new(1_000),
+            })
+        );
+        assert_eq!(accounts.balance(ALICE), before);
+        assert!(accounts.escrow(BOB, Balance::new(1)).is_err());
+
+        accounts.escrow(ALICE, Balance::new(400)).unwrap();
+        let alice = accounts.balance(ALICE);
+        assert_eq!(alice.bid_asset, Balance::new(600));
+        assert_eq!(alice.escrowed, Balance::new(400));
+    }
+
+    #[test]
+    fn test_release_is_limited_to_the_escrow() {
+        let mut accounts = accounts();
+        accounts.escrow(ALICE, Balance::new(400)).unwrap();
+        let before = accounts.balance(ALICE);
+
+        assert_eq!(
+            accounts.release(ALICE, Balance::new(500)),
+            Err(AccountError::InsufficientFunds {
+                account: ALICE,
+                needed: Balance::new(500),
+                available: Balance::new(400),
+            })
+        );
+        assert_eq!(accounts.balance(ALICE), before);
+
+        accounts.release(ALICE, Balance::new(400)).unwrap();
+        assert_eq!(accounts.balance(ALICE).bid_asset, Balance::new(1_000));
+        assert_eq!(accounts.balance(ALICE).escrowed, Balance::ZERO);
+    }
+
+    #[test]
+    fn test_mint_overflow_changes_nothing() {
+        let mut accounts = accounts();
+        accounts.mint(BOB, Balance::MAX, Balance::ZERO).unwrap();
+
+        assert_eq!(
+            accounts.mint(BOB, Balance::new(1), Balance::ZERO),
+            Err(AccountError::Overflow { account: BOB })
+        );
+        // the collateral overflows after the bid asset was added
+        assert_eq!(
+            accounts.mint(DAVE, Balance::new(10), Balance::MAX),
+            Err(AccountError::Overflow { account: DAVE })
+        );
+        assert_eq!(accounts.balance(BOB).bid_asset, Balance::MAX);
+        assert_eq!(accounts.balance(DAVE).bid_asset, Balance::ZERO);
+        assert_eq!(accounts.total_bid_asset(), Err(BalanceOverflow));
+    }
+
+    #[test]
+    fn test_settlement_differing_from_the_preview() {
+        let mut accounts = accounts();
+        accounts.escrow(ALICE, Balance::new(1_000)).unwrap();
+        let preview = report(vec![fill(ALICE, 600, 630)]);
+        accounts.check_settlement(&preview, DAVE).unwrap();
+
+        // the report spends more than ALICE has escrowed
+        let before: Vec<_> = accounts.iter().map(|(id, b)| (*id, *b)).collect();
+        let settled = report(vec![fill(ALICE, 600, 630), fill(ALICE, 600, 630)]);
+        assert_eq!(
+            accounts.settle(&settled, DAVE),
+            Err(AccountError::InsufficientFunds {
+                account: ALICE,
+                needed: Balance::new(600),
+                available: Balance::new(400),
+            })
+        );
+        let after: Vec<_> = accounts.iter().map(|(id, b)| (*id, *b)).collect();
+        assert_eq!(after, before);
+
+        accounts.settle(&preview, DAVE).unwrap();
+        let alice = accounts.balance(ALICE);
+        assert_eq!(alice.escrowed, Balance::new(400));
+        assert_eq!(alice.collateral, Balance::new(630));
+        let dave = accounts.balance(DAVE);
+        assert_eq!(dave.bid_asset, Balance::new(600));
+        assert_eq!(dave.collateral, Balance::new(5_000 - 630));
+        assert_eq!(accounts.total_bid_asset(), Ok(Balance::new(1_000)));
+        assert_eq!(accounts.total_collateral(), Ok(Balance::new(5_000)));
+    }
+}
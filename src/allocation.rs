@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Balance, BalanceOverflow};
+
+/// How a liquidation shares out a discount tier it cannot fill completely.
+///
+/// A tier is every bid at the same discount. Tiers the liquidation fills
+/// completely are filled the same way whatever the policy; only the last,
+/// partially filled tier is shared out.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AllocationPolicy {
+    /// Earlier bids are filled first, see [`crate::Bid`]'s ordering.
+    #[default]
+    Fifo,
+    /// Every bid gets a share in proportion to its remaining amount. The
+    /// units lost rounding the shares down go one each to the earliest bids.
+    ProRata,
+    /// Like [`AllocationPolicy::ProRata`], but bids whose share would be
+    /// below `min_fill` are left out and the tier is shared among the others
+    /// again. If the others can be filled completely the rest goes to the
+    /// bids left out, earliest first; if every bid is left out the tier is
+    /// filled first in first out.
+    ProRataMinFill { min_fill: Balance },
+}
+
+impl AllocationPolicy {
+    /// Shares `available` among bids with these remaining `amounts`, given in
+    /// book order. Returns how much each bid is filled; the allocations add
+    /// up to exactly `available`, or to every amount if there is more. Fails
+    /// if the amounts add up to more than a [`Balance`] holds.
+    pub fn allocate(
+        &self,
+        amounts: &[Balance],
+        available: Balance,
+    ) -> Result<Vec<Balance>, BalanceOverflow> {
+        let everyone: Vec<usize> = (0..amounts.len()).collect();
+        let tier_total = total(amounts, &everyone)?;
+        if available >= tier_total {
+            return Ok(amounts.to_vec());
+        }
+        Ok(match self {
+            AllocationPolicy::Fifo => fifo(amounts, &everyone, available),
+            AllocationPolicy::ProRata => pro_rata(amounts, &everyone, tier_total, available),
+            AllocationPolicy::ProRataMinFill { min_fill } => {
+                let mut kept = everyone;
+                let mut kept_total = tier_total;
+                loop {
+                    let shares = pro_rata(amounts, &kept, kept_total, available);
+                    let still_kept: Vec<usize> = kept
+                        .iter()
+                        .copied()
+                        .filter(|bid| shares[*bid] >= *min_fill)
+                        .collect();
+                    if still_kept.len() == kept.len() {
+                        break shares;
+                    }
+                    if still_kept.is_empty() {
+                        let everyone: Vec<usize> = (0..amounts.len()).collect();
+                        break fifo(amounts, &everyone, available);
+                    }
+                    kept_total = total(amounts, &still_kept)?;
+                    if available >= kept_total {
+                        let mut shares = vec![Balance::ZERO; amounts.len()];
+                        for bid in &still_kept {
+                            shares[*bid] = amounts[*bid];
+                        }
+                        let left_out: Vec<usize> = (0..amounts.len())
+                            .filter(|bid| !still_kept.contains(bid))
+                            .collect();
+                        let rest = fifo(amounts, &left_out, available - kept_total);
+                        for bid in left_out {
+                            shares[bid] = rest[bid];
+                        }
+                        break shares;
+                    }
+                    kept = still_kept;
+                }
+            }
+        })
+    }
+}
+
+// the sum of the amounts of the bids `among`
+fn total(amounts: &[Balance], among: &[usize]) -> Result<Balance, BalanceOverflow> {
+    Balance::checked_sum(among.iter().map(|bid| amounts[*bid])).ok_or(BalanceOverflow)
+}
+
+// fills the bids `among` in order until `available` runs out
+fn fifo(amounts: &[Balance], among: &[usize], mut available: Balance) -> Vec<Balance> {
+    let mut shares = vec![Balance::ZERO; amounts.len()];
+    for bid in among {
+        let share = amounts[*bid].min(available);
+        shares[*bid] = share;
+        available -= share;
+    }
+    shares
+}
+
+// shares `available` among the bids `among`, whose amounts add up to `total`,
+// more than `available`
+fn pro_rata(
+    amounts: &[Balance],
+    among: &[usize],
+    total: Balance,
+    available: Balance,
+) -> Vec<Balance> {
+    let mut shares = vec![Balance::ZERO; amounts.len()];
+    let mut allocated = Balance::ZERO;
+    for bid in among {
+        // below the bid's amount, as `available` is below the total
+        let share = amounts[*bid]
+            .mul_div(available.units(), total.units())
+            .expect("share is below the amount");
+        shares[*bid] = share;
+        allocated += share;
+    }
+    // fewer units than bids are lost rounding down
+    let lost = (available - allocated).units() as usize;
+    for bid in among.iter().take(lost) {
+        shares[*bid] += Balance::new(1);
+    }
+    shares
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::SimRng;
+
+    // allocates among amounts given in units
+    fn allocate(policy: AllocationPolicy, amounts: &[u128], available: u128) -> Vec<u128> {
+        let amounts: Vec<_> = amounts.iter().map(|amount| Balance::new(*amount)).collect();
+        policy
+            .allocate(&amounts, Balance::new(available))
+            .unwrap()
+            .into_iter()
+            .map(Balance::units)
+            .collect()
+    }
+
+    #[test]
+    fn test_fifo_fills_earliest_first() {
+        assert_eq!(
+            allocate(AllocationPolicy::Fifo, &[300, 500, 200], 600),
+            vec![300, 300, 0]
+        );
+    }
+
+    #[test]
+    fn test_pro_rata_shares_by_amount() {
+        assert_eq!(
+            allocate(AllocationPolicy::ProRata, &[300, 500, 200], 600),
+            vec![180, 300, 120]
+        );
+        // 100 / 3 each, the unit left over goes to the earliest bid
+        assert_eq!(
+            allocate(AllocationPolicy::ProRata, &[100, 100, 100], 100),
+            vec![34, 33, 33]
+        );
+        assert_eq!(
+            allocate(AllocationPolicy::ProRata, &[100, 100], 500),
+            vec![100, 100]
+        );
+    }
+
+    #[test]
+    fn test_min_fill_leaves_small_shares_out() {
+        let policy = AllocationPolicy::ProRataMinFill {
+            min_fill: Balance::new(50),
+        };
+        // 10 would get 6, so 990 takes everything
+        assert_eq!(allocate(policy, &[10, 990], 600), vec![0, 600]);
+        // the others are filled completely and the rest goes to the small bid
+        assert_eq!(allocate(policy, &[10, 10, 500], 505), vec![5, 0, 500]);
+        // nobody gets 50, so the tier is filled first in first out
+        assert_eq!(allocate(policy, &[40, 40, 40], 60), vec![40, 20, 0]);
+    }
+
+    #[test]
+    fn test_tier_totals_match_exactly() {
+        let mut rng = SimRng::new(13);
+        let policies = [
+            AllocationPolicy::Fifo,
+            AllocationPolicy::ProRata,
+            AllocationPolicy::ProRataMinFill {
+                min_fill: Balance::new(100),
+            },
+        ];
+        for round in 0..1_000 {
+            // token amounts with 18 decimals every other round
+            let unit = if round % 2 == 0 { 1 } else { 10u128.pow(18) };
+            let amounts: Vec<Balance> = (0..rng.random_range(1..20))
+                .map(|_| Balance::new(rng.random_range(1..=10_000) * unit))
+                .collect();
+            let total = Balance::checked_sum(amounts.iter().copied()).unwrap();
+            let available = Balance::new(rng.random_range(0..=total.units() + 1_000 * unit));
+            for policy in &policies {
+                let shares = policy.allocate(&amounts, available).unwrap();
+                assert_eq!(
+                    Balance::checked_sum(shares.iter().copied()),
+                    Some(available.min(total))
+                );
+                assert!(shares
+                    .iter()
+                    .zip(&amounts)
+                    .all(|(share, amount)| share <= amount));
+            }
+        }
+    }
+
+    #[test]
+    fn test_overflowing_tier() {
+        assert_eq!(
+            AllocationPolicy::ProRata.allocate(&[Balance::MAX, Balance::new(1)], Balance::new(5)),
+            Err(BalanceOverflow)
+        );
+    }
+}
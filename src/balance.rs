@@ -0,0 +1,441 @@
+use std::fmt;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// An amount of an asset, counted in the asset's smallest unit.
+///
+/// Balances are backed by a `u128`, so a book of millions of bids cannot
+/// come near the limit, and arithmetic is always checked: the `checked_*`
+/// methods return `None` and the operators panic rather than wrap, in
+/// release builds too. How many units make one token depends on the asset,
+/// see [`Asset`]. Amounts are serialized as a plain number of units, or as a
+/// string of digits above `u64::MAX`, which not every format can hold.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct Balance(u128);
+
+impl Balance {
+    /// No units at all.
+    pub const ZERO: Balance = Balance(0);
+    /// The largest balance there can be.
+    pub const MAX: Balance = Balance(u128::MAX);
+
+    /// A balance of `units` of the smallest unit.
+    pub const fn new(units: u128) -> Self {
+        Balance(units)
+    }
+
+    /// The number of smallest units.
+    pub const fn units(self) -> u128 {
+        self.0
+    }
+
+    /// Whether the balance is zero.
+    pub const fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// `self + other`, `None` on overflow.
+    pub fn checked_add(self, other: Balance) -> Option<Balance> {
+        self.0.checked_add(other.0).map(Balance)
+    }
+
+    /// `self - other`, `None` if `other` is larger.
+    pub fn checked_sub(self, other: Balance) -> Option<Balance> {
+        self.0.checked_sub(other.0).map(Balance)
+    }
+
+    /// `self - other`, zero if `other` is larger.
+    pub fn saturating_sub(self, other: Balance) -> Balance {
+        Balance(self.0.saturating_sub(other.0))
+    }
+
+    /// `self + other`, [`Balance::MAX`] on overflow.
+    pub fn saturating_add(self, other: Balance) -> Balance {
+        Balance(self.0.saturating_add(other.0))
+    }
+
+    /// `floor(self * numerator / denominator)` computed without an
+    /// intermediate overflow. `None` if `denominator` is zero or the result
+    /// does not fit.
+    pub fn mul_div(self, numerator: u128, denominator: u128) -> Option<Balance> {
+        mul_div(self.0, numerator, denominator).map(Balance)
+    }
+
+    /// The sum of `balances`, `None` on overflow.
+    pub fn checked_sum<I: IntoIterator<Item = Balance>>(balances: I) -> Option<Balance> {
+        balances
+            .into_iter()
+            .try_fold(Balance::ZERO, Balance::checked_add)
+    }
+
+    /// The balance as a float, for display and statistics.
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64
+    }
+}
+
+impl From<u32> for Balance {
+    fn from(units: u32) -> Self {
+        Balance(units as u128)
+    }
+}
+
+impl From<u64> for Balance {
+    fn from(units: u64) -> Self {
+        Balance(units as u128)
+    }
+}
+
+impl fmt::Debug for Balance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for Balance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Panics on overflow; use [`Balance::checked_add`] for amounts that are not
+/// bounded by the caller.
+impl Add for Balance {
+    type Output = Balance;
+
+    fn add(self, other: Balance) -> Balance {
+        self.checked_add(other).expect("balance overflow")
+    }
+}
+
+impl AddAssign for Balance {
+    fn add_assign(&mut self, other: Balance) {
+        *self = *self + other;
+    }
+}
+
+/// Panics if `other` is larger; use [`Balance::checked_sub`] for amounts
+/// that are not bounded by the caller.
+impl Sub for Balance {
+    type Output = Balance;
+
+    fn sub(self, other: Balance) -> Balance {
+        self.checked_sub(other).expect("balance underflow")
+    }
+}
+
+impl SubAssign for Balance {
+    fn sub_assign(&mut self, other: Balance) {
+        *self = *self - other;
+    }
+}
+
+impl Serialize for Balance {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match u64::try_from(self.0) {
+            Ok(units) => serializer.serialize_u64(units),
+            Err(_) => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Balance {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(BalanceVisitor)
+    }
+}
+
+struct BalanceVisitor;
+
+impl Visitor<'_> for BalanceVisitor {
+    type Value = Balance;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a number of units or a string of digits")
+    }
+
+    fn visit_u64<E: de::Error>(self, units: u64) -> Result<Balance, E> {
+        Ok(Balance(units as u128))
+    }
+
+    fn visit_u128<E: de::Error>(self, units: u128) -> Result<Balance, E> {
+        Ok(Balance(units))
+    }
+
+    fn visit_i64<E: de::Error>(self, units: i64) -> Result<Balance, E> {
+        u128::try_from(units)
+            .map(Balance)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(units), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Balance, E> {
+        if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+            return Err(E::invalid_value(de::Unexpected::Str(s), &self));
+        }
+        s.parse()
+            .map(Balance)
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(s), &self))
+    }
+}
+
+/// A total that does not fit in a [`Balance`].
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct BalanceOverflow;
+
+impl fmt::Display for BalanceOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "balance overflows")
+    }
+}
+
+impl std::error::Error for BalanceOverflow {}
+
+/// An asset of the marketplace and how its balances are written.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Asset {
+    /// Ticker shown after amounts.
+    pub symbol: String,
+    /// Number of decimal places of one token, at most
+    /// [`Asset::MAX_DECIMALS`]: a balance of one unit is `10^-decimals`
+    /// tokens.
+    pub decimals: u8,
+}
+
+impl Default for Asset {
+    fn default() -> Self {
+        Asset::new("", 0)
+    }
+}
+
+impl Asset {
+    /// The most decimals a `u128` balance can hold a whole token with.
+    pub const MAX_DECIMALS: u8 = 38;
+
+    /// An asset called `symbol` with `decimals` decimal places.
+    pub fn new(symbol: &str, decimals: u8) -> Self {
+        Asset {
+            symbol: symbol.to_string(),
+            decimals,
+        }
+    }
+
+    /// Number of units in one token.
+    pub fn scale(&self) -> u128 {
+        10u128.pow(self.decimals.min(Self::MAX_DECIMALS) as u32)
+    }
+
+    /// A balance of `tokens` whole tokens, `None` if it does not fit.
+    pub fn tokens(&self, tokens: u128) -> Option<Balance> {
+        tokens.checked_mul(self.scale()).map(Balance)
+    }
+
+    /// Writes `balance` in tokens, without trailing zeros, followed by the
+    /// symbol, e.g. `"12.5 USDC"`.
+    pub fn format(&self, balance: Balance) -> String {
+        let scale = self.scale();
+        let integer = balance.0 / scale;
+        let mut amount = integer.to_string();
+        if scale > 1 {
+            let width = self.decimals.min(Self::MAX_DECIMALS) as usize;
+            let fraction = format!("{:0width$}", balance.0 % scale, width = width);
+            let fraction = fraction.trim_end_matches('0');
+            if !fraction.is_empty() {
+                amount = format!("{}.{}", amount, fraction);
+            }
+        }
+        if self.symbol.is_empty() {
+            amount
+        } else {
+            format!("{} {}", amount, self.symbol)
+        }
+    }
+
+    /// Parses an amount of tokens such as `"12.5"` exactly. More decimal
+    /// places than the asset has are rejected rather than rounded.
+    pub fn parse(&self, s: &str) -> Result<Balance, ParseBalanceError> {
+        let invalid = || ParseBalanceError(s.to_string());
+        let decimals = self.decimals.min(Self::MAX_DECIMALS) as usize;
+        let (integer, fraction) = s.trim().split_once('.').unwrap_or((s.trim(), ""));
+        if integer.is_empty() && fraction.is_empty()
+            || fraction.len() > decimals
+            || !integer
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let integer: u128 = if integer.is_empty() {
+            0
+        } else {
+            integer.parse().map_err(|_| invalid())?
+        };
+        let fraction: u128 = if fraction.is_empty() {
+            0
+        } else {
+            let padding = (decimals - fraction.len()) as u32;
+            fraction.parse::<u128>().map_err(|_| invalid())? * 10u128.pow(padding)
+        };
+        integer
+            .checked_mul(self.scale())
+            .and_then(|units| units.checked_add(fraction))
+            .map(Balance)
+            .ok_or_else(invalid)
+    }
+}
+
+/// The bid asset and the collateral of a market.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Assets {
+    /// The asset bids are placed in and liquidations are owed in.
+    pub bid_asset: Asset,
+    /// The asset liquidations sell.
+    pub collateral: Asset,
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        Assets {
+            bid_asset: Asset::new("BID", 0),
+            collateral: Asset::new("COL", 0),
+        }
+    }
+}
+
+/// The string is not an amount of the asset.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseBalanceError(String);
+
+impl fmt::Display for ParseBalanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid amount {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseBalanceError {}
+
+// floor(a * b / c) with a 256-bit intermediate product
+fn mul_div(a: u128, b: u128, c: u128) -> Option<u128> {
+    if c == 0 {
+        return None;
+    }
+    let (high, low) = widening_mul(a, b);
+    if high >= c {
+        // the quotient needs more than 128 bits
+        return None;
+    }
+    if high == 0 {
+        return Some(low / c);
+    }
+    // long division of high:low by c, one bit at a time
+    let mut remainder = high;
+    let mut quotient = 0u128;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= c {
+            remainder = remainder.wrapping_sub(c);
+            quotient |= 1;
+        }
+    }
+    Some(quotient)
+}
+
+// the full product of a and b as (high, low) halves
+fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_high, a_low) = (a >> 64, a & MASK);
+    let (b_high, b_low) = (b >> 64, b & MASK);
+
+    let low_low = a_low * b_low;
+    let low_high = a_low * b_high;
+    let high_low = a_high * b_low;
+    let high_high = a_high * b_high;
+
+    let middle = (low_low >> 64) + (low_high & MASK) + (high_low & MASK);
+    let low = (low_low & MASK) | (middle << 64);
+    let high = high_high + (low_high >> 64) + (high_low >> 64) + (middle >> 64);
+    (high, low)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let a = Balance::new(5);
+        assert_eq!(a.checked_add(Balance::new(7)), Some(Balance::new(12)));
+        assert_eq!(Balance::MAX.checked_add(a), None);
+        assert_eq!(a.checked_sub(Balance::new(6)), None);
+        assert_eq!(a.saturating_sub(Balance::new(6)), Balance::ZERO);
+        assert_eq!(Balance::checked_sum([Balance::MAX, Balance::new(1)]), None);
+        assert_eq!(Balance::checked_sum([a, a, a]), Some(Balance::new(15)));
+    }
+
+    #[test]
+    #[should_panic(expected = "balance overflow")]
+    fn test_operators_never_wrap() {
+        let _ = Balance::MAX + Balance::new(1);
+    }
+
+    #[test]
+    fn test_mul_div_is_exact() {
+        assert_eq!(Balance::new(10).mul_div(3, 4), Some(Balance::new(7)));
+        assert_eq!(Balance::new(10).mul_div(3, 0), None);
+        // the product does not fit in 128 bits, the quotient does
+        let big = Balance::new(u128::MAX / 3);
+        assert_eq!(big.mul_div(6, 3), Some(Balance::new(u128::MAX / 3 * 2)));
+        assert_eq!(
+            Balance::new(10u128.pow(30)).mul_div(10u128.pow(20), 10u128.pow(25)),
+            Some(Balance::new(10u128.pow(25)))
+        );
+        assert_eq!(Balance::MAX.mul_div(2, 1), None);
+        assert_eq!(
+            Balance::MAX.mul_div(u128::MAX, u128::MAX),
+            Some(Balance::MAX)
+        );
+    }
+
+    #[test]
+    fn test_asset_format_and_parse() {
+        let usdc = Asset::new("USDC", 6);
+        assert_eq!(usdc.format(Balance::new(12_500_000)), "12.5 USDC");
+        assert_eq!(usdc.format(Balance::new(1)), "0.000001 USDC");
+        assert_eq!(usdc.format(Balance::new(3_000_000)), "3 USDC");
+        assert_eq!(usdc.parse("12.5"), Ok(Balance::new(12_500_000)));
+        assert_eq!(usdc.parse(".000001"), Ok(Balance::new(1)));
+        assert_eq!(usdc.tokens(4), Some(Balance::new(4_000_000)));
+        assert!(usdc.parse("0.0000001").is_err());
+        assert!(usdc.parse("1.2.3").is_err());
+        assert!(usdc.parse("-1").is_err());
+
+        let units = Asset::default();
+        assert_eq!(units.format(Balance::new(42)), "42");
+        assert!(units.parse("4.2").is_err());
+
+        let eth = Asset::new("ETH", 18);
+        let amount = eth.parse("123456789.000000000000000001").unwrap();
+        assert_eq!(eth.format(amount), "123456789.000000000000000001 ETH");
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        assert_eq!(serde_json::to_string(&Balance::new(42)).unwrap(), "42");
+        let big = Balance::new(10u128.pow(30));
+        let json = serde_json::to_string(&big).unwrap();
+        assert_eq!(json, format!("\"{}\"", 10u128.pow(30)));
+        assert_eq!(serde_json::from_str::<Balance>(&json).unwrap(), big);
+        assert_eq!(
+            serde_json::from_str::<Balance>("42").unwrap(),
+            Balance::new(42)
+        );
+        assert!(serde_json::from_str::<Balance>("-1").is_err());
+        assert!(serde_json::from_str::<Balance>("\"1.5\"").is_err());
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Balance, Discount, LiquidationMetrics, Scenario, ScenarioError};
+
+/// Many runs of the same [`Scenario`], each seeded differently, to see how
+/// its results are distributed rather than what one random book gives.
+///
+/// Run `i` uses the seed `first_seed + i`, so every run of a batch can be
+/// replayed on its own by running the scenario with that seed. Runs are
+/// shared between `threads` threads; the report is the same whatever the
+/// number of threads.
+#[derive(Clone, Debug)]
+pub struct Batch {
+    /// Number of runs.
+    pub runs: u64,
+    /// Seed of the first run.
+    pub first_seed: u64,
+    /// Number of threads running the scenario, at least one.
+    pub threads: usize,
+}
+
+impl Default for Batch {
+    fn default() -> Self {
+        Self {
+            runs: 100,
+            first_seed: 0,
+            threads: 1,
+        }
+    }
+}
+
+/// The results of one run of a batch.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RunSummary {
+    /// Seed of the run.
+    pub seed: u64,
+    /// Fraction of the amount queued that was filled, see
+    /// [`LiquidationMetrics::fill_rate`].
+    pub fill_rate: f64,
+    /// Average discount of the fills in percent, `None` if nothing was
+    /// filled, see [`LiquidationMetrics::average_discount`].
+    pub average_discount: Option<f64>,
+    /// Amount queued that was not filled.
+    pub unfilled: Balance,
+    /// How many times each discount level was emptied.
+    pub emptied_discounts: BTreeMap<Discount, u64>,
+    /// Number of steps that failed.
+    pub errors: usize,
+}
+
+impl RunSummary {
+    fn new(seed: u64, metrics: &LiquidationMetrics, errors: usize) -> Self {
+        Self {
+            seed,
+            fill_rate: metrics.fill_rate(),
+            average_discount: (!metrics.filled.is_zero()).then(|| metrics.average_discount()),
+            unfilled: metrics.unfilled(),
+            emptied_discounts: metrics.emptied_discounts.clone(),
+            errors,
+        }
+    }
+}
+
+/// How a value is distributed over the runs of a batch. Percentiles are
+/// interpolated linearly between the two closest runs.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Distribution {
+    /// Number of runs the value was measured in.
+    pub count: usize,
+    pub mean: f64,
+    pub min: f64,
+    pub p5: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl Distribution {
+    /// The distribution of `samples`; all zeros if there are none.
+    pub fn new(mut samples: Vec<f64>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_by(f64::total_cmp);
+        let percentile = |p: f64| {
+            let rank = p / 100.0 * (samples.len() - 1) as f64;
+            let below = samples[rank.floor() as usize];
+            let above = samples[rank.ceil() as usize];
+            below + (above - below) * rank.fract()
+        };
+        Self {
+            count: samples.len(),
+            mean: samples.iter().sum::<f64>() / samples.len() as f64,
+            min: samples[0],
+            p5: percentile(5.0),
+            p25: percentile(25.0),
+            p50: percentile(50.0),
+            p75: percentile(75.0),
+            p95: percentile(95.0),
+            max: samples[samples.len() - 1],
+        }
+    }
+}
+
+/// How often a discount level was emptied over the runs of a batch.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TierEmptying {
+    pub discount: Discount,
+    /// Number of runs that emptied the level at least once.
+    pub runs: u64,
+    /// Fraction of the runs that emptied the level at least once.
+    pub frequency: f64,
+    /// How many times the level was emptied per run, runs that never
+    /// emptied it included.
+    pub per_run: Distribution,
+}
+
+/// The aggregated results of a [`Batch`].
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct BatchReport {
+    /// Number of runs.
+    pub runs: u64,
+    /// Seed of the first run.
+    pub first_seed: u64,
+    /// Fraction of the amount queued that was filled.
+    pub fill_rate: Distribution,
+    /// Average discount of the fills in percent, over the runs that filled
+    /// something.
+    pub average_discount: Distribution,
+    /// Amount queued that was not filled, in units of the bid asset.
+    pub unfilled: Distribution,
+    /// Every discount level emptied in some run, lowest first.
+    pub emptied_discounts: Vec<TierEmptying>,
+    /// Number of runs with failed steps.
+    pub runs_with_errors: u64,
+    /// The results of every run, by seed.
+    pub summaries: Vec<RunSummary>,
+}
+
+impl BatchReport {
+    /// Aggregates the results of the runs of a batch.
+    pub fn new(first_seed: u64, mut summaries: Vec<RunSummary>) -> Self {
+        summaries.sort_by_key(|summary| summary.seed.wrapping_sub(first_seed));
+        let runs = summaries.len() as u64;
+        let distribution = |value: fn(&RunSummary) -> Option<f64>| {
+            Distribution::new(summaries.iter().filter_map(value).collect())
+        };
+
+        let mut discounts: Vec<Discount> = summaries
+            .iter()
+            .flat_map(|summary| summary.emptied_discounts.keys().copied())
+            .collect();
+        discounts.sort();
+        discounts.dedup();
+        let emptied_discounts = discounts
+            .into_iter()
+            .map(|discount| {
+                let counts: Vec<u64> = summaries
+                    .iter()
+                    .map(|summary| {
+                        summary
+                            .emptied_discounts
+                            .get(&discount)
+                            .copied()
+                            .unwrap_or(0)
+                    })
+                    .collect();
+                let emptied = counts.iter().filter(|count| **count > 0).count() as u64;
+                TierEmptying {
+                    discount,
+                    runs: emptied,
+                    frequency: emptied as f64 / runs as f64,
+                    per_run: Distribution::new(
+                        counts.into_iter().map(|count| count as f64).collect(),
+                    ),
+                }
+            })
+            .collect();
+
+        Self {
+            runs,
+            first_seed,
+            fill_rate: distribution(|summary| Some(summary.fill_rate)),
+            average_discount: distribution(|summary| summary.average_discount),
+            unfilled: distribution(|summary| Some(summary.unfilled.to_f64())),
+            emptied_discounts,
+            runs_with_errors: summaries
+                .iter()
+                .filter(|summary| summary.errors > 0)
+                .count() as u64,
+            summaries,
+        }
+    }
+
+    /// Writes the report to a file as pretty-printed JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl Batch {
+    /// Runs the scenario once per seed and aggregates the results, failing
+    /// if the scenario cannot be set up, see [`Scenario::run`].
+    pub fn run(&self, scenario: &Scenario) -> Result<BatchReport, ScenarioError> {
+        let threads = self.threads.clamp(1, self.runs.max(1) as usize);
+        let run = |index: u64| {
+            let seed = self.first_seed.wrapping_add(index);
+            let outcome = Scenario {
+                seed,
+                ..scenario.clone()
+            }
+            .run()?;
+            Ok(RunSummary::new(
+                seed,
+                &outcome.metrics,
+                outcome.errors.len(),
+            ))
+        };
+
+        // thread `t` makes the runs `t`, `t + threads`, ...
+        let summaries: Result<Vec<_>, ScenarioError> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads as u64)
+                .map(|first| {
+                    scope.spawn(move || {
+                        (first..self.runs)
+                            .step_by(threads)
+                            .map(run)
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                })
+                .collect();
+            let mut summaries = Vec::new();
+            for worker in workers {
+                summaries.extend(worker.join().expect("batch run panicked")?);
+            }
+            Ok(summaries)
+        });
+        Ok(BatchReport::new(self.first_seed, summaries?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario() -> Scenario {
+        Scenario::from_json(
+            r#"{
+                "accounts": [
+                    { "account": 1, "bid_asset": 1000000 },
+                    { "account": 2, "bid_asset": 1000000 },
+                    { "account": 100, "collateral": 1000000 }
+                ],
+                "generator": { "bidders": 2, "arrival_rate": 2 },
+                "steps": [
+                    { "action": "generate_blocks", "blocks": 5 },
+                    { "action": "liquidate", "account": 100, "amount": 10000 }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_distribution() {
+        let distribution = Distribution::new(vec![4.0, 1.0, 3.0, 2.0, 5.0]);
+        assert_eq!(distribution.count, 5);
+        assert_eq!(distribution.mean, 3.0);
+        assert_eq!(distribution.min, 1.0);
+        assert_eq!(distribution.p25, 2.0);
+        assert_eq!(distribution.p50, 3.0);
+        assert_eq!(distribution.p95, 4.8);
+        assert_eq!(distribution.max, 5.0);
+        assert_eq!(Distribution::new(Vec::new()), Distribution::default());
+    }
+
+    #[test]
+    fn test_batch_does_not_depend_on_threads() {
+        let batch = Batch {
+            runs: 12,
+            first_seed: 40,
+            threads: 1,
+        };
+        let report = batch.run(&scenario()).unwrap();
+        let parallel = Batch {
+            threads: 5,
+            ..batch
+        }
+        .run(&scenario())
+        .unwrap();
+        assert_eq!(parallel, report);
+
+        assert_eq!(report.runs, 12);
+        let seeds: Vec<_> = report
+            .summaries
+            .iter()
+            .map(|summary| summary.seed)
+            .collect();
+        assert_eq!(seeds, (40..52).collect::<Vec<_>>());
+        assert_eq!(report.fill_rate.count, 12);
+        assert!(report.fill_rate.min <= report.fill_rate.p50);
+        assert!(report.fill_rate.p50 <= report.fill_rate.max);
+        assert!(report.fill_rate.max <= 1.0);
+
+        // every run can be replayed on its own
+        let replayed = Scenario {
+            seed: 45,
+            ..scenario()
+        }
+        .run()
+        .unwrap();
+        assert_eq!(
+            report.summaries[5],
+            RunSummary::new(45, &replayed.metrics, replayed.errors.len())
+        );
+    }
+
+    #[test]
+    fn test_emptied_discounts_are_counted_per_run() {
+        let summary = |seed, emptied: &[(u8, u64)]| RunSummary {
+            seed,
+            fill_rate: 1.0,
+            average_discount: None,
+            unfilled: Balance::ZERO,
+            emptied_discounts: emptied
+                .iter()
+                .map(|(percent, count)| (Discount::percent(*percent), *count))
+                .collect(),
+            errors: 0,
+        };
+        let report = BatchReport::new(
+            0,
+            vec![
+                summary(0, &[(1, 2), (3, 1)]),
+                summary(1, &[(1, 1)]),
+                summary(2, &[]),
+                summary(3, &[(1, 1)]),
+            ],
+        );
+
+        assert_eq!(report.average_discount.count, 0);
+        let tiers: Vec<_> = report
+            .emptied_discounts
+            .iter()
+            .map(|tier| (tier.discount, tier.runs, tier.frequency, tier.per_run.mean))
+            .collect();
+        assert_eq!(
+            tiers,
+            vec![
+                (Discount::percent(1), 3, 0.75, 1.0),
+                (Discount::percent(3), 1, 0.25, 0.25)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_setup_errors_stop_the_batch() {
+        let scenario = Scenario {
+            bids: vec![crate::scenario::BidPlacement {
+                bidder: 9,
+                amount: Balance::new(1000),
+                discount: Discount::percent(5),
+                expires_at: None,
+            }],
+            ..scenario()
+        };
+        let batch = Batch {
+            runs: 4,
+            threads: 2,
+            ..Default::default()
+        };
+        assert!(matches!(batch.run(&scenario), Err(ScenarioError::Setup(_))));
+    }
+}
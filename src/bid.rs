@@ -0,0 +1,319 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{AccountId, Balance, BlockIndex, BlockNumber, Discount, UserBid};
+
+/// Identifies a bid by the transaction that placed it: the block it was
+/// placed in and its index within that block.
+#[derive(
+    Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Serialize, Deserialize,
+)]
+pub struct BidId {
+    pub block: BlockNumber,
+    pub index: BlockIndex,
+}
+
+impl BidId {
+    /// The id of the `index`th transaction of `block`.
+    pub fn new(block: BlockNumber, index: BlockIndex) -> Self {
+        Self { block, index }
+    }
+}
+
+impl fmt::Display for BidId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.block, self.index)
+    }
+}
+
+/// Represents a bid placed in the liquidation system.
+///
+/// Bids are ordered by their priority key, see [`Bid::key`]: lowest discount
+/// first, then earliest block, then earliest transaction within the block,
+/// then bidder. Ids are unique within a book, so the key tells open bids
+/// apart; the remaining fields only break ties between otherwise equal keys
+/// so that the ordering agrees with `Eq`, as `BTreeSet` requires.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Bid<AccountId, Balance, BlockNumber> {
+    /// The account that placed the bid.
+    pub bidder: AccountId,
+    /// Amount the bidder is willing to spend in Bid Asset and the amount of the asset consumed so far.
+    pub amount: Balance,
+    /// Discount percentage asked by the bidder, within the range a market
+    /// accepts, see [`crate::BidRules`].
+    pub discount: Discount,
+    /// The block number when the bid was placed.
+    pub blocknumber: BlockNumber,
+    /// The sequential number of the transaction within the block.
+    pub index: BlockIndex,
+    /// The original amount of the bid.
+    pub original_amount: Balance,
+    /// Current status of the bid.
+    pub status: BidStatus,
+    /// The block the bid expires at, if any: it is swept from the book at
+    /// the start of that block, see [`crate::Market::expire_bids`].
+    #[serde(default)]
+    pub expires_at: Option<BlockNumber>,
+}
+
+impl<AccountId, Balance, BlockNumber> Bid<AccountId, Balance, BlockNumber> {
+    /// The priority key of the bid: discount, block number, index within the
+    /// block and bidder.
+    pub fn key(&self) -> (Discount, &BlockNumber, BlockIndex, &AccountId) {
+        (self.discount, &self.blocknumber, self.index, &self.bidder)
+    }
+}
+
+impl<AccountId: Ord, Balance: Ord, BlockNumber: Ord> Ord for Bid<AccountId, Balance, BlockNumber> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key()).then_with(|| {
+            (
+                &self.amount,
+                &self.original_amount,
+                &self.status,
+                &self.expires_at,
+            )
+                .cmp(&(
+                    &other.amount,
+                    &other.original_amount,
+                    &other.status,
+                    &other.expires_at,
+                ))
+        })
+    }
+}
+
+impl<AccountId: Ord, Balance: Ord, BlockNumber: Ord> PartialOrd
+    for Bid<AccountId, Balance, BlockNumber>
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Bid<AccountId, Balance, BlockNumber> {
+    /// The id of the transaction that placed the bid.
+    pub fn id(&self) -> BidId {
+        BidId::new(self.blocknumber, self.index)
+    }
+}
+
+/// Represents the possible statuses of a bid in the liquidation system.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize)]
+pub enum BidStatus {
+    /// The bid is currently active and available for fulfillment.
+    Active,
+    /// The bid has been partially fulfilled but not yet completed.
+    PartiallyFilled,
+    /// The bid has been fully fulfilled and is now closed.
+    Fulfilled,
+    /// The bid has been cancelled and is no longer available.
+    Cancelled,
+    /// The bid reached its expiry block before being filled.
+    Expired,
+}
+
+// create a random bid with values drawn from `rng`
+pub fn create_random_bid<R: Rng + ?Sized>(
+    rng: &mut R,
+    bidder: AccountId,
+    blocknumber: BlockNumber,
+    index: BlockIndex,
+) -> UserBid {
+    // create a random amount between 100 and 10000
+    let amount = Balance::from(rng.random_range(1..=99u32) * 100);
+    // create a random discount between 2 and 20, multiple of 2
+    let discount = Discount::percent(rng.random_range(1..=10) * 2);
+    create_bid(bidder, amount, discount, blocknumber, index)
+}
+
+// create a bid with a specific amount and discount
+pub fn create_bid(
+    bidder: AccountId,
+    amount: Balance,
+    discount: Discount,
+    blocknumber: BlockNumber,
+    index: BlockIndex,
+) -> UserBid {
+    UserBid {
+        bidder,
+        amount,
+        discount,
+        blocknumber,
+        index,
+        status: BidStatus::Active,
+        original_amount: amount,
+        expires_at: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SimRng;
+    use proptest::prelude::*;
+    use std::collections::BTreeSet;
+
+    const BOB: AccountId = 2;
+    const CHARLIE: AccountId = 3;
+
+    #[test]
+    fn test_bid_ordering() {
+        let mut bids = BTreeSet::new();
+
+        // Add bids with different discounts and indices
+        bids.insert(Bid {
+            bidder: BOB,
+            amount: Balance::new(100),
+            discount: Discount::percent(20),
+            blocknumber: 1,
+            index: 2,
+            original_amount: Balance::new(100),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+
+        bids.insert(Bid {
+            bidder: CHARLIE,
+            amount: Balance::new(100),
+            discount: Discount::percent(10),
+            blocknumber: 1,
+            index: 1,
+            original_amount: Balance::new(100),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+
+        // Lower discount should be processed first
+        let first_bid = bids.iter().next().unwrap();
+        assert_eq!(first_bid.discount, Discount::percent(10));
+        assert_eq!(first_bid.bidder, CHARLIE);
+    }
+
+    #[test]
+    fn test_earlier_blocks_come_first() {
+        let bid = |blocknumber, index| Bid {
+            bidder: BOB,
+            amount: Balance::new(100),
+            discount: Discount::percent(10),
+            blocknumber,
+            index,
+            original_amount: Balance::new(100),
+            status: BidStatus::Active,
+            expires_at: None,
+        };
+        let bids = BTreeSet::from([bid(2, 0), bid(1, 5), bid(1, 0)]);
+
+        let ids: Vec<_> = bids.iter().map(|bid| bid.id()).collect();
+        assert_eq!(
+            ids,
+            vec![BidId::new(1, 0), BidId::new(1, 5), BidId::new(2, 0)]
+        );
+    }
+
+    #[test]
+    fn test_random_bids_follow_the_seed() {
+        let bids = |seed| {
+            let mut rng = SimRng::new(seed);
+            (0..20)
+                .map(|index| create_random_bid(&mut rng, BOB, 7, index))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(bids(1), bids(1));
+        assert_ne!(bids(1), bids(2));
+        assert!(bids(1).iter().all(|bid| bid.blocknumber == 7
+            && (Balance::new(100)..=Balance::new(9_900)).contains(&bid.amount)
+            && bid.discount.bps() % 200 == 0
+            && (Discount::percent(2)..=Discount::percent(20)).contains(&bid.discount)));
+    }
+
+    #[test]
+    fn test_bidder_breaks_ties() {
+        let bid = |bidder| create_bid(bidder, Balance::new(100), Discount::percent(10), 1, 0);
+        let bids = BTreeSet::from([bid(CHARLIE), bid(BOB)]);
+
+        let bidders: Vec<_> = bids.iter().map(|bid| bid.bidder).collect();
+        assert_eq!(bidders, vec![BOB, CHARLIE]);
+    }
+
+    // narrow ranges, so that keys often collide
+    fn any_bid() -> impl Strategy<Value = UserBid> {
+        (
+            1..4u32,
+            1..4u128,
+            0..3u8,
+            0..3u64,
+            0..3u64,
+            0..5usize,
+            proptest::option::of(0..3u64),
+        )
+            .prop_map(
+                |(bidder, amount, discount, blocknumber, index, status, expires_at)| UserBid {
+                    bidder,
+                    amount: Balance::new(amount),
+                    discount: Discount::percent(discount),
+                    blocknumber,
+                    index,
+                    original_amount: Balance::new(amount * 2),
+                    status: [
+                        BidStatus::Active,
+                        BidStatus::PartiallyFilled,
+                        BidStatus::Fulfilled,
+                        BidStatus::Cancelled,
+                        BidStatus::Expired,
+                    ][status]
+                        .clone(),
+                    expires_at,
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn ordering_agrees_with_eq(a in any_bid(), b in any_bid()) {
+            prop_assert_eq!(a.cmp(&b) == Ordering::Equal, a == b);
+            prop_assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+            if a.key() != b.key() {
+                prop_assert_eq!(a.cmp(&b), a.key().cmp(&b.key()));
+            }
+        }
+
+        #[test]
+        fn btree_set_never_loses_bids(
+            bids in prop::collection::vec(any_bid(), 0..40),
+            removed in prop::collection::vec(any::<prop::sample::Index>(), 0..20),
+        ) {
+            let mut distinct: Vec<UserBid> = Vec::new();
+            for bid in &bids {
+                if !distinct.contains(bid) {
+                    distinct.push(bid.clone());
+                }
+            }
+
+            let mut set = BTreeSet::new();
+            for bid in &bids {
+                set.insert(bid.clone());
+            }
+            prop_assert_eq!(set.len(), distinct.len());
+            prop_assert!(distinct.iter().all(|bid| set.contains(bid)));
+            prop_assert!(set.iter().zip(set.iter().skip(1)).all(|(a, b)| a.key() <= b.key()));
+
+            if !distinct.is_empty() {
+                for index in &removed {
+                    let bid = index.get(&distinct).clone();
+                    set.remove(&bid);
+                    distinct.retain(|kept| *kept != bid);
+                    if distinct.is_empty() {
+                        break;
+                    }
+                }
+            }
+            prop_assert_eq!(set.len(), distinct.len());
+            prop_assert!(distinct.iter().all(|bid| set.contains(bid)));
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//! Headless runner for scripted liquidation scenarios.
+//!
+//! Runs every scenario file given on the command line through the engine and
+//! writes one JSON document per scenario with the fills, final book,
+//! liquidation statuses, balances and fill metrics, see [`ScenarioOutcome`].
+//! `--seed` overrides the seed of every scenario, to replay a run or try
+//! another one, and `--mechanism` the way liquidations are matched, to
+//! compare mechanisms on the same scenario. `--events` writes the events of
+//! every run to a file of JSON lines instead, each tagged with its scenario.
+//! `--tables` writes the fills, book depth and liquidation summaries of every
+//! run as CSV files to a directory, named after the scenario.
+//!
+//! `--runs` runs every scenario that many times instead, seeded one after
+//! the other from its seed, on `--threads` threads, and writes one
+//! [`BatchReport`] per scenario with the percentiles of the fill rate,
+//! average discount, unfilled amount and emptied discount levels.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use collateral_marketplace::scenario::{Scenario, ScenarioOutcome};
+use collateral_marketplace::{
+    book_depth, fill_history, liquidation_summaries, save_rows, Batch, BatchReport, DutchAuction,
+    Format, LoggedEvent, Mechanism,
+};
+use serde::Serialize;
+
+const USAGE: &str = "usage: cmsim [--output <file>] [--pretty] [--seed <n>] [--mechanism queue|dutch] [--events <file>] [--tables <dir>] [--runs <n> [--threads <n>]] <scenario.json>...";
+
+#[derive(Serialize)]
+struct Run<'a> {
+    scenario: &'a str,
+    #[serde(flatten)]
+    outcome: ScenarioOutcome,
+}
+
+#[derive(Serialize)]
+struct BatchRun<'a> {
+    scenario: &'a str,
+    #[serde(flatten)]
+    report: BatchReport,
+}
+
+#[derive(Serialize)]
+struct RunEvent<'a> {
+    scenario: &'a str,
+    #[serde(flatten)]
+    event: &'a LoggedEvent,
+}
+
+struct Args {
+    output: Option<String>,
+    events: Option<String>,
+    tables: Option<String>,
+    pretty: bool,
+    seed: Option<u64>,
+    mechanism: Option<Mechanism>,
+    runs: Option<u64>,
+    threads: usize,
+    scenarios: Vec<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        output: None,
+        events: None,
+        tables: None,
+        pretty: false,
+        seed: None,
+        mechanism: None,
+        runs: None,
+        threads: std::thread::available_parallelism().map_or(1, usize::from),
+        scenarios: Vec::new(),
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                args.output = Some(iter.next().ok_or("--output needs a file")?);
+            }
+            "--events" => {
+                args.events = Some(iter.next().ok_or("--events needs a file")?);
+            }
+            "--tables" => {
+                args.tables = Some(iter.next().ok_or("--tables needs a directory")?);
+            }
+            "--pretty" => args.pretty = true,
+            "--seed" => {
+                let seed = iter.next().ok_or("--seed needs a number")?;
+                args.seed = Some(
+                    seed.parse()
+                        .map_err(|_| format!("invalid seed {}\n{}", seed, USAGE))?,
+                );
+            }
+            "--mechanism" => {
+                args.mechanism = match iter.next().as_deref() {
+                    Some("queue") => Some(Mechanism::default()),
+                    Some("dutch") => Some(Mechanism::DutchAuction(DutchAuction::default())),
+                    _ => return Err(format!("--mechanism needs queue or dutch\n{}", USAGE)),
+                };
+            }
+            "--runs" => {
+                let runs = iter.next().ok_or("--runs needs a number")?;
+                args.runs = match runs.parse() {
+                    Ok(runs) if runs > 0 => Some(runs),
+                    _ => return Err(format!("invalid number of runs {}\n{}", runs, USAGE)),
+                };
+            }
+            "--threads" => {
+                let threads = iter.next().ok_or("--threads needs a number")?;
+                args.threads = match threads.parse() {
+                    Ok(threads) if threads > 0 => threads,
+                    _ => return Err(format!("invalid number of threads {}\n{}", threads, USAGE)),
+                };
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}\n{}", arg, USAGE)),
+            _ => args.scenarios.push(arg),
+        }
+    }
+    if args.scenarios.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    if args.runs.is_some() && (args.events.is_some() || args.tables.is_some()) {
+        return Err(format!(
+            "--runs cannot be combined with --events or --tables\n{}",
+            USAGE
+        ));
+    }
+    Ok(args)
+}
+
+// writes the fills, book depth and liquidations of a run to
+// `<dir>/<scenario>.<table>.csv`, creating the directory if needed
+fn write_tables(dir: &Path, scenario: &str, outcome: &ScenarioOutcome) -> io::Result<()> {
+    let name = Path::new(scenario)
+        .file_stem()
+        .map_or("scenario".into(), |stem| stem.to_string_lossy());
+    let file = |table: &str| dir.join(format!("{}.{}.csv", name, table));
+    let events = &outcome.events;
+    std::fs::create_dir_all(dir)?;
+    save_rows(&fill_history(events), Format::Csv, file("fills"))?;
+    let depth = book_depth(events).map_err(io::Error::other)?;
+    save_rows(&depth, Format::Csv, file("book_depth"))?;
+    save_rows(
+        &liquidation_summaries(&outcome.liquidations, events).map_err(io::Error::other)?,
+        Format::Csv,
+        file("liquidations"),
+    )
+}
+
+// writes `value` as a line of JSON, or as indented JSON if `pretty`
+fn write_json(out: &mut dyn Write, value: &impl Serialize, pretty: bool) -> io::Result<()> {
+    if pretty {
+        serde_json::to_writer_pretty(&mut *out, value)?;
+    } else {
+        serde_json::to_writer(&mut *out, value)?;
+    }
+    writeln!(out)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(err) => {
+                eprintln!("cannot create {}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let mut events = match &args.events {
+        Some(path) => match File::create(path) {
+            Ok(file) => Some(BufWriter::new(file)),
+            Err(err) => {
+                eprintln!("cannot create {}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+
+    let mut failed = false;
+    for path in &args.scenarios {
+        let scenario = Scenario::load(path).map(|scenario| Scenario {
+            seed: args.seed.unwrap_or(scenario.seed),
+            mechanism: args.mechanism.unwrap_or(scenario.mechanism),
+            ..scenario
+        });
+        if let Some(runs) = args.runs {
+            let batch = |scenario: Scenario| {
+                Batch {
+                    runs,
+                    first_seed: scenario.seed,
+                    threads: args.threads,
+                }
+                .run(&scenario)
+            };
+            let report = match scenario.and_then(batch) {
+                Ok(report) => report,
+                Err(err) => {
+                    eprintln!("{}: {}", path, err);
+                    failed = true;
+                    continue;
+                }
+            };
+            if report.runs_with_errors > 0 {
+                eprintln!(
+                    "{}: {} of {} runs had failed steps",
+                    path, report.runs_with_errors, report.runs
+                );
+            }
+            let run = BatchRun {
+                scenario: path,
+                report,
+            };
+            if let Err(err) = write_json(&mut out, &run, args.pretty) {
+                eprintln!("cannot write the report of {}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+            continue;
+        }
+
+        let mut outcome = match scenario.and_then(|scenario| scenario.run()) {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                failed = true;
+                continue;
+            }
+        };
+        for error in &outcome.errors {
+            eprintln!("{}: step {}: {}", path, error.step, error.message);
+        }
+        if let Some(dir) = &args.tables {
+            if let Err(err) = write_tables(Path::new(dir), path, &outcome) {
+                eprintln!("cannot write the tables of {}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        }
+        if let Some(events) = &mut events {
+            let written = std::mem::take(&mut outcome.events)
+                .iter()
+                .try_for_each(|event| {
+                    serde_json::to_writer(
+                        &mut *events,
+                        &RunEvent {
+                            scenario: path,
+                            event,
+                        },
+                    )?;
+                    writeln!(events)
+                });
+            if let Err(err) = written {
+                eprintln!("cannot write the events of {}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        }
+
+        let run = Run {
+            scenario: path,
+            outcome,
+        };
+        if let Err(err) = write_json(&mut out, &run, args.pretty) {
+            eprintln!("cannot write the outcome of {}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Err(err) = out.flush() {
+        eprintln!("cannot write the outcome: {}", err);
+        return ExitCode::FAILURE;
+    }
+    if let Some(Err(err)) = events.as_mut().map(|events| events.flush()) {
+        eprintln!("cannot write the events: {}", err);
+        return ExitCode::FAILURE;
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,721 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    apply_liquidation, liquidate, AccountError, AccountId, Balance, Bid, BidId, BidLedger,
+    BidStatus, BlockNumber, Discount, GeneratorError, LiquidationReport, Price, SettlementError,
+    SystemLiquidation, UserBid,
+};
+
+/// The open bids of the marketplace together with the bids that left it.
+///
+/// Open bids are indexed by id, so looking one up does not scan the book.
+/// Change them through the book's methods only, which keep the index in
+/// sync.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(from = "BookFields")]
+pub struct BidBook {
+    /// Open bids, lowest discount first.
+    pub bids: BTreeSet<UserBid>,
+    /// Bids fulfilled, cancelled or expired, see [`BidLedger`].
+    pub history: BidLedger,
+    // the discount and bidder of every open bid, by id: with the id, the
+    // start of the bid's priority key, see `Bid::key`
+    #[serde(skip)]
+    keys: BTreeMap<BidId, (Discount, AccountId)>,
+}
+
+// the saved fields of a book, its index being rebuilt from them
+#[derive(Deserialize)]
+struct BookFields {
+    bids: BTreeSet<UserBid>,
+    history: BidLedger,
+}
+
+impl From<BookFields> for BidBook {
+    fn from(fields: BookFields) -> Self {
+        let keys = fields
+            .bids
+            .iter()
+            .map(|bid| (bid.id(), (bid.discount, bid.bidder)))
+            .collect();
+        Self {
+            bids: fields.bids,
+            history: fields.history,
+            keys,
+        }
+    }
+}
+
+/// Reasons an operation on a bid is rejected.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum BidError {
+    /// No open bid has this id.
+    NotFound { id: BidId },
+    /// An open bid already has this id.
+    DuplicateId { id: BidId },
+    /// The bid belongs to another account.
+    NotOwner { id: BidId, bidder: AccountId },
+    /// The amount must be greater than zero.
+    ZeroAmount,
+    /// The amount is below the market's minimum bid.
+    BelowMinimum { amount: Balance, min: Balance },
+    /// The amount is not a multiple of the market's tick size.
+    OffTick { amount: Balance, tick: Balance },
+    /// The discount is outside the range the market accepts.
+    DiscountOutOfRange {
+        discount: Discount,
+        min: Discount,
+        max: Discount,
+    },
+    /// The discount is not one of the market's tiers.
+    OffStep { discount: Discount, step: Discount },
+    /// The bid would expire at or before the current block.
+    AlreadyExpired {
+        expires_at: BlockNumber,
+        now: BlockNumber,
+    },
+    /// The bidder's balance cannot cover the bid.
+    Account(AccountError),
+    /// A random bid cannot be drawn.
+    Generator(GeneratorError),
+}
+
+impl fmt::Display for BidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BidError::NotFound { id } => write!(f, "no open bid with id {}", id),
+            BidError::DuplicateId { id } => write!(f, "an open bid already has id {}", id),
+            BidError::NotOwner { id, bidder } => {
+                write!(f, "bid {} does not belong to account {}", id, bidder)
+            }
+            BidError::ZeroAmount => write!(f, "amount must be greater than zero"),
+            BidError::BelowMinimum { amount, min } => {
+                write!(f, "amount {} is below the minimum bid of {}", amount, min)
+            }
+            BidError::OffTick { amount, tick } => {
+                write!(
+                    f,
+                    "amount {} is not a multiple of the tick size {}",
+                    amount, tick
+                )
+            }
+            BidError::DiscountOutOfRange { discount, min, max } => write!(
+                f,
+                "discount {}% is outside the accepted range of {}% to {}%",
+                discount, min, max
+            ),
+            BidError::OffStep { discount, step } => write!(
+                f,
+                "discount {}% is not on a step of {}% from the minimum",
+                discount, step
+            ),
+            BidError::AlreadyExpired { expires_at, now } => write!(
+                f,
+                "bid would expire at block {} but block {} has been reached",
+                expires_at, now
+            ),
+            BidError::Account(err) => err.fmt(f),
+            BidError::Generator(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for BidError {}
+
+impl From<AccountError> for BidError {
+    fn from(err: AccountError) -> Self {
+        BidError::Account(err)
+    }
+}
+
+impl From<GeneratorError> for BidError {
+    fn from(err: GeneratorError) -> Self {
+        BidError::Generator(err)
+    }
+}
+
+impl BidBook {
+    /// Creates an empty book.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the open bid with this id.
+    pub fn get(&self, id: BidId) -> Option<&UserBid> {
+        let &(discount, bidder) = self.keys.get(&id)?;
+        // orders before every bid with this key, the fields after the key
+        // being at their lowest
+        let first = Bid {
+            bidder,
+            amount: Balance::ZERO,
+            discount,
+            blocknumber: id.block,
+            index: id.index,
+            original_amount: Balance::ZERO,
+            status: BidStatus::Active,
+            expires_at: None,
+        };
+        self.bids.range(first..).next().filter(|bid| bid.id() == id)
+    }
+
+    /// Adds a bid to the book and returns its id, unless an open bid already
+    /// has that id.
+    pub fn insert(&mut self, bid: UserBid) -> Result<BidId, BidError> {
+        let id = bid.id();
+        if self.keys.contains_key(&id) {
+            return Err(BidError::DuplicateId { id });
+        }
+        self.put(bid);
+        Ok(id)
+    }
+
+    /// Runs [`liquidate`] against the open bids and records the fills and
+    /// the fulfilled bids in [`BidBook::history`].
+    pub fn liquidate(
+        &mut self,
+        liquidation: &mut SystemLiquidation,
+        price: Price,
+    ) -> Result<LiquidationReport, SettlementError> {
+        let report = liquidate(&mut self.bids, liquidation, price)?;
+        self.forget_closed(&report);
+        self.history.record_liquidation(&report);
+        Ok(report)
+    }
+
+    /// Applies fills worked out beforehand, see [`apply_liquidation`], and
+    /// records them in [`BidBook::history`].
+    pub fn apply(
+        &mut self,
+        liquidation: &mut SystemLiquidation,
+        preview: LiquidationReport,
+    ) -> Result<LiquidationReport, SettlementError> {
+        let report = apply_liquidation(&mut self.bids, liquidation, preview)?;
+        self.forget_closed(&report);
+        self.history.record_liquidation(&report);
+        Ok(report)
+    }
+
+    /// Removes `bidder`'s open bid `id` from the book and records it in
+    /// [`BidBook::history`] with the [`BidStatus::Cancelled`] status.
+    pub fn cancel_bid(&mut self, bidder: AccountId, id: BidId) -> Result<&UserBid, BidError> {
+        let mut bid = self.take_owned(bidder, id)?;
+        bid.status = BidStatus::Cancelled;
+        self.history.record_cancelled(bid);
+        Ok(&self.history.closed().last().expect("just recorded").bid)
+    }
+
+    /// Removes the open bids expiring at `now` or earlier and records them in
+    /// [`BidBook::history`] with the [`BidStatus::Expired`] status. Returns
+    /// the expired bids, lowest discount first.
+    pub fn expire(&mut self, now: BlockNumber) -> Vec<UserBid> {
+        let expired: Vec<UserBid> = self.expiring(now).cloned().collect();
+        for bid in &expired {
+            self.bids.remove(bid);
+            self.keys.remove(&bid.id());
+            let mut bid = bid.clone();
+            bid.status = BidStatus::Expired;
+            self.history.record_expired(bid);
+        }
+        expired
+    }
+
+    /// The open bids expiring at `now` or earlier, lowest discount first.
+    pub fn expiring(&self, now: BlockNumber) -> impl Iterator<Item = &UserBid> {
+        self.bids
+            .iter()
+            .filter(move |bid| bid.expires_at.is_some_and(|expires_at| expires_at <= now))
+    }
+
+    /// Changes the remaining amount and discount of `bidder`'s open bid `id`
+    /// and returns the id the bid is queued under afterwards.
+    ///
+    /// Only decreasing the amount keeps the bid's place in the queue.
+    /// Changing the discount or increasing the amount moves it behind every
+    /// other bid at its discount by re-queuing it as `requeue_id`, which must
+    /// be the id of the next transaction and not already taken.
+    /// `original_amount` is adjusted by the same delta as the amount, so what
+    /// has been filled is preserved.
+    pub fn amend_bid(
+        &mut self,
+        bidder: AccountId,
+        id: BidId,
+        amount: Balance,
+        discount: Discount,
+        requeue_id: BidId,
+    ) -> Result<BidId, BidError> {
+        if amount.is_zero() {
+            return Err(BidError::ZeroAmount);
+        }
+        let mut bid = self.take_owned(bidder, id)?;
+        if discount != bid.discount || amount > bid.amount {
+            if self.keys.contains_key(&requeue_id) {
+                self.put(bid);
+                return Err(BidError::DuplicateId { id: requeue_id });
+            }
+            self.history.reindex(bid.id(), requeue_id);
+            bid.blocknumber = requeue_id.block;
+            bid.index = requeue_id.index;
+        }
+        bid.original_amount = bid.original_amount - bid.amount + amount;
+        bid.amount = amount;
+        bid.discount = discount;
+        let new_id = bid.id();
+        self.put(bid);
+        Ok(new_id)
+    }
+
+    // adds a bid whose id is not taken to the book and its index
+    fn put(&mut self, bid: UserBid) {
+        self.keys.insert(bid.id(), (bid.discount, bid.bidder));
+        self.bids.insert(bid);
+    }
+
+    // drops the bids a liquidation closed from the index; partially filled
+    // bids keep their id, discount and bidder
+    fn forget_closed(&mut self, report: &LiquidationReport) {
+        for bid in &report.closed {
+            self.keys.remove(&bid.id());
+        }
+    }
+
+    // removes the bid from the book if it exists and belongs to the bidder
+    fn take_owned(&mut self, bidder: AccountId, id: BidId) -> Result<UserBid, BidError> {
+        let bid = self.get(id).ok_or(BidError::NotFound { id })?;
+        if bid.bidder != bidder {
+            return Err(BidError::NotOwner { id, bidder });
+        }
+        let bid = bid.clone();
+        self.bids.remove(&bid);
+        self.keys.remove(&id);
+        Ok(bid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::btree_map::{BTreeMap, Entry};
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{BlockIndex, LiquidationStatus};
+
+    const ALICE: AccountId = 1;
+    const BOB: AccountId = 2;
+
+    // bids of (bidder, amount in units, discount, index)
+    fn book_with(bids: &[(AccountId, u128, u8, BlockIndex)]) -> BidBook {
+        let mut book = BidBook::new();
+        for &(bidder, amount, discount, index) in bids {
+            book.insert(Bid {
+                bidder,
+                amount: Balance::new(amount),
+                discount: Discount::percent(discount),
+                blocknumber: 1,
+                index,
+                original_amount: Balance::new(amount),
+                status: BidStatus::Active,
+                expires_at: None,
+            })
+            .unwrap();
+        }
+        book
+    }
+
+    // bids of `book_with` are all placed in block 1
+    fn id(index: BlockIndex) -> BidId {
+        BidId::new(1, index)
+    }
+
+    fn queue(book: &BidBook) -> Vec<BlockIndex> {
+        book.bids.iter().map(|bid| bid.index).collect()
+    }
+
+    #[test]
+    fn test_cancel_bid() {
+        let mut book = book_with(&[(ALICE, 100, 10, 1), (BOB, 200, 10, 2)]);
+
+        let cancelled = book.cancel_bid(ALICE, id(1)).unwrap();
+        assert_eq!(cancelled.status, BidStatus::Cancelled);
+        assert_eq!(cancelled.amount, Balance::new(100));
+
+        assert_eq!(queue(&book), vec![2]);
+        let closed = book.history.closed();
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].bid.index, 1);
+        assert_eq!(closed[0].status(), &BidStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_cancel_checks_ownership() {
+        let mut book = book_with(&[(ALICE, 100, 10, 1)]);
+
+        assert_eq!(
+            book.cancel_bid(BOB, id(1)).unwrap_err(),
+            BidError::NotOwner {
+                id: id(1),
+                bidder: BOB
+            }
+        );
+        assert_eq!(
+            book.cancel_bid(ALICE, id(7)).unwrap_err(),
+            BidError::NotFound { id: id(7) }
+        );
+        assert_eq!(queue(&book), vec![1]);
+        assert!(book.history.closed().is_empty());
+    }
+
+    #[test]
+    fn test_decreasing_amount_keeps_priority() {
+        let mut book = book_with(&[(ALICE, 500, 10, 1), (BOB, 200, 10, 2)]);
+
+        assert_eq!(
+            book.amend_bid(
+                ALICE,
+                id(1),
+                Balance::new(300),
+                Discount::percent(10),
+                id(3)
+            ),
+            Ok(id(1))
+        );
+
+        assert_eq!(queue(&book), vec![1, 2]);
+        let bid = book.get(id(1)).unwrap();
+        assert_eq!(bid.amount, Balance::new(300));
+        assert_eq!(bid.original_amount, Balance::new(300));
+    }
+
+    #[test]
+    fn test_increasing_amount_loses_priority() {
+        let mut book = book_with(&[(ALICE, 500, 10, 1), (BOB, 200, 10, 2)]);
+
+        assert_eq!(
+            book.amend_bid(
+                ALICE,
+                id(1),
+                Balance::new(800),
+                Discount::percent(10),
+                id(3)
+            ),
+            Ok(id(3))
+        );
+
+        assert_eq!(queue(&book), vec![2, 3]);
+        assert_eq!(book.get(id(3)).unwrap().amount, Balance::new(800));
+    }
+
+    #[test]
+    fn test_amending_discount_loses_priority() {
+        let mut book = book_with(&[(ALICE, 500, 10, 1), (BOB, 200, 5, 2)]);
+
+        // lowering the discount moves the bid ahead of the 10% tier but
+        // behind every bid already queued at 5%
+        assert_eq!(
+            book.amend_bid(ALICE, id(1), Balance::new(500), Discount::percent(5), id(3)),
+            Ok(id(3))
+        );
+
+        assert_eq!(queue(&book), vec![2, 3]);
+        assert_eq!(book.get(id(3)).unwrap().discount, Discount::percent(5));
+    }
+
+    #[test]
+    fn test_amend_preserves_filled_amount() {
+        let mut book = BidBook::new();
+        book.insert(Bid {
+            bidder: ALICE,
+            amount: Balance::new(400),
+            discount: Discount::percent(10),
+            blocknumber: 1,
+            index: 1,
+            original_amount: Balance::new(1000),
+            status: BidStatus::PartiallyFilled,
+            expires_at: None,
+        })
+        .unwrap();
+
+        book.amend_bid(
+            ALICE,
+            id(1),
+            Balance::new(100),
+            Discount::percent(10),
+            id(2),
+        )
+        .unwrap();
+
+        let bid = book.get(id(1)).unwrap();
+        assert_eq!(bid.original_amount - bid.amount, Balance::new(600));
+        assert_eq!(bid.status, BidStatus::PartiallyFilled);
+    }
+
+    #[test]
+    fn test_amend_rejects_zero_and_foreign_bids() {
+        let mut book = book_with(&[(ALICE, 500, 10, 1)]);
+
+        assert_eq!(
+            book.amend_bid(ALICE, id(1), Balance::ZERO, Discount::percent(10), id(2)),
+            Err(BidError::ZeroAmount)
+        );
+        assert_eq!(
+            book.amend_bid(BOB, id(1), Balance::new(100), Discount::percent(10), id(2)),
+            Err(BidError::NotOwner {
+                id: id(1),
+                bidder: BOB
+            })
+        );
+        assert_eq!(book.get(id(1)).unwrap().amount, Balance::new(500));
+    }
+
+    #[test]
+    fn test_ids_are_unique() {
+        let mut book = book_with(&[(ALICE, 500, 10, 1), (BOB, 200, 5, 2)]);
+
+        // same id, other bidder and discount
+        assert_eq!(
+            book.insert(crate::create_bid(
+                BOB,
+                Balance::new(100),
+                Discount::percent(20),
+                1,
+                1
+            )),
+            Err(BidError::DuplicateId { id: id(1) })
+        );
+        assert_eq!(
+            book.amend_bid(
+                ALICE,
+                id(1),
+                Balance::new(500),
+                Discount::percent(20),
+                id(2)
+            ),
+            Err(BidError::DuplicateId { id: id(2) })
+        );
+        assert_eq!(queue(&book), vec![2, 1]);
+        assert_eq!(book.get(id(1)).unwrap().discount, Discount::percent(10));
+    }
+
+    #[test]
+    fn test_lookups_follow_the_book() {
+        let mut book = book_with(&[(ALICE, 300, 5, 1), (BOB, 500, 5, 2), (ALICE, 400, 10, 3)]);
+        book.bids.iter().for_each(|bid| {
+            assert_eq!(book.get(bid.id()), Some(bid));
+        });
+
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            account_liquidated: BOB,
+            amount: Balance::new(500),
+            status: LiquidationStatus::Created,
+        };
+        book.liquidate(&mut liquidation, Price::ONE).unwrap();
+        assert_eq!(book.get(id(1)), None);
+        assert_eq!(book.get(id(2)).unwrap().amount, Balance::new(300));
+        // the id of a filled bid can be taken again
+        book.insert(crate::create_bid(
+            BOB,
+            Balance::new(100),
+            Discount::percent(20),
+            1,
+            1,
+        ))
+        .unwrap();
+
+        book.cancel_bid(ALICE, id(3)).unwrap();
+        assert_eq!(book.get(id(3)), None);
+
+        let restored: BidBook =
+            serde_json::from_str(&serde_json::to_string(&book).unwrap()).unwrap();
+        assert_eq!(restored.get(id(2)), book.get(id(2)));
+        assert_eq!(restored.get(id(1)).unwrap().discount, Discount::percent(20));
+        assert_eq!(restored.get(id(3)), None);
+    }
+
+    #[test]
+    fn test_expiry_survives_fills_and_amendments() {
+        let mut book = BidBook::new();
+        for (index, expires_at) in [(1, Some(5)), (2, None), (3, Some(8))] {
+            let mut bid =
+                crate::create_bid(ALICE, Balance::new(500), Discount::percent(10), 1, index);
+            bid.expires_at = expires_at;
+            book.insert(bid).unwrap();
+        }
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            account_liquidated: BOB,
+            amount: Balance::new(200),
+            status: LiquidationStatus::Created,
+        };
+        book.liquidate(&mut liquidation, Price::ONE).unwrap();
+        let new_id = book
+            .amend_bid(
+                ALICE,
+                id(3),
+                Balance::new(600),
+                Discount::percent(10),
+                id(4),
+            )
+            .unwrap();
+
+        assert!(book.expire(4).is_empty());
+        let expired = book.expire(5);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].amount, Balance::new(300));
+        assert_eq!(book.get(new_id).unwrap().expires_at, Some(8));
+        assert_eq!(book.expire(8)[0].id(), new_id);
+        assert_eq!(queue(&book), vec![2]);
+        let statuses: Vec<_> = book
+            .history
+            .closed()
+            .iter()
+            .map(|closed| closed.status())
+            .collect();
+        assert_eq!(statuses, vec![&BidStatus::Expired, &BidStatus::Expired]);
+        assert_eq!(book.history.closed()[0].total_filled, Balance::new(200));
+    }
+
+    #[test]
+    fn test_history_tracks_fills_across_liquidations() {
+        let mut book = book_with(&[(ALICE, 500, 10, 1), (BOB, 200, 10, 2)]);
+
+        let mut first = SystemLiquidation {
+            id: 7,
+            account_liquidated: 3,
+            amount: Balance::new(300),
+            status: LiquidationStatus::Created,
+        };
+        book.liquidate(&mut first, Price::ONE).unwrap();
+        assert!(book.history.closed().is_empty());
+        assert_eq!(book.history.open_fills(id(1)), &[7]);
+
+        let mut second = SystemLiquidation {
+            id: 8,
+            account_liquidated: 3,
+            amount: Balance::new(300),
+            status: LiquidationStatus::Created,
+        };
+        book.liquidate(&mut second, Price::ONE).unwrap();
+
+        let closed = book.history.closed();
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].bid.index, 1);
+        assert_eq!(closed[0].status(), &BidStatus::Fulfilled);
+        assert_eq!(closed[0].bid.original_amount, Balance::new(500));
+        assert_eq!(closed[0].total_filled, Balance::new(500));
+        assert_eq!(closed[0].liquidations, vec![7, 8]);
+        assert!(book.history.open_fills(id(1)).is_empty());
+        assert_eq!(book.history.open_fills(id(2)), &[8]);
+    }
+
+    #[test]
+    fn test_history_by_bidder() {
+        let mut book = book_with(&[(ALICE, 100, 10, 1), (BOB, 200, 10, 2), (ALICE, 300, 5, 3)]);
+
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            account_liquidated: 3,
+            amount: Balance::new(350),
+            status: LiquidationStatus::Created,
+        };
+        book.liquidate(&mut liquidation, Price::ONE).unwrap();
+        book.cancel_bid(BOB, id(2)).unwrap();
+
+        let alice: Vec<_> = book.history.by_bidder(ALICE).collect();
+        assert_eq!(alice.len(), 1);
+        assert_eq!(alice[0].bid.index, 3);
+        assert_eq!(alice[0].liquidations, vec![1]);
+
+        let bob: Vec<_> = book.history.by_bidder(BOB).collect();
+        assert_eq!(bob.len(), 1);
+        assert_eq!(bob[0].status(), &BidStatus::Cancelled);
+        assert_eq!(bob[0].total_filled, Balance::ZERO);
+        assert!(bob[0].liquidations.is_empty());
+    }
+
+    #[test]
+    fn test_history_follows_requeued_bid() {
+        let mut book = book_with(&[(ALICE, 500, 10, 1)]);
+
+        let mut liquidation = SystemLiquidation {
+            id: 4,
+            account_liquidated: 3,
+            amount: Balance::new(100),
+            status: LiquidationStatus::Created,
+        };
+        book.liquidate(&mut liquidation, Price::ONE).unwrap();
+        assert_eq!(
+            book.amend_bid(ALICE, id(1), Balance::new(400), Discount::percent(5), id(2)),
+            Ok(id(2))
+        );
+        book.cancel_bid(ALICE, id(2)).unwrap();
+
+        let closed = &book.history.closed()[0];
+        assert_eq!(closed.total_filled, Balance::new(100));
+        assert_eq!(closed.liquidations, vec![4]);
+    }
+
+    proptest! {
+        // random placements, cancellations and amendments never lose a bid
+        // nor leave two open bids with the same id
+        #[test]
+        fn book_keeps_every_bid(
+            ops in prop::collection::vec((0..3u8, 1..4u32, 0..8u64, 1..1_000u32, 0..5u8), 0..60)
+        ) {
+            let mut book = BidBook::new();
+            let mut model: BTreeMap<BidId, (AccountId, Balance, Discount)> = BTreeMap::new();
+            let mut next = 0;
+            for (op, bidder, index, amount, discount) in ops {
+                let amount = Balance::from(amount);
+                let discount = Discount::percent(discount);
+                let target = id(index);
+                match op {
+                    0 => {
+                        let result = book.insert(crate::create_bid(bidder, amount, discount, 1, index));
+                        match model.entry(target) {
+                            Entry::Occupied(_) => prop_assert_eq!(
+                                result,
+                                Err(BidError::DuplicateId { id: target })
+                            ),
+                            Entry::Vacant(entry) => {
+                                prop_assert_eq!(result, Ok(target));
+                                entry.insert((bidder, amount, discount));
+                            }
+                        }
+                    }
+                    1 => {
+                        let owned = model.get(&target).is_some_and(|bid| bid.0 == bidder);
+                        prop_assert_eq!(book.cancel_bid(bidder, target).is_ok(), owned);
+                        if owned {
+                            model.remove(&target);
+                        }
+                    }
+                    _ => {
+                        let requeue_id = BidId::new(2, next);
+                        let owned = model.get(&target).is_some_and(|bid| bid.0 == bidder);
+                        let result = book.amend_bid(bidder, target, amount, discount, requeue_id);
+                        prop_assert_eq!(result.is_ok(), owned);
+                        if let Ok(new_id) = result {
+                            model.remove(&target);
+                            model.insert(new_id, (bidder, amount, discount));
+                            if new_id == requeue_id {
+                                next += 1;
+                            }
+                        }
+                    }
+                }
+
+                let open: BTreeMap<_, _> = book
+                    .bids
+                    .iter()
+                    .map(|bid| (bid.id(), (bid.bidder, bid.amount, bid.discount)))
+                    .collect();
+                prop_assert_eq!(book.bids.len(), open.len());
+                prop_assert_eq!(&open, &model);
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{BlockIndex, BlockNumber};
+
+/// Simulated time, counted in blocks.
+///
+/// The simulation never reads the wall clock: bids are stamped with the
+/// current block of the market's clock, which only moves when it is advanced,
+/// and with their index among the transactions of that block.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Clock {
+    block: BlockNumber,
+    transactions: BlockIndex,
+}
+
+impl Clock {
+    /// Creates a clock starting at `block`.
+    pub fn new(block: BlockNumber) -> Self {
+        Self {
+            block,
+            transactions: 0,
+        }
+    }
+
+    /// The current block.
+    pub fn now(&self) -> BlockNumber {
+        self.block
+    }
+
+    /// Number of transactions in the current block so far, which is also the
+    /// index the next one gets.
+    pub fn transactions(&self) -> BlockIndex {
+        self.transactions
+    }
+
+    /// Records a transaction in the current block and returns its index.
+    pub fn next_transaction(&mut self) -> BlockIndex {
+        let index = self.transactions;
+        self.transactions += 1;
+        index
+    }
+
+    /// Moves the clock `blocks` forward and returns the new current block.
+    pub fn advance(&mut self, blocks: BlockNumber) -> BlockNumber {
+        if blocks > 0 {
+            self.block = self.block.saturating_add(blocks);
+            self.transactions = 0;
+        }
+        self.block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transactions_restart_every_block() {
+        let mut clock = Clock::new(10);
+        assert_eq!(clock.next_transaction(), 0);
+        assert_eq!(clock.next_transaction(), 1);
+        assert_eq!(clock.advance(0), 10);
+        assert_eq!(clock.transactions(), 2);
+
+        assert_eq!(clock.advance(5), 15);
+        assert_eq!(clock.transactions(), 0);
+        assert_eq!(clock.next_transaction(), 0);
+    }
+}
@@ -0,0 +1,210 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A discount off the oracle price, counted in basis points (hundredths of a
+/// percent).
+///
+/// Discounts are written as a number of percent, e.g. `5` or `2.5`, with at
+/// most two decimal places. Bids of the same discount form a tier; which
+/// tiers a market accepts is set by its [`crate::BidRules`].
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct Discount(u16);
+
+impl Discount {
+    /// No discount at all.
+    pub const ZERO: Discount = Discount(0);
+    /// The whole price, which no bid can be settled at.
+    pub const FULL: Discount = Discount(10_000);
+    /// Basis points in one percent.
+    pub const BPS_PER_PERCENT: u16 = 100;
+
+    /// A discount of `percent` whole percent.
+    pub const fn percent(percent: u8) -> Self {
+        Discount(percent as u16 * Self::BPS_PER_PERCENT)
+    }
+
+    /// A discount of `bps` basis points.
+    pub const fn from_bps(bps: u16) -> Self {
+        Discount(bps)
+    }
+
+    /// The number of basis points.
+    pub const fn bps(self) -> u16 {
+        self.0
+    }
+
+    /// The discount in percent.
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::BPS_PER_PERCENT as f64
+    }
+
+    /// The discount of `percent` percent rounded to the nearest basis point,
+    /// `None` outside 0% to 100%.
+    pub fn from_f64(percent: f64) -> Option<Self> {
+        let bps = (percent * Self::BPS_PER_PERCENT as f64).round();
+        (0.0..=Self::FULL.0 as f64)
+            .contains(&bps)
+            .then_some(Discount(bps as u16))
+    }
+}
+
+impl fmt::Debug for Discount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", self)
+    }
+}
+
+/// Writes the discount in percent, without trailing zeros.
+impl fmt::Display for Discount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let whole = self.0 / Self::BPS_PER_PERCENT;
+        match self.0 % Self::BPS_PER_PERCENT {
+            0 => write!(f, "{}", whole),
+            hundredths if hundredths % 10 == 0 => write!(f, "{}.{}", whole, hundredths / 10),
+            hundredths => write!(f, "{}.{:02}", whole, hundredths),
+        }
+    }
+}
+
+/// The string is not a discount between 0% and 100% in basis points.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseDiscountError(String);
+
+impl fmt::Display for ParseDiscountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid discount {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseDiscountError {}
+
+/// Parses a number of percent such as `"2.5"` or `"2.5%"`.
+impl FromStr for Discount {
+    type Err = ParseDiscountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseDiscountError(s.to_string());
+        let number = s.trim().trim_end_matches('%');
+        let (whole, hundredths) = number.split_once('.').unwrap_or((number, ""));
+        if whole.is_empty() && hundredths.is_empty()
+            || hundredths.len() > 2
+            || !whole
+                .chars()
+                .chain(hundredths.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let whole: u32 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| invalid())?
+        };
+        let hundredths: u32 = format!("{:0<2}", hundredths)
+            .parse()
+            .map_err(|_| invalid())?;
+        let bps = whole
+            .checked_mul(Self::BPS_PER_PERCENT as u32)
+            .and_then(|bps| bps.checked_add(hundredths))
+            .filter(|bps| *bps <= Self::FULL.0 as u32)
+            .ok_or_else(invalid)?;
+        Ok(Discount(bps as u16))
+    }
+}
+
+/// Whole percents are written as integers, the others as decimals.
+impl Serialize for Discount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0.is_multiple_of(Self::BPS_PER_PERCENT) {
+            serializer.serialize_u64((self.0 / Self::BPS_PER_PERCENT) as u64)
+        } else {
+            serializer.serialize_f64(self.to_f64())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Discount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DiscountVisitor)
+    }
+}
+
+struct DiscountVisitor;
+
+impl Visitor<'_> for DiscountVisitor {
+    type Value = Discount;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a percentage between 0 and 100 in basis points")
+    }
+
+    fn visit_u64<E: de::Error>(self, percent: u64) -> Result<Discount, E> {
+        u8::try_from(percent)
+            .ok()
+            .filter(|percent| *percent <= 100)
+            .map(Discount::percent)
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Unsigned(percent), &self))
+    }
+
+    fn visit_i64<E: de::Error>(self, percent: i64) -> Result<Discount, E> {
+        u64::try_from(percent)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(percent), &self))
+            .and_then(|percent| self.visit_u64(percent))
+    }
+
+    fn visit_f64<E: de::Error>(self, percent: f64) -> Result<Discount, E> {
+        Discount::from_f64(percent)
+            .filter(|discount| (discount.to_f64() - percent).abs() < 1e-9)
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Float(percent), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Discount, E> {
+        s.parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(s), &self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_and_parse() {
+        assert_eq!(Discount::percent(5).to_string(), "5");
+        assert_eq!(Discount::from_bps(250).to_string(), "2.5");
+        assert_eq!(Discount::from_bps(1).to_string(), "0.01");
+        assert_eq!(format!("{:?}", Discount::from_bps(1_050)), "10.5%");
+        assert_eq!("2.5".parse(), Ok(Discount::from_bps(250)));
+        assert_eq!("2.5%".parse(), Ok(Discount::from_bps(250)));
+        assert_eq!(".05".parse(), Ok(Discount::from_bps(5)));
+        assert_eq!("100".parse(), Ok(Discount::FULL));
+        for invalid in ["", ".", "100.01", "2.555", "-1", "1.2.3", "256"] {
+            assert!(invalid.parse::<Discount>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        assert_eq!(serde_json::to_string(&Discount::percent(5)).unwrap(), "5");
+        assert_eq!(
+            serde_json::to_string(&Discount::from_bps(250)).unwrap(),
+            "2.5"
+        );
+        for (json, bps) in [("5", 500), ("2.5", 250), ("0.01", 1), ("\"7.25\"", 725)] {
+            assert_eq!(
+                serde_json::from_str::<Discount>(json).unwrap(),
+                Discount::from_bps(bps)
+            );
+        }
+        for invalid in ["101", "-1", "2.555", "\"x\""] {
+            assert!(
+                serde_json::from_str::<Discount>(invalid).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Balance;
+
+/// What a fill does with a bid it would leave below the minimum remaining
+/// amount, so that partial fills do not clog the front of the book with dust.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DustPolicy {
+    /// The dust stays in the book as a bid of its own.
+    #[default]
+    Keep,
+    /// The fill consumes the whole bid: the bid leaves the book and the dust
+    /// is refunded to the bidder.
+    Consume,
+    /// The fill is rounded down so that the bid keeps the minimum remaining
+    /// amount; what it no longer covers goes to the next bids.
+    Round,
+}
+
+/// The smallest amount a partially filled bid may keep in the book, and what
+/// happens to the bids a fill would leave with less.
+///
+/// With the default `min_remaining` of zero no bid is ever dust.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DustRule {
+    /// Partially filled bids left with less than this are dust.
+    pub min_remaining: Balance,
+    /// What is done with them.
+    pub policy: DustPolicy,
+}
+
+impl DustRule {
+    /// Whether a bid left with `remaining` in the book is dust.
+    pub fn is_dust(&self, remaining: Balance) -> bool {
+        !remaining.is_zero() && remaining < self.min_remaining
+    }
+
+    /// Applies the rule to the `shares` a tier of bids with these remaining
+    /// `amounts` was allocated, given in book order.
+    ///
+    /// Returns the share each bid is filled with and the dust refunded to
+    /// it. Only [`DustPolicy::Round`] changes the shares: they add up to at
+    /// most what they did, the units taken off a bid going to the bids after
+    /// it where they leave no dust.
+    pub fn apply(&self, amounts: &[Balance], shares: &[Balance]) -> Vec<(Balance, Balance)> {
+        match self.policy {
+            DustPolicy::Keep => shares.iter().map(|share| (*share, Balance::ZERO)).collect(),
+            DustPolicy::Consume => amounts
+                .iter()
+                .zip(shares)
+                .map(|(amount, share)| {
+                    let remaining = *amount - *share;
+                    if !share.is_zero() && self.is_dust(remaining) {
+                        (*share, remaining)
+                    } else {
+                        (*share, Balance::ZERO)
+                    }
+                })
+                .collect(),
+            DustPolicy::Round => {
+                let mut rounded = Vec::with_capacity(shares.len());
+                let mut spare = Balance::ZERO;
+                for (amount, share) in amounts.iter().zip(shares) {
+                    let wanted = share.saturating_add(spare);
+                    let fill = if wanted >= *amount {
+                        *amount
+                    } else {
+                        // the most the bid gives while keeping the minimum,
+                        // no less than its share unless that leaves dust
+                        wanted.min(amount.saturating_sub(self.min_remaining))
+                    };
+                    spare = wanted.saturating_sub(fill);
+                    rounded.push((fill, Balance::ZERO));
+                }
+                rounded
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balances(units: &[u128]) -> Vec<Balance> {
+        units.iter().map(|units| Balance::new(*units)).collect()
+    }
+
+    fn applied(rule: &DustRule, amounts: &[u128], shares: &[u128]) -> Vec<(u128, u128)> {
+        rule.apply(&balances(amounts), &balances(shares))
+            .into_iter()
+            .map(|(share, dust)| (share.units(), dust.units()))
+            .collect()
+    }
+
+    #[test]
+    fn test_keep_leaves_dust() {
+        let rule = DustRule {
+            min_remaining: Balance::new(10),
+            policy: DustPolicy::Keep,
+        };
+        assert_eq!(applied(&rule, &[100, 50], &[95, 0]), vec![(95, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn test_consume_refunds_dust() {
+        let rule = DustRule {
+            min_remaining: Balance::new(10),
+            policy: DustPolicy::Consume,
+        };
+        assert_eq!(
+            applied(&rule, &[100, 100, 100, 5], &[100, 95, 80, 0]),
+            vec![(100, 0), (95, 5), (80, 0), (0, 0)]
+        );
+    }
+
+    #[test]
+    fn test_round_keeps_the_minimum() {
+        let rule = DustRule {
+            min_remaining: Balance::new(10),
+            policy: DustPolicy::Round,
+        };
+        // first in first out: the 5 units the first bid cannot give go to
+        // the next one
+        assert_eq!(applied(&rule, &[100, 100], &[95, 0]), vec![(90, 0), (5, 0)]);
+        // pro-rata: shares leaving no dust are kept as they are, and the
+        // units the last bid cannot give are left over
+        assert_eq!(
+            applied(&rule, &[100, 100, 100], &[50, 45, 95]),
+            vec![(50, 0), (45, 0), (90, 0)]
+        );
+        // a bid below the minimum can only be filled entirely
+        assert_eq!(applied(&rule, &[8, 100], &[4, 0]), vec![(0, 0), (4, 0)]);
+        // with nothing after it the units are left over
+        assert_eq!(applied(&rule, &[100], &[95]), vec![(90, 0)]);
+    }
+
+    #[test]
+    fn test_no_dust_without_a_minimum() {
+        for policy in [DustPolicy::Keep, DustPolicy::Consume, DustPolicy::Round] {
+            let rule = DustRule {
+                min_remaining: Balance::ZERO,
+                policy,
+            };
+            assert_eq!(applied(&rule, &[100, 100], &[99, 1]), vec![(99, 0), (1, 0)]);
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AccountId, Balance, BidId, BlockIndex, BlockNumber, Discount, LiquidationId, LiquidationStatus,
+    Price,
+};
+
+/// Something that happened in a [`crate::Market`].
+///
+/// Events are written to JSON with their kind in the `event` field, e.g.
+/// `{"event": "bid_cancelled", "id": {"block": 3, "index": 0}, ...}`.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum MarketEvent {
+    /// Assets were minted to an account.
+    AccountFunded {
+        account: AccountId,
+        bid_asset: Balance,
+        collateral: Balance,
+    },
+    /// A bid entered the book.
+    BidPlaced {
+        id: BidId,
+        bidder: AccountId,
+        amount: Balance,
+        discount: Discount,
+        expires_at: Option<BlockNumber>,
+    },
+    /// A bid was amended; it is queued under `new_id` afterwards.
+    BidAmended {
+        id: BidId,
+        new_id: BidId,
+        bidder: AccountId,
+        amount: Balance,
+        discount: Discount,
+    },
+    /// A bid was cancelled and what it had left refunded.
+    BidCancelled {
+        id: BidId,
+        bidder: AccountId,
+        refunded: Balance,
+    },
+    /// A bid expired and what it had left was refunded.
+    BidExpired {
+        id: BidId,
+        bidder: AccountId,
+        refunded: Balance,
+    },
+    /// A liquidation filled part of a bid, which stays in the book.
+    BidPartiallyFilled {
+        id: BidId,
+        bidder: AccountId,
+        liquidation: LiquidationId,
+        discount: Discount,
+        spent: Balance,
+        collateral: Balance,
+        remaining: Balance,
+    },
+    /// A liquidation filled what was left of a bid, which left the book;
+    /// `dust` is what was refunded to the bidder, see
+    /// [`crate::DustPolicy::Consume`].
+    BidFilled {
+        id: BidId,
+        bidder: AccountId,
+        liquidation: LiquidationId,
+        discount: Discount,
+        spent: Balance,
+        collateral: Balance,
+        dust: Balance,
+    },
+    /// A liquidation was queued.
+    LiquidationCreated {
+        id: LiquidationId,
+        account: AccountId,
+        amount: Balance,
+    },
+    /// A liquidation was run against the book and settled.
+    LiquidationSettled {
+        id: LiquidationId,
+        filled: Balance,
+        collateral: Balance,
+        remaining: Balance,
+        status: LiquidationStatus,
+    },
+    /// A liquidation could not be settled and stays pending.
+    LiquidationFailed { id: LiquidationId, reason: String },
+    /// A pending liquidation was cancelled.
+    LiquidationCancelled { id: LiquidationId },
+    /// The oracle price moved.
+    PriceChanged { price: Price },
+}
+
+impl MarketEvent {
+    /// Every kind of event, as written in the `event` field.
+    pub const KINDS: [&'static str; 12] = [
+        "account_funded",
+        "bid_placed",
+        "bid_amended",
+        "bid_cancelled",
+        "bid_expired",
+        "bid_partially_filled",
+        "bid_filled",
+        "liquidation_created",
+        "liquidation_settled",
+        "liquidation_failed",
+        "liquidation_cancelled",
+        "price_changed",
+    ];
+
+    /// The kind of the event, as written in the `event` field.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MarketEvent::AccountFunded { .. } => "account_funded",
+            MarketEvent::BidPlaced { .. } => "bid_placed",
+            MarketEvent::BidAmended { .. } => "bid_amended",
+            MarketEvent::BidCancelled { .. } => "bid_cancelled",
+            MarketEvent::BidExpired { .. } => "bid_expired",
+            MarketEvent::BidPartiallyFilled { .. } => "bid_partially_filled",
+            MarketEvent::BidFilled { .. } => "bid_filled",
+            MarketEvent::LiquidationCreated { .. } => "liquidation_created",
+            MarketEvent::LiquidationSettled { .. } => "liquidation_settled",
+            MarketEvent::LiquidationFailed { .. } => "liquidation_failed",
+            MarketEvent::LiquidationCancelled { .. } => "liquidation_cancelled",
+            MarketEvent::PriceChanged { .. } => "price_changed",
+        }
+    }
+}
+
+impl fmt::Display for MarketEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarketEvent::AccountFunded {
+                account,
+                bid_asset,
+                collateral,
+            } => write!(
+                f,
+                "account {} funded with {} bid asset and {} collateral",
+                account, bid_asset, collateral
+            ),
+            MarketEvent::BidPlaced {
+                id,
+                bidder,
+                amount,
+                discount,
+                expires_at,
+            } => {
+                write!(
+                    f,
+                    "bid {} placed by account {}: {} at {}%",
+                    id, bidder, amount, discount
+                )?;
+                match expires_at {
+                    Some(block) => write!(f, ", expiring at block {}", block),
+                    None => Ok(()),
+                }
+            }
+            MarketEvent::BidAmended {
+                id,
+                new_id,
+                bidder,
+                amount,
+                discount,
+            } => write!(
+                f,
+                "bid {} amended by account {} to {} at {}%, now {}",
+                id, bidder, amount, discount, new_id
+            ),
+            MarketEvent::BidCancelled {
+                id,
+                bidder,
+                refunded,
+            } => write!(
+                f,
+                "bid {} cancelled, {} refunded to account {}",
+                id, refunded, bidder
+            ),
+            MarketEvent::BidExpired {
+                id,
+                bidder,
+                refunded,
+            } => write!(
+                f,
+                "bid {} expired, {} refunded to account {}",
+                id, refunded, bidder
+            ),
+            MarketEvent::BidPartiallyFilled {
+                id,
+                liquidation,
+                discount,
+                spent,
+                collateral,
+                remaining,
+                ..
+            } => write!(
+                f,
+                "bid {} partially filled by liquidation {}: {} for {} collateral at {}%, {} left",
+                id, liquidation, spent, collateral, discount, remaining
+            ),
+            MarketEvent::BidFilled {
+                id,
+                liquidation,
+                discount,
+                spent,
+                collateral,
+                dust,
+                ..
+            } => {
+                write!(
+                    f,
+                    "bid {} filled by liquidation {}: {} for {} collateral at {}%",
+                    id, liquidation, spent, collateral, discount
+                )?;
+                if dust.is_zero() {
+                    Ok(())
+                } else {
+                    write!(f, ", {} dust refunded", dust)
+                }
+            }
+            MarketEvent::LiquidationCreated {
+                id,
+                account,
+                amount,
+            } => write!(
+                f,
+                "liquidation {} of {} queued against account {}",
+                id, amount, account
+            ),
+            MarketEvent::LiquidationSettled {
+                id,
+                filled,
+                collateral,
+                remaining,
+                status,
+            } => write!(
+                f,
+                "liquidation {} settled: {} filled for {} collateral, {} remaining ({:?})",
+                id, filled, collateral, remaining, status
+            ),
+            MarketEvent::LiquidationFailed { id, reason } => {
+                write!(f, "liquidation {} failed: {}", id, reason)
+            }
+            MarketEvent::LiquidationCancelled { id } => {
+                write!(f, "liquidation {} cancelled", id)
+            }
+            MarketEvent::PriceChanged { price } => write!(f, "price set to {}", price),
+        }
+    }
+}
+
+/// A [`MarketEvent`] with the block it happened in and its index among the
+/// events of that block.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LoggedEvent {
+    pub block: BlockNumber,
+    pub index: BlockIndex,
+    #[serde(flatten)]
+    pub event: MarketEvent,
+}
+
+impl fmt::Display for LoggedEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}:{}] {}", self.block, self.index, self.event)
+    }
+}
+
+/// Every event of a market, in the order they happened.
+///
+/// Events are indexed from zero within each block. The log can be written
+/// as JSON lines, one [`LoggedEvent`] per line, for analysis elsewhere.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EventLog {
+    events: Vec<LoggedEvent>,
+}
+
+impl EventLog {
+    /// Creates an empty log.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `event` as having happened in `block`.
+    pub fn record(&mut self, block: BlockNumber, event: MarketEvent) {
+        let index = match self.events.last() {
+            Some(last) if last.block == block => last.index + 1,
+            _ => 0,
+        };
+        self.events.push(LoggedEvent {
+            block,
+            index,
+            event,
+        });
+    }
+
+    /// Every event recorded, oldest first.
+    pub fn events(&self) -> &[LoggedEvent] {
+        &self.events
+    }
+
+    /// Iterates over the events, oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &LoggedEvent> {
+        self.events.iter()
+    }
+
+    /// Number of events recorded.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Whether no event has been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Writes every event as a line of JSON.
+    pub fn write_jsonl<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for event in &self.events {
+            serde_json::to_writer(&mut writer, event)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+
+    /// Writes the log as JSON lines to a file, see [`EventLog::write_jsonl`].
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.write_jsonl(io::BufWriter::new(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cancelled(block: BlockNumber) -> MarketEvent {
+        MarketEvent::BidCancelled {
+            id: BidId::new(block, 0),
+            bidder: 1,
+            refunded: Balance::new(100),
+        }
+    }
+
+    #[test]
+    fn test_events_are_indexed_per_block() {
+        let mut log = EventLog::new();
+        log.record(0, cancelled(0));
+        log.record(0, MarketEvent::PriceChanged { price: Price::ONE });
+        log.record(3, cancelled(3));
+        log.record(3, MarketEvent::LiquidationCancelled { id: 1 });
+
+        let positions: Vec<_> = log.iter().map(|event| (event.block, event.index)).collect();
+        assert_eq!(positions, vec![(0, 0), (0, 1), (3, 0), (3, 1)]);
+        assert_eq!(log.len(), 4);
+    }
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        let mut log = EventLog::new();
+        log.record(2, cancelled(1));
+        log.record(
+            2,
+            MarketEvent::LiquidationSettled {
+                id: 0,
+                filled: Balance::new(100),
+                collateral: Balance::new(110),
+                remaining: Balance::ZERO,
+                status: LiquidationStatus::Fulfilled,
+            },
+        );
+
+        let mut written = Vec::new();
+        log.write_jsonl(&mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        let lines: Vec<_> = written.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(r#"{"block":2,"index":0,"event":"bid_cancelled""#));
+
+        let read: Vec<LoggedEvent> = lines
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(read, log.events());
+    }
+
+    #[test]
+    fn test_kinds_match_the_json_tag() {
+        let event = cancelled(0);
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], event.kind());
+        assert!(MarketEvent::KINDS.contains(&event.kind()));
+    }
+}
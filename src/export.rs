@@ -0,0 +1,552 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::metrics::average_discount;
+use crate::{
+    AccountId, Balance, BalanceOverflow, BidId, BlockIndex, BlockNumber, Discount, LiquidationId,
+    LiquidationStatus, LoggedEvent, MarketEvent, QueuedLiquidation,
+};
+
+/// A row of a table written by [`write_rows`].
+pub trait Row: Serialize {
+    /// Names of the columns, in the order of [`Row::fields`].
+    const COLUMNS: &'static [&'static str];
+
+    /// The values of the row as written in CSV.
+    fn fields(&self) -> Vec<String>;
+}
+
+/// How a table is written.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum Format {
+    /// A header line, then one line of comma-separated values per row.
+    #[default]
+    Csv,
+    /// One JSON object per row.
+    JsonLines,
+}
+
+impl Format {
+    /// The file extension of the format, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::JsonLines => "jsonl",
+        }
+    }
+}
+
+/// Writes `rows` as a table in `format`.
+pub fn write_rows<R: Row, W: Write>(rows: &[R], format: Format, mut writer: W) -> io::Result<()> {
+    match format {
+        Format::Csv => {
+            writeln!(writer, "{}", R::COLUMNS.join(","))?;
+            for row in rows {
+                let fields: Vec<_> = row.fields().iter().map(|field| csv_field(field)).collect();
+                writeln!(writer, "{}", fields.join(","))?;
+            }
+        }
+        Format::JsonLines => {
+            for row in rows {
+                serde_json::to_writer(&mut writer, row)?;
+                writeln!(writer)?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+/// Writes `rows` as a table in `format` to a file, see [`write_rows`].
+pub fn save_rows<R: Row>(rows: &[R], format: Format, path: impl AsRef<Path>) -> io::Result<()> {
+    let file = std::fs::File::create(path)?;
+    write_rows(rows, format, io::BufWriter::new(file))
+}
+
+// quotes a CSV field if it needs it
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or(String::new(), |value| value.to_string())
+}
+
+/// A fill of a bid by a liquidation, see [`fill_history`].
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct FillRow {
+    /// The block the fill happened in.
+    pub block: BlockNumber,
+    /// Index of the fill among the events of its block.
+    pub index: BlockIndex,
+    pub liquidation: LiquidationId,
+    /// The block and index the filled bid was placed at.
+    pub bid_block: BlockNumber,
+    pub bid_index: BlockIndex,
+    pub bidder: AccountId,
+    /// Discount the fill was settled at.
+    pub discount: Discount,
+    /// Bid asset paid by the bidder.
+    pub spent: Balance,
+    /// Collateral the bidder received.
+    pub collateral: Balance,
+    /// Amount the bid has left in the book, zero once filled.
+    pub remaining: Balance,
+    /// Dust refunded to the bidder as the bid was consumed.
+    pub dust: Balance,
+}
+
+impl Row for FillRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "block",
+        "index",
+        "liquidation",
+        "bid_block",
+        "bid_index",
+        "bidder",
+        "discount",
+        "spent",
+        "collateral",
+        "remaining",
+        "dust",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.block.to_string(),
+            self.index.to_string(),
+            self.liquidation.to_string(),
+            self.bid_block.to_string(),
+            self.bid_index.to_string(),
+            self.bidder.to_string(),
+            self.discount.to_string(),
+            self.spent.to_string(),
+            self.collateral.to_string(),
+            self.remaining.to_string(),
+            self.dust.to_string(),
+        ]
+    }
+}
+
+/// Every fill recorded in `events`, in the order they happened.
+pub fn fill_history(events: &[LoggedEvent]) -> Vec<FillRow> {
+    events
+        .iter()
+        .filter_map(|logged| {
+            let (id, bidder, liquidation, discount, spent, collateral, remaining, dust) =
+                match logged.event {
+                    MarketEvent::BidPartiallyFilled {
+                        id,
+                        bidder,
+                        liquidation,
+                        discount,
+                        spent,
+                        collateral,
+                        remaining,
+                    } => (
+                        id,
+                        bidder,
+                        liquidation,
+                        discount,
+                        spent,
+                        collateral,
+                        remaining,
+                        Balance::ZERO,
+                    ),
+                    MarketEvent::BidFilled {
+                        id,
+                        bidder,
+                        liquidation,
+                        discount,
+                        spent,
+                        collateral,
+                        dust,
+                    } => (
+                        id,
+                        bidder,
+                        liquidation,
+                        discount,
+                        spent,
+                        collateral,
+                        Balance::ZERO,
+                        dust,
+                    ),
+                    _ => return None,
+                };
+            Some(FillRow {
+                block: logged.block,
+                index: logged.index,
+                liquidation,
+                bid_block: id.block,
+                bid_index: id.index,
+                bidder,
+                discount,
+                spent,
+                collateral,
+                remaining,
+                dust,
+            })
+        })
+        .collect()
+}
+
+/// The open bids at one discount at the end of a block, see [`book_depth`].
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct DepthRow {
+    pub block: BlockNumber,
+    pub discount: Discount,
+    /// Number of open bids at the discount.
+    pub bids: usize,
+    /// Total amount they offer.
+    pub amount: Balance,
+}
+
+impl Row for DepthRow {
+    const COLUMNS: &'static [&'static str] = &["block", "discount", "bids", "amount"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.block.to_string(),
+            self.discount.to_string(),
+            self.bids.to_string(),
+            self.amount.to_string(),
+        ]
+    }
+}
+
+/// The depth of the book by discount at the end of every block with events,
+/// lowest discount first within a block.
+///
+/// The book is rebuilt from the bids placed, amended, filled, cancelled and
+/// expired in `events`, so it only holds the bids that went through the
+/// market. Blocks without events leave the book as it was. A discount that
+/// had bids at the end of the previous block with events and has none left
+/// gets a row with no bids, so a block emptying the book still has rows.
+///
+/// Fails if the amount of a discount overflows.
+pub fn book_depth(events: &[LoggedEvent]) -> Result<Vec<DepthRow>, BalanceOverflow> {
+    let mut open: BTreeMap<BidId, (Discount, Balance)> = BTreeMap::new();
+    let mut tiers: Vec<Discount> = Vec::new();
+    let mut rows = Vec::new();
+    for (position, logged) in events.iter().enumerate() {
+        match logged.event {
+            MarketEvent::BidPlaced {
+                id,
+                amount,
+                discount,
+                ..
+            } => {
+                open.insert(id, (discount, amount));
+            }
+            MarketEvent::BidAmended {
+                id,
+                new_id,
+                amount,
+                discount,
+                ..
+            } => {
+                open.remove(&id);
+                open.insert(new_id, (discount, amount));
+            }
+            MarketEvent::BidPartiallyFilled { id, remaining, .. } => {
+                if let Some((_, amount)) = open.get_mut(&id) {
+                    *amount = remaining;
+                }
+            }
+            MarketEvent::BidFilled { id, .. }
+            | MarketEvent::BidCancelled { id, .. }
+            | MarketEvent::BidExpired { id, .. } => {
+                open.remove(&id);
+            }
+            _ => {}
+        }
+
+        let block_ends = events
+            .get(position + 1)
+            .is_none_or(|next| next.block != logged.block);
+        if block_ends {
+            let mut depth: BTreeMap<Discount, (usize, Balance)> = BTreeMap::new();
+            for (discount, amount) in open.values() {
+                let (bids, total) = depth.entry(*discount).or_default();
+                *bids += 1;
+                *total = total.checked_add(*amount).ok_or(BalanceOverflow)?;
+            }
+            for emptied in tiers {
+                depth.entry(emptied).or_default();
+            }
+            tiers = depth
+                .iter()
+                .filter(|(_, (bids, _))| *bids > 0)
+                .map(|(discount, _)| *discount)
+                .collect();
+            rows.extend(
+                depth
+                    .into_iter()
+                    .map(|(discount, (bids, amount))| DepthRow {
+                        block: logged.block,
+                        discount,
+                        bids,
+                        amount,
+                    }),
+            );
+        }
+    }
+    Ok(rows)
+}
+
+/// How a liquidation went, see [`liquidation_summaries`].
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct LiquidationSummary {
+    pub id: LiquidationId,
+    pub account: AccountId,
+    /// The block the liquidation was queued in.
+    pub queued_at: BlockNumber,
+    /// The amount it was queued with.
+    pub amount: Balance,
+    pub filled: Balance,
+    pub remaining: Balance,
+    pub collateral_sold: Balance,
+    /// Number of bids filled.
+    pub fills: u64,
+    /// Discount of the fills in percent, weighted by the bid asset they
+    /// paid; zero without fills.
+    pub average_discount: f64,
+    /// The block of the last fill, if any.
+    pub last_fill_at: Option<BlockNumber>,
+    pub status: LiquidationStatus,
+}
+
+impl Row for LiquidationSummary {
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "account",
+        "queued_at",
+        "amount",
+        "filled",
+        "remaining",
+        "collateral_sold",
+        "fills",
+        "average_discount",
+        "last_fill_at",
+        "status",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.account.to_string(),
+            self.queued_at.to_string(),
+            self.amount.to_string(),
+            self.filled.to_string(),
+            self.remaining.to_string(),
+            self.collateral_sold.to_string(),
+            self.fills.to_string(),
+            self.average_discount.to_string(),
+            optional(self.last_fill_at),
+            format!("{:?}", self.status),
+        ]
+    }
+}
+
+/// A summary of every liquidation, with its fills taken from `events`.
+///
+/// Fails if the bid asset paid by the fills of a liquidation, weighted by
+/// their discounts, overflows.
+pub fn liquidation_summaries<'a>(
+    liquidations: impl IntoIterator<Item = &'a QueuedLiquidation>,
+    events: &[LoggedEvent],
+) -> Result<Vec<LiquidationSummary>, BalanceOverflow> {
+    let fills = fill_history(events);
+    liquidations
+        .into_iter()
+        .map(|queued| {
+            let id = queued.liquidation.id;
+            let mut summary = LiquidationSummary {
+                id,
+                account: queued.liquidation.account_liquidated,
+                queued_at: queued.queued_at,
+                amount: queued.original_amount,
+                filled: queued.filled(),
+                remaining: queued.liquidation.amount,
+                collateral_sold: queued.collateral_sold,
+                fills: 0,
+                average_discount: 0.0,
+                last_fill_at: None,
+                status: queued.liquidation.status.clone(),
+            };
+            let (mut volume, mut spent) = (0u128, Balance::ZERO);
+            for fill in fills.iter().filter(|fill| fill.liquidation == id) {
+                summary.fills += 1;
+                summary.last_fill_at = Some(fill.block);
+                volume = (fill.discount.bps() as u128)
+                    .checked_mul(fill.spent.units())
+                    .and_then(|weighted| volume.checked_add(weighted))
+                    .ok_or(BalanceOverflow)?;
+                spent = spent.checked_add(fill.spent).ok_or(BalanceOverflow)?;
+            }
+            summary.average_discount = average_discount(volume, spent);
+            Ok(summary)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Market, Price};
+
+    fn market() -> Market {
+        let mut market = Market::new();
+        market.mint(1, Balance::new(10_000), Balance::ZERO).unwrap();
+        market.mint(2, Balance::new(10_000), Balance::ZERO).unwrap();
+        market
+            .mint(100, Balance::ZERO, Balance::new(100_000))
+            .unwrap();
+        let first = market
+            .place_bid(1, Balance::new(1_000), Discount::percent(5))
+            .unwrap();
+        market
+            .place_bid(2, Balance::new(2_000), Discount::percent(10))
+            .unwrap();
+        market
+            .place_bid(2, Balance::new(500), Discount::percent(10))
+            .unwrap();
+        market.clock.advance(1);
+        market
+            .amend_bid(1, first, Balance::new(1_500), Discount::percent(5))
+            .unwrap();
+        market.queue_liquidation(100, Balance::new(2_000));
+        market.process_liquidations(Price::ONE);
+        market.clock.advance(1);
+        market.queue_liquidation(100, Balance::new(50_000));
+        market
+    }
+
+    #[test]
+    fn test_fill_history() {
+        let market = market();
+        let fills = fill_history(market.events.events());
+
+        let rows: Vec<_> = fills
+            .iter()
+            .map(|fill| {
+                (
+                    fill.block,
+                    fill.bid_block,
+                    fill.bid_index,
+                    fill.spent.units(),
+                )
+            })
+            .collect();
+        assert_eq!(rows, vec![(1, 1, 0, 1_500), (1, 0, 1, 500)]);
+        assert_eq!(fills[1].remaining, Balance::new(1_500));
+        assert_eq!(fills[1].discount, Discount::percent(10));
+    }
+
+    #[test]
+    fn test_book_depth() {
+        let market = market();
+        let depth: Vec<_> = book_depth(market.events.events())
+            .unwrap()
+            .iter()
+            .map(|row| (row.block, row.discount.bps(), row.bids, row.amount.units()))
+            .collect();
+        assert_eq!(
+            depth,
+            vec![
+                (0, 500, 1, 1_000),
+                (0, 1_000, 2, 2_500),
+                (1, 500, 0, 0),
+                (1, 1_000, 2, 2_000),
+                (2, 1_000, 2, 2_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_emptied_tiers_have_rows() {
+        let mut market = market();
+        market.clock.advance(1);
+        market.process_liquidations(Price::ONE);
+        market.clock.advance(1);
+        market
+            .place_bid(1, Balance::new(300), Discount::percent(5))
+            .unwrap();
+
+        let depth: Vec<_> = book_depth(market.events.events())
+            .unwrap()
+            .iter()
+            .filter(|row| row.block >= 3)
+            .map(|row| (row.block, row.discount.bps(), row.bids, row.amount.units()))
+            .collect();
+        // the queued liquidation empties the book in block 3
+        assert_eq!(depth, vec![(3, 1_000, 0, 0), (4, 500, 1, 300)]);
+    }
+
+    #[test]
+    fn test_overflowing_depth_fails() {
+        let placed = |index| LoggedEvent {
+            block: 0,
+            index,
+            event: MarketEvent::BidPlaced {
+                id: BidId::new(0, index),
+                bidder: 1,
+                amount: Balance::MAX,
+                discount: Discount::percent(5),
+                expires_at: None,
+            },
+        };
+        assert_eq!(book_depth(&[placed(0)]).unwrap()[0].amount, Balance::MAX);
+        assert_eq!(book_depth(&[placed(0), placed(1)]), Err(BalanceOverflow));
+    }
+
+    #[test]
+    fn test_liquidation_summaries() {
+        let market = market();
+        let summaries =
+            liquidation_summaries(market.liquidations.iter(), market.events.events()).unwrap();
+
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].fills, 2);
+        assert_eq!(summaries[0].filled, Balance::new(2_000));
+        assert_eq!(summaries[0].last_fill_at, Some(1));
+        assert_eq!(summaries[0].status, LiquidationStatus::Fulfilled);
+        // (1500 * 5% + 500 * 10%) / 2000
+        assert_eq!(summaries[0].average_discount, 6.25);
+        assert_eq!(summaries[1].fills, 0);
+        assert_eq!(summaries[1].queued_at, 2);
+        assert_eq!(summaries[1].remaining, Balance::new(50_000));
+    }
+
+    #[test]
+    fn test_write_rows() {
+        let rows = vec![DepthRow {
+            block: 3,
+            discount: Discount::from_bps(250),
+            bids: 2,
+            amount: Balance::new(700),
+        }];
+
+        let mut csv = Vec::new();
+        write_rows(&rows, Format::Csv, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "block,discount,bids,amount\n3,2.5,2,700\n"
+        );
+
+        let mut jsonl = Vec::new();
+        write_rows(&rows, Format::JsonLines, &mut jsonl).unwrap();
+        assert_eq!(
+            String::from_utf8(jsonl).unwrap(),
+            "{\"block\":3,\"discount\":2.5,\"bids\":2,\"amount\":700}\n"
+        );
+
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+}
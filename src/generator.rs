@@ -0,0 +1,431 @@
+use std::fmt;
+
+use rand::distr::weighted::WeightedIndex;
+use rand::Rng;
+use rand_distr::{Distribution, LogNormal, Normal, Pareto, Poisson};
+use serde::{Deserialize, Serialize};
+
+use crate::{AccountId, Balance, Discount, SimRng};
+
+/// How the amount of a generated bid is drawn.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AmountDistribution {
+    /// Every amount between `min` and `max` is equally likely.
+    Uniform { min: Balance, max: Balance },
+    /// Log-normal around `median`; `sigma` is the standard deviation of the
+    /// amount's logarithm. Most bids are small, a few are much larger.
+    LogNormal { median: f64, sigma: f64 },
+    /// Pareto with minimum `scale`; a lower `shape` gives a heavier tail of
+    /// whale bids.
+    Pareto { scale: f64, shape: f64 },
+}
+
+/// How the discount of a generated bid is drawn. Uniform and normal draws are
+/// rounded to the generator's `discount_step`, below 100%.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum DiscountDistribution {
+    /// Every discount between `min` and `max` is equally likely.
+    Uniform { min: Discount, max: Discount },
+    /// Normal around `mean` percent.
+    Normal { mean: f64, std_dev: f64 },
+    /// Each `(discount, weight)` pair is drawn in proportion to its weight,
+    /// e.g. taken from an observed book.
+    Histogram { weights: Vec<(Discount, f64)> },
+}
+
+/// Configuration of the random bids arriving at the market.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BidGenerator {
+    /// Distribution of bid amounts.
+    pub amount: AmountDistribution,
+    /// Amounts are rounded down to a multiple of `lot`, and are at least one
+    /// lot.
+    pub lot: Balance,
+    /// Distribution of bid discounts.
+    pub discount: DiscountDistribution,
+    /// Drawn discounts are a multiple of `discount_step`, e.g. the step of
+    /// the market's tiers.
+    pub discount_step: Discount,
+    /// Bids are placed by accounts `1..=bidders`, each equally likely.
+    pub bidders: AccountId,
+    /// Average number of bids arriving per block; arrivals follow a Poisson
+    /// distribution.
+    pub arrival_rate: f64,
+}
+
+impl Default for BidGenerator {
+    fn default() -> Self {
+        Self {
+            amount: AmountDistribution::Uniform {
+                min: Balance::new(100),
+                max: Balance::new(9_900),
+            },
+            lot: Balance::new(100),
+            discount: DiscountDistribution::Uniform {
+                min: Discount::percent(1),
+                max: Discount::percent(20),
+            },
+            discount_step: Discount::percent(1),
+            bidders: 10,
+            arrival_rate: 3.0,
+        }
+    }
+}
+
+/// Reasons a [`BidGenerator`] cannot draw bids.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum GeneratorError {
+    /// The amount distribution parameters are out of range.
+    Amount(String),
+    /// The discount distribution parameters are out of range.
+    Discount(String),
+    /// Amounts must be rounded to a lot greater than zero.
+    ZeroLot,
+    /// Discounts must be rounded to a step greater than zero.
+    ZeroDiscountStep,
+    /// There must be at least one bidder.
+    NoBidders,
+    /// The arrival rate must be a finite number, zero or more.
+    ArrivalRate,
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeneratorError::Amount(err) => write!(f, "invalid amount distribution: {}", err),
+            GeneratorError::Discount(err) => write!(f, "invalid discount distribution: {}", err),
+            GeneratorError::ZeroLot => write!(f, "lot must be greater than zero"),
+            GeneratorError::ZeroDiscountStep => {
+                write!(f, "discount step must be greater than zero")
+            }
+            GeneratorError::NoBidders => write!(f, "there must be at least one bidder"),
+            GeneratorError::ArrivalRate => write!(f, "arrival rate must be zero or more"),
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+impl BidGenerator {
+    /// Checks every parameter, so drawing bids cannot fail afterwards.
+    pub fn validate(&self) -> Result<(), GeneratorError> {
+        let mut rng = SimRng::default();
+        self.amount(&mut rng)?;
+        self.discount(&mut rng)?;
+        self.bidder(&mut rng)?;
+        self.arrivals(&mut rng)?;
+        Ok(())
+    }
+
+    /// Draws the amount of a bid.
+    pub fn amount<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<Balance, GeneratorError> {
+        if self.lot.is_zero() {
+            return Err(GeneratorError::ZeroLot);
+        }
+        let units = match &self.amount {
+            AmountDistribution::Uniform { min, max } => {
+                if min > max {
+                    return Err(GeneratorError::Amount(format!(
+                        "min {} is above max {}",
+                        min, max
+                    )));
+                }
+                rng.random_range(min.units()..=max.units())
+            }
+            AmountDistribution::LogNormal { median, sigma } => {
+                if median.is_nan() || *median <= 0.0 {
+                    return Err(GeneratorError::Amount(
+                        "median must be greater than zero".to_string(),
+                    ));
+                }
+                // saturating conversion
+                LogNormal::new(median.ln(), *sigma)
+                    .map_err(|err| GeneratorError::Amount(err.to_string()))?
+                    .sample(rng) as u128
+            }
+            AmountDistribution::Pareto { scale, shape } => Pareto::new(*scale, *shape)
+                .map_err(|err| GeneratorError::Amount(err.to_string()))?
+                .sample(rng) as u128,
+        };
+        // round down to whole lots
+        let lot = self.lot.units();
+        Ok(Balance::new((units / lot * lot).max(lot)))
+    }
+
+    /// Draws the discount of a bid.
+    pub fn discount<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<Discount, GeneratorError> {
+        let step = self.discount_step.bps();
+        if step == 0 {
+            return Err(GeneratorError::ZeroDiscountStep);
+        }
+        // the most steps below 100%, which no bid can be settled at
+        let max_steps = (Discount::FULL.bps() - 1) / step;
+        match &self.discount {
+            DiscountDistribution::Uniform { min, max } => {
+                let first = min.bps().div_ceil(step);
+                let last = max.bps() / step;
+                if first > last || last > max_steps {
+                    return Err(GeneratorError::Discount(format!(
+                        "range {}%..={}% has no step of {}% below 100%",
+                        min, max, self.discount_step
+                    )));
+                }
+                Ok(Discount::from_bps(rng.random_range(first..=last) * step))
+            }
+            DiscountDistribution::Normal { mean, std_dev } => {
+                let percent = Normal::new(*mean, *std_dev)
+                    .map_err(|err| GeneratorError::Discount(err.to_string()))?
+                    .sample(rng);
+                let steps = (percent * Discount::BPS_PER_PERCENT as f64 / step as f64).round();
+                Ok(Discount::from_bps(
+                    steps.clamp(0.0, max_steps as f64) as u16 * step,
+                ))
+            }
+            DiscountDistribution::Histogram { weights } => {
+                if let Some((discount, _)) = weights
+                    .iter()
+                    .find(|(discount, _)| *discount >= Discount::FULL)
+                {
+                    return Err(GeneratorError::Discount(format!(
+                        "discount {}% is not below 100%",
+                        discount
+                    )));
+                }
+                let index = WeightedIndex::new(weights.iter().map(|(_, weight)| *weight))
+                    .map_err(|err| GeneratorError::Discount(err.to_string()))?;
+                Ok(weights[index.sample(rng)].0)
+            }
+        }
+    }
+
+    /// Draws the account placing a bid.
+    pub fn bidder<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<AccountId, GeneratorError> {
+        if self.bidders == 0 {
+            return Err(GeneratorError::NoBidders);
+        }
+        Ok(rng.random_range(1..=self.bidders))
+    }
+
+    /// Draws how many bids arrive during one block.
+    pub fn arrivals<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<u32, GeneratorError> {
+        if !self.arrival_rate.is_finite() || self.arrival_rate < 0.0 {
+            return Err(GeneratorError::ArrivalRate);
+        }
+        if self.arrival_rate == 0.0 {
+            return Ok(0);
+        }
+        let arrivals = Poisson::new(self.arrival_rate)
+            .map_err(|_| GeneratorError::ArrivalRate)?
+            .sample(rng);
+        Ok(arrivals as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draws<T>(mut draw: impl FnMut(&mut SimRng) -> T) -> Vec<T> {
+        let mut rng = SimRng::new(11);
+        (0..2_000).map(|_| draw(&mut rng)).collect()
+    }
+
+    #[test]
+    fn test_default_generator() {
+        let generator = BidGenerator::default();
+        assert_eq!(generator.validate(), Ok(()));
+
+        let amounts = draws(|rng| generator.amount(rng).unwrap().units());
+        assert!(amounts
+            .iter()
+            .all(|amount| (100..=9_900).contains(amount) && amount % 100 == 0));
+        let discounts = draws(|rng| generator.discount(rng).unwrap());
+        assert!(discounts
+            .iter()
+            .all(|discount| (Discount::percent(1)..=Discount::percent(20)).contains(discount)));
+        let bidders = draws(|rng| generator.bidder(rng).unwrap());
+        assert!(bidders.iter().all(|bidder| (1..=10).contains(bidder)));
+    }
+
+    #[test]
+    fn test_heavy_tailed_amounts() {
+        let lognormal = BidGenerator {
+            amount: AmountDistribution::LogNormal {
+                median: 1_000.0,
+                sigma: 1.0,
+            },
+            lot: Balance::new(1),
+            ..Default::default()
+        };
+        let mut amounts = draws(|rng| lognormal.amount(rng).unwrap().units());
+        amounts.sort();
+        let median = amounts[amounts.len() / 2];
+        assert!((800..1_250).contains(&median), "median {}", median);
+        assert!(*amounts.last().unwrap() > 10_000);
+
+        let pareto = BidGenerator {
+            amount: AmountDistribution::Pareto {
+                scale: 500.0,
+                shape: 1.5,
+            },
+            lot: Balance::new(1),
+            ..Default::default()
+        };
+        let amounts = draws(|rng| pareto.amount(rng).unwrap().units());
+        assert!(amounts.iter().all(|amount| *amount >= 500));
+    }
+
+    #[test]
+    fn test_discount_distributions() {
+        let normal = BidGenerator {
+            discount: DiscountDistribution::Normal {
+                mean: 8.0,
+                std_dev: 2.0,
+            },
+            ..Default::default()
+        };
+        let discounts = draws(|rng| normal.discount(rng).unwrap());
+        let mean = discounts.iter().map(|d| d.to_f64()).sum::<f64>() / discounts.len() as f64;
+        assert!((7.5..8.5).contains(&mean), "mean {}", mean);
+
+        let histogram = BidGenerator {
+            discount: DiscountDistribution::Histogram {
+                weights: vec![
+                    (Discount::percent(5), 3.0),
+                    (Discount::percent(10), 1.0),
+                    (Discount::percent(15), 0.0),
+                ],
+            },
+            ..Default::default()
+        };
+        let discounts = draws(|rng| histogram.discount(rng).unwrap());
+        let fives = discounts
+            .iter()
+            .filter(|d| **d == Discount::percent(5))
+            .count();
+        assert!(discounts
+            .iter()
+            .all(|d| [Discount::percent(5), Discount::percent(10)].contains(d)));
+        assert!((1_350..1_650).contains(&fives), "fives {}", fives);
+    }
+
+    #[test]
+    fn test_discounts_follow_the_step() {
+        let halves = BidGenerator {
+            discount: DiscountDistribution::Uniform {
+                min: Discount::from_bps(50),
+                max: Discount::percent(3),
+            },
+            discount_step: Discount::from_bps(50),
+            ..Default::default()
+        };
+        let discounts = draws(|rng| halves.discount(rng).unwrap());
+        assert!(discounts
+            .iter()
+            .all(|d| d.bps() % 50 == 0
+                && (Discount::from_bps(50)..=Discount::percent(3)).contains(d)));
+        assert!(discounts.contains(&Discount::from_bps(250)));
+
+        let normal = BidGenerator {
+            discount: DiscountDistribution::Normal {
+                mean: 50.0,
+                std_dev: 80.0,
+            },
+            discount_step: Discount::percent(2),
+            ..Default::default()
+        };
+        let discounts = draws(|rng| normal.discount(rng).unwrap());
+        assert!(discounts
+            .iter()
+            .all(|d| d.bps() % 200 == 0 && *d < Discount::FULL));
+
+        let zero_step = BidGenerator {
+            discount_step: Discount::ZERO,
+            ..Default::default()
+        };
+        assert_eq!(zero_step.validate(), Err(GeneratorError::ZeroDiscountStep));
+    }
+
+    #[test]
+    fn test_arrival_rate() {
+        let generator = BidGenerator {
+            arrival_rate: 4.0,
+            ..Default::default()
+        };
+        let arrivals = draws(|rng| generator.arrivals(rng).unwrap());
+        let mean = arrivals.iter().sum::<u32>() as f64 / arrivals.len() as f64;
+        assert!((3.8..4.2).contains(&mean), "mean {}", mean);
+
+        let idle = BidGenerator {
+            arrival_rate: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(idle.arrivals(&mut SimRng::new(1)), Ok(0));
+    }
+
+    #[test]
+    fn test_invalid_generators() {
+        let invalid = [
+            BidGenerator {
+                amount: AmountDistribution::Uniform {
+                    min: Balance::new(10),
+                    max: Balance::new(1),
+                },
+                ..Default::default()
+            },
+            BidGenerator {
+                amount: AmountDistribution::Pareto {
+                    scale: 0.0,
+                    shape: 1.0,
+                },
+                ..Default::default()
+            },
+            BidGenerator {
+                discount: DiscountDistribution::Uniform {
+                    min: Discount::ZERO,
+                    max: Discount::FULL,
+                },
+                ..Default::default()
+            },
+            BidGenerator {
+                discount: DiscountDistribution::Histogram {
+                    weights: Vec::new(),
+                },
+                ..Default::default()
+            },
+            BidGenerator {
+                lot: Balance::ZERO,
+                ..Default::default()
+            },
+            BidGenerator {
+                bidders: 0,
+                ..Default::default()
+            },
+            BidGenerator {
+                arrival_rate: -1.0,
+                ..Default::default()
+            },
+        ];
+        for generator in invalid {
+            assert!(generator.validate().is_err(), "{:?}", generator);
+        }
+    }
+
+    #[test]
+    fn test_generator_from_json() {
+        let generator: BidGenerator = serde_json::from_str(
+            r#"{
+                "amount": { "kind": "log_normal", "median": 2000, "sigma": 0.8 },
+                "discount": { "kind": "histogram", "weights": [[2, 1], [4, 2.5]] },
+                "bidders": 25
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(generator.bidders, 25);
+        assert_eq!(generator.lot, Balance::new(100));
+        assert_eq!(generator.validate(), Ok(()));
+    }
+}
@@ -0,0 +1,1298 @@
+use egui_plot::{Bar, BarChart, Plot};
+
+use crate::{
+    book_depth, fill_history, group_bids_by_discount, liquidation_summaries, load_bids, save_rows,
+    AccountId, AllocationPolicy, AmountDistribution, Balance, BidGenerator, BidId, BidRules,
+    BlockNumber, BlockOutcome, Columns, Discount, DiscountDistribution, DutchAuction, Event,
+    Format, LiquidationMechanism, Market, MarketEvent, Mechanism, Price, RowError, Scenario,
+    ScenarioOutcome, Simulation, Snapshot,
+};
+
+/// The egui application driving the liquidation simulation.
+#[derive(Default)]
+pub struct LiquidationApp {
+    // Bids, balances, random numbers and scheduled events of our liquidation
+    // system, run block by block
+    sim: Simulation,
+
+    // Seed to restart the simulation from, as typed in the UI
+    pub seed: u64,
+
+    // File the simulation is saved to and opened from, and how the last save
+    // or open went
+    state_path: String,
+    state_status: Option<String>,
+
+    // Scenario file opened, the scenario being stepped through, the index of
+    // its next step and what its steps produced so far
+    scenario_path: String,
+    scenario: Option<Scenario>,
+    scenario_step: usize,
+    scenario_outcome: ScenarioOutcome,
+
+    // Whether blocks run on their own, and how many per frame
+    playing: bool,
+    pub blocks_per_frame: u32,
+
+    // Block the next scheduled event happens at, and the price it moves to
+    pub schedule_block: BlockNumber,
+    pub scheduled_price: f64,
+
+    // Account and amount of the next liquidation to queue
+    pub new_liquidation_account: AccountId,
+    pub new_liquidation_amount: Balance,
+
+    // Parameters that control how many new random bids to insert
+    num_new_bids: u64,
+
+    // The last action of the UI that failed, if any; what succeeded shows up
+    // in the market's events
+    last_error: Option<String>,
+
+    // Kind of events and text the event viewer is filtered by, and the file
+    // it exports to
+    event_kind: Option<&'static str>,
+    event_search: String,
+    event_export_path: String,
+    export_status: Option<String>,
+
+    // Directory and format the fill, book depth and liquidation tables are
+    // exported to
+    tables_dir: String,
+    tables_format: Format,
+
+    pub new_bid_amount: Balance,
+    pub new_bid_discount: Discount,
+    // Whether the new bid expires, and how many blocks from now
+    new_bid_expires: bool,
+    pub new_bid_lifetime: BlockNumber,
+
+    // File bids are imported from, whether bidders are minted what they lack,
+    // and how the last import went
+    import_path: String,
+    import_mint: bool,
+    import_status: Option<String>,
+    import_errors: Vec<RowError>,
+
+    // The discount histogram of the bid generator, as typed in the UI
+    histogram_text: String,
+
+    // Oracle price of the collateral in bid asset, as typed in the UI
+    pub oracle_price: f64,
+
+    // Bid picked in the bid list, and the account acting on it
+    selected_bid: Option<BidId>,
+    pub acting_account: AccountId,
+    pub amend_amount: Balance,
+    pub amend_discount: Discount,
+
+    // Which view the central panel shows
+    tab: Tab,
+
+    // Only show the history of this bidder, if set
+    history_bidder: Option<AccountId>,
+
+    // Account and amounts for the "Mint" control of the accounts tab
+    mint_account: AccountId,
+    mint_bid_asset: Balance,
+    mint_collateral: Balance,
+}
+
+// Account whose collateral the default liquidation sells
+const LIQUIDATED_ACCOUNT: AccountId = 100;
+
+/// The views of the central panel.
+#[derive(Default, PartialEq)]
+enum Tab {
+    #[default]
+    ActiveBids,
+    History,
+    Accounts,
+    Events,
+}
+
+impl LiquidationApp {
+    /// Creates the application with a fresh random seed.
+    pub fn new() -> Self {
+        Self::with_seed(rand::random())
+    }
+
+    /// Creates the application with its initial liquidation and controls,
+    /// drawing every random number from `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        let mut market = Market::new();
+        // Fund the bidders and the account being liquidated
+        let num_bidders = 10;
+        for bidder in 1..=num_bidders {
+            market
+                .mint(bidder, Balance::new(1_000_000), Balance::ZERO)
+                .expect("fresh account");
+        }
+        market
+            .mint(LIQUIDATED_ACCOUNT, Balance::ZERO, Balance::new(1_000_000))
+            .expect("fresh account");
+        // Start with some default liquidation
+        market.queue_liquidation(LIQUIDATED_ACCOUNT, Balance::new(5000));
+
+        let mut sim = Simulation::new(market, seed);
+        sim.generator = BidGenerator {
+            bidders: num_bidders,
+            ..Default::default()
+        };
+
+        LiquidationApp {
+            sim,
+            seed,
+            blocks_per_frame: 1,
+            schedule_block: 10,
+            scheduled_price: 1.0,
+            histogram_text: "5:1, 10:1".to_string(),
+            new_liquidation_account: LIQUIDATED_ACCOUNT,
+            new_liquidation_amount: Balance::new(5000),
+            num_new_bids: 3,
+            new_bid_amount: Balance::new(1000),
+            new_bid_discount: BidRules::default().min_discount,
+            new_bid_lifetime: 10,
+            oracle_price: 1.0,
+            acting_account: 1,
+            mint_account: 1,
+            mint_bid_asset: Balance::new(10_000),
+            event_export_path: "events.jsonl".to_string(),
+            tables_dir: ".".to_string(),
+            state_path: "simulation.json".to_string(),
+            scenario_path: "scenario.json".to_string(),
+            import_path: "bids.csv".to_string(),
+            import_mint: true,
+            ..Default::default()
+        }
+    }
+}
+
+impl LiquidationApp {
+    // Replaces the running simulation with one read from disk, pausing it and
+    // showing its seed and price in the controls
+    fn open(&mut self, sim: Simulation) {
+        self.sim = sim;
+        self.seed = self.sim.rng.seed();
+        self.oracle_price = self.sim.price.to_f64();
+        self.playing = false;
+        self.selected_bid = None;
+        self.last_error = None;
+        self.scenario = None;
+    }
+
+    // Starts stepping through the scenario of `scenario_path` from its
+    // initial simulation
+    fn open_scenario(&mut self) -> Result<(), crate::ScenarioError> {
+        let scenario = Scenario::load(&self.scenario_path)?;
+        self.open(scenario.simulation()?);
+        self.scenario_step = 0;
+        self.scenario_outcome = ScenarioOutcome {
+            seed: scenario.seed,
+            ..Default::default()
+        };
+        self.scenario = Some(scenario);
+        Ok(())
+    }
+
+    // Applies the next step of the open scenario
+    fn next_scenario_step(&mut self) {
+        let Some(scenario) = &self.scenario else {
+            return;
+        };
+        let errors = self.scenario_outcome.errors.len();
+        scenario.apply_step(
+            self.scenario_step,
+            &mut self.sim,
+            &mut self.scenario_outcome,
+        );
+        if let Some(error) = self.scenario_outcome.errors[errors..].last() {
+            self.last_error = Some(format!("Step {}: {}", error.step, error.message));
+        }
+        self.scenario_step += 1;
+        self.oracle_price = self.sim.price.to_f64();
+    }
+}
+
+impl LiquidationApp {
+    // Imports the bids of a CSV or JSON Lines file into the book
+    fn show_import(&mut self, ui: &mut egui::Ui) {
+        ui.label("Import Bids:");
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.text_edit_singleline(&mut self.import_path);
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.import_mint, "Mint missing funds");
+            if ui.button("Import bids").clicked() {
+                match load_bids(&self.import_path, &Columns::default()) {
+                    Ok(import) => {
+                        let rows = import.bids.len() + import.errors.len();
+                        let mut errors = import.errors;
+                        errors.extend(self.sim.market.import_bids(&import.bids, self.import_mint));
+                        errors.sort_by_key(|err| err.line);
+                        self.import_status = Some(format!(
+                            "Imported {} of {} bids.",
+                            rows - errors.len(),
+                            rows
+                        ));
+                        self.import_errors = errors;
+                    }
+                    Err(err) => {
+                        self.import_status = Some(format!("Import failed: {}", err));
+                        self.import_errors.clear();
+                    }
+                }
+            }
+        });
+        if let Some(status) = &self.import_status {
+            ui.label(status);
+        }
+        for error in self.import_errors.iter().take(5) {
+            ui.colored_label(egui::Color32::RED, error.to_string());
+        }
+        if self.import_errors.len() > 5 {
+            ui.label(format!("and {} more rows", self.import_errors.len() - 5));
+        }
+    }
+}
+
+impl LiquidationApp {
+    // Lists the fulfilled and cancelled bids, optionally for a single bidder
+    fn show_history(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Closed Bids");
+
+        ui.horizontal(|ui| {
+            let mut filter = self.history_bidder.is_some();
+            ui.checkbox(&mut filter, "Only bidder:");
+            let mut bidder = self.history_bidder.unwrap_or(1);
+            ui.add_enabled(filter, egui::DragValue::new(&mut bidder).speed(1));
+            self.history_bidder = filter.then_some(bidder);
+        });
+
+        ui.separator();
+
+        let closed: Vec<_> = match self.history_bidder {
+            Some(bidder) => self.sim.market.book.history.by_bidder(bidder).collect(),
+            None => self.sim.market.book.history.closed().iter().collect(),
+        };
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("closed_bids").striped(true).show(ui, |ui| {
+                for header in [
+                    "Id",
+                    "Bidder",
+                    "Discount",
+                    "Status",
+                    "Original",
+                    "Filled",
+                    "Liquidations",
+                ] {
+                    ui.strong(header);
+                }
+                ui.end_row();
+
+                for closed in closed.iter().rev() {
+                    ui.label(closed.bid.id().to_string());
+                    ui.label(closed.bid.bidder.to_string());
+                    ui.label(format!("{}%", closed.bid.discount));
+                    ui.label(format!("{:?}", closed.status()));
+                    ui.label(closed.bid.original_amount.to_string());
+                    ui.label(closed.total_filled.to_string());
+                    ui.label(format!("{:?}", closed.liquidations));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
+
+impl LiquidationApp {
+    // Lists the balances of every account and lets us mint new funds
+    fn show_accounts(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Accounts");
+
+        ui.horizontal(|ui| {
+            ui.label("Account:");
+            ui.add(egui::DragValue::new(&mut self.mint_account).speed(1));
+            ui.label("Bid Asset:");
+            ui.add(egui::DragValue::new(&mut self.mint_bid_asset).speed(100));
+            ui.label("Collateral:");
+            ui.add(egui::DragValue::new(&mut self.mint_collateral).speed(100));
+            if ui.button("Mint").clicked() {
+                if let Err(err) = self.sim.market.mint(
+                    self.mint_account,
+                    self.mint_bid_asset,
+                    self.mint_collateral,
+                ) {
+                    self.last_error = Some(format!("Minting failed: {}", err));
+                }
+            }
+        });
+
+        let assets = &self.sim.market.assets;
+        let total = |total: Result<Balance, _>, asset: &crate::Asset| match total {
+            Ok(total) => asset.format(total),
+            Err(_) => "overflows".to_string(),
+        };
+        ui.label(format!(
+            "Total bid asset: {}, total collateral: {}",
+            total(
+                self.sim.market.accounts.total_bid_asset(),
+                &assets.bid_asset
+            ),
+            total(
+                self.sim.market.accounts.total_collateral(),
+                &assets.collateral
+            )
+        ));
+
+        ui.separator();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("accounts").striped(true).show(ui, |ui| {
+                for header in ["Account", "Bid Asset", "Escrowed", "Collateral"] {
+                    ui.strong(header);
+                }
+                ui.end_row();
+
+                let assets = &self.sim.market.assets;
+                for (account, balance) in self.sim.market.accounts.iter() {
+                    ui.label(account.to_string());
+                    ui.label(assets.bid_asset.format(balance.bid_asset));
+                    ui.label(assets.bid_asset.format(balance.escrowed));
+                    ui.label(assets.collateral.format(balance.collateral));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
+
+impl LiquidationApp {
+    // Writes the fill history, the book depth of every block with events and
+    // a summary of every liquidation to the tables directory, creating it if
+    // needed
+    fn export_tables(&self) -> std::io::Result<()> {
+        let market = &self.sim.market;
+        let events = market.events.events();
+        let dir = std::path::Path::new(&self.tables_dir);
+        let file = |name: &str| dir.join(format!("{}.{}", name, self.tables_format.extension()));
+        std::fs::create_dir_all(dir)?;
+        save_rows(&fill_history(events), self.tables_format, file("fills"))?;
+        let depth = book_depth(events).map_err(std::io::Error::other)?;
+        save_rows(&depth, self.tables_format, file("book_depth"))?;
+        let summaries = liquidation_summaries(market.liquidations.iter(), events)
+            .map_err(std::io::Error::other)?;
+        save_rows(&summaries, self.tables_format, file("liquidations"))
+    }
+
+    // Lists the events of the market, newest first, filtered by kind and by
+    // the text they contain, and exports them as JSON lines
+    fn show_events(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Events");
+
+        ui.horizontal(|ui| {
+            ui.label("Kind:");
+            egui::ComboBox::from_id_salt("event_kind")
+                .selected_text(self.event_kind.unwrap_or("all"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.event_kind, None, "all");
+                    for kind in MarketEvent::KINDS {
+                        ui.selectable_value(&mut self.event_kind, Some(kind), kind);
+                    }
+                });
+            ui.label("Search:");
+            ui.text_edit_singleline(&mut self.event_search);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Export to:");
+            ui.text_edit_singleline(&mut self.event_export_path);
+            if ui.button("Export").clicked() {
+                let events = &self.sim.market.events;
+                self.export_status = Some(match events.save(&self.event_export_path) {
+                    Ok(()) => format!(
+                        "Exported {} events to {}",
+                        events.len(),
+                        self.event_export_path
+                    ),
+                    Err(err) => format!("Export failed: {}", err),
+                });
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Tables to:");
+            ui.text_edit_singleline(&mut self.tables_dir);
+            egui::ComboBox::from_id_salt("tables_format")
+                .selected_text(match self.tables_format {
+                    Format::Csv => "CSV",
+                    Format::JsonLines => "JSON Lines",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.tables_format, Format::Csv, "CSV");
+                    ui.selectable_value(&mut self.tables_format, Format::JsonLines, "JSON Lines");
+                });
+            if ui.button("Export tables").clicked() {
+                self.export_status = Some(match self.export_tables() {
+                    Ok(()) => format!(
+                        "Exported fills, book depth and liquidations to {}",
+                        self.tables_dir
+                    ),
+                    Err(err) => format!("Export failed: {}", err),
+                });
+            }
+        });
+        if let Some(status) = &self.export_status {
+            ui.label(status);
+        }
+
+        ui.separator();
+
+        let search = self.event_search.to_lowercase();
+        let events: Vec<_> = self
+            .sim
+            .market
+            .events
+            .iter()
+            .rev()
+            .filter(|logged| {
+                self.event_kind
+                    .is_none_or(|kind| logged.event.kind() == kind)
+            })
+            .filter(|logged| {
+                search.is_empty() || logged.event.to_string().to_lowercase().contains(&search)
+            })
+            .collect();
+        ui.label(format!(
+            "{} of {} events",
+            events.len(),
+            self.sim.market.events.len()
+        ));
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Body);
+        egui::ScrollArea::vertical().show_rows(ui, row_height, events.len(), |ui, rows| {
+            for logged in &events[rows] {
+                ui.horizontal(|ui| {
+                    ui.monospace(format!("{:>6}:{:<4}", logged.block, logged.index));
+                    ui.label(logged.event.to_string());
+                });
+            }
+        });
+    }
+}
+
+impl LiquidationApp {
+    // Picks how the queue is matched against the book, and its parameters
+    fn show_mechanism(&mut self, ui: &mut egui::Ui) {
+        let mechanism = &mut self.sim.mechanism;
+        ui.horizontal(|ui| {
+            ui.label("Mechanism:");
+            egui::ComboBox::from_id_salt("mechanism")
+                .selected_text(mechanism.name())
+                .show_ui(ui, |ui| {
+                    if ui.selectable_label(false, "discount queue").clicked() {
+                        *mechanism = Mechanism::default();
+                    }
+                    if ui.selectable_label(false, "dutch auction").clicked() {
+                        *mechanism = Mechanism::DutchAuction(DutchAuction::default());
+                    }
+                });
+        });
+        if let Mechanism::DutchAuction(auction) = mechanism {
+            ui.horizontal(|ui| {
+                ui.label("Start %:");
+                ui.add(percent_value(&mut auction.start_discount));
+                ui.label("Max %:");
+                ui.add(percent_value(&mut auction.max_discount));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Step %:");
+                ui.add(percent_value(&mut auction.step));
+                ui.label("Every blocks:");
+                ui.add(egui::DragValue::new(&mut auction.step_blocks).range(1..=1000));
+            });
+        }
+
+        let allocation = match mechanism {
+            Mechanism::DiscountQueue(queue) => &mut queue.allocation,
+            Mechanism::DutchAuction(auction) => &mut auction.allocation,
+        };
+        ui.horizontal(|ui| {
+            ui.label("Within a discount:");
+            egui::ComboBox::from_id_salt("allocation")
+                .selected_text(match allocation {
+                    AllocationPolicy::Fifo => "FIFO",
+                    AllocationPolicy::ProRata => "Pro-rata",
+                    AllocationPolicy::ProRataMinFill { .. } => "Pro-rata, min fill",
+                })
+                .show_ui(ui, |ui| {
+                    if ui.selectable_label(false, "FIFO").clicked() {
+                        *allocation = AllocationPolicy::Fifo;
+                    }
+                    if ui.selectable_label(false, "Pro-rata").clicked() {
+                        *allocation = AllocationPolicy::ProRata;
+                    }
+                    if ui.selectable_label(false, "Pro-rata, min fill").clicked() {
+                        *allocation = AllocationPolicy::ProRataMinFill {
+                            min_fill: Balance::new(100),
+                        };
+                    }
+                });
+            if let AllocationPolicy::ProRataMinFill { min_fill } = allocation {
+                ui.add(egui::DragValue::new(min_fill).speed(10));
+            }
+        });
+    }
+
+    // Edits the distributions random bids are drawn from
+    fn show_generator(&mut self, ui: &mut egui::Ui) {
+        let generator = &mut self.sim.generator;
+        ui.add(egui::Slider::new(&mut generator.bidders, 1..=50).text("bidders"));
+        ui.add(egui::Slider::new(&mut generator.arrival_rate, 0.0..=50.0).text("bids per block"));
+
+        ui.horizontal(|ui| {
+            ui.label("Amount:");
+            egui::ComboBox::from_id_salt("amount_distribution")
+                .selected_text(match generator.amount {
+                    AmountDistribution::Uniform { .. } => "Uniform",
+                    AmountDistribution::LogNormal { .. } => "Log-normal",
+                    AmountDistribution::Pareto { .. } => "Pareto",
+                })
+                .show_ui(ui, |ui| {
+                    let amount = &mut generator.amount;
+                    if ui.selectable_label(false, "Uniform").clicked() {
+                        *amount = AmountDistribution::Uniform {
+                            min: Balance::new(100),
+                            max: Balance::new(9_900),
+                        };
+                    }
+                    if ui.selectable_label(false, "Log-normal").clicked() {
+                        *amount = AmountDistribution::LogNormal {
+                            median: 1_000.0,
+                            sigma: 1.0,
+                        };
+                    }
+                    if ui.selectable_label(false, "Pareto").clicked() {
+                        *amount = AmountDistribution::Pareto {
+                            scale: 500.0,
+                            shape: 1.5,
+                        };
+                    }
+                });
+            ui.label("Lot");
+            ui.add(egui::DragValue::new(&mut generator.lot).speed(10));
+        });
+        ui.horizontal(|ui| match &mut generator.amount {
+            AmountDistribution::Uniform { min, max } => {
+                ui.label("Min");
+                ui.add(egui::DragValue::new(min).speed(10));
+                ui.label("Max");
+                ui.add(egui::DragValue::new(max).speed(10));
+            }
+            AmountDistribution::LogNormal { median, sigma } => {
+                ui.label("Median");
+                ui.add(egui::DragValue::new(median).speed(10));
+                ui.label("Sigma");
+                ui.add(egui::DragValue::new(sigma).speed(0.01));
+            }
+            AmountDistribution::Pareto { scale, shape } => {
+                ui.label("Scale");
+                ui.add(egui::DragValue::new(scale).speed(10));
+                ui.label("Shape");
+                ui.add(egui::DragValue::new(shape).speed(0.01));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Discount:");
+            egui::ComboBox::from_id_salt("discount_distribution")
+                .selected_text(match generator.discount {
+                    DiscountDistribution::Uniform { .. } => "Uniform",
+                    DiscountDistribution::Normal { .. } => "Normal",
+                    DiscountDistribution::Histogram { .. } => "Histogram",
+                })
+                .show_ui(ui, |ui| {
+                    let discount = &mut generator.discount;
+                    if ui.selectable_label(false, "Uniform").clicked() {
+                        *discount = DiscountDistribution::Uniform {
+                            min: Discount::percent(1),
+                            max: Discount::percent(20),
+                        };
+                    }
+                    if ui.selectable_label(false, "Normal").clicked() {
+                        *discount = DiscountDistribution::Normal {
+                            mean: 10.0,
+                            std_dev: 3.0,
+                        };
+                    }
+                    if ui.selectable_label(false, "Histogram").clicked() {
+                        *discount = DiscountDistribution::Histogram {
+                            weights: parse_histogram(&self.histogram_text).unwrap_or_default(),
+                        };
+                    }
+                });
+        });
+        ui.horizontal(|ui| match &mut generator.discount {
+            DiscountDistribution::Uniform { min, max } => {
+                ui.label("Min");
+                ui.add(percent_value(min));
+                ui.label("Max");
+                ui.add(percent_value(max));
+            }
+            DiscountDistribution::Normal { mean, std_dev } => {
+                ui.label("Mean");
+                ui.add(egui::DragValue::new(mean).speed(0.1));
+                ui.label("Std dev");
+                ui.add(egui::DragValue::new(std_dev).speed(0.1));
+            }
+            DiscountDistribution::Histogram { weights } => {
+                ui.label("discount:weight");
+                if ui.text_edit_singleline(&mut self.histogram_text).changed() {
+                    *weights = parse_histogram(&self.histogram_text).unwrap_or_default();
+                }
+            }
+        });
+    }
+}
+
+// parses "discount:weight" pairs separated by commas, e.g. "5:2, 10.5:1"
+fn parse_histogram(text: &str) -> Option<Vec<(Discount, f64)>> {
+    text.split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| {
+            let (discount, weight) = pair.split_once(':')?;
+            Some((discount.trim().parse().ok()?, weight.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+impl LiquidationApp {
+    // Keeps the last thing that went wrong during a block; everything else
+    // it did is in the market's events
+    fn record_outcome(&mut self, outcome: BlockOutcome) {
+        if let Some(error) = outcome.errors.last() {
+            self.last_error = Some(format!("Block {}: {}", outcome.block, error));
+        }
+        for (id, result) in outcome.liquidations {
+            if let Err(err) = result {
+                self.last_error = Some(format!("Liquidation {} failed: {}", id, err));
+            }
+        }
+        self.oracle_price = self.sim.price.to_f64();
+    }
+
+    // Lists every queued liquidation with its progress
+    fn show_queue(&mut self, ui: &mut egui::Ui) {
+        let mut cancelled = None;
+        let assets = &self.sim.market.assets;
+        egui::ScrollArea::vertical()
+            .id_salt("liquidation_queue")
+            .max_height(200.0)
+            .show(ui, |ui| {
+                egui::Grid::new("liquidation_queue_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for header in ["Id", "Account", "Status", "Progress", ""] {
+                            ui.strong(header);
+                        }
+                        ui.end_row();
+
+                        for entry in self.sim.market.liquidations.iter() {
+                            let liquidation = &entry.liquidation;
+                            ui.label(liquidation.id.to_string());
+                            ui.label(liquidation.account_liquidated.to_string());
+                            ui.label(format!("{:?}", liquidation.status));
+                            ui.add(
+                                egui::ProgressBar::new(entry.progress())
+                                    .desired_width(120.0)
+                                    .text(format!(
+                                        "{} / {}",
+                                        assets.bid_asset.format(entry.filled()),
+                                        assets.bid_asset.format(entry.original_amount)
+                                    )),
+                            );
+                            if entry.is_pending() && ui.small_button("Cancel").clicked() {
+                                cancelled = Some(liquidation.id);
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        if let Some(id) = cancelled {
+            self.sim.market.cancel_liquidation(id);
+        }
+    }
+}
+
+impl eframe::App for LiquidationApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Top panel
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            ui.heading("Liquidation Simulation - Kylix Finance");
+            ui.horizontal(|ui| {
+                ui.label("Seed:");
+                ui.add(egui::DragValue::new(&mut self.seed).speed(1));
+                if ui.button("Restart").clicked() {
+                    *self = Self::with_seed(self.seed);
+                }
+                ui.label(format!("Running seed: {}", self.sim.rng.seed()));
+
+                ui.separator();
+
+                ui.label(format!("Block: {}", self.sim.block()));
+                let play = if self.playing { "Pause" } else { "Play" };
+                if ui.button(play).clicked() {
+                    self.playing = !self.playing;
+                }
+                if ui
+                    .add_enabled(!self.playing, egui::Button::new("Step"))
+                    .clicked()
+                {
+                    let outcome = self.sim.step();
+                    self.record_outcome(outcome);
+                }
+                ui.add(egui::Slider::new(&mut self.blocks_per_frame, 1..=100).text("blocks/frame"));
+            });
+            ui.horizontal(|ui| {
+                ui.label("State file:");
+                ui.text_edit_singleline(&mut self.state_path);
+                if ui.button("Save").clicked() {
+                    let snapshot = Snapshot::new(self.sim.clone());
+                    self.state_status = Some(match snapshot.save(&self.state_path) {
+                        Ok(()) => format!("Saved to {}", self.state_path),
+                        Err(err) => format!("Saving failed: {}", err),
+                    });
+                }
+                if ui.button("Open").clicked() {
+                    self.state_status = Some(match Snapshot::load(&self.state_path) {
+                        Ok(snapshot) => {
+                            self.open(snapshot.simulation);
+                            format!("Opened {}", self.state_path)
+                        }
+                        Err(err) => format!("Opening failed: {}", err),
+                    });
+                }
+                if let Some(status) = &self.state_status {
+                    ui.label(status);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Scenario:");
+                ui.text_edit_singleline(&mut self.scenario_path);
+                if ui.button("Open Scenario").clicked() {
+                    self.state_status = Some(match self.open_scenario() {
+                        Ok(()) => format!("Opened {}", self.scenario_path),
+                        Err(err) => format!("Opening failed: {}", err),
+                    });
+                }
+                if let Some(steps) = self.scenario.as_ref().map(|scenario| scenario.steps.len()) {
+                    let done = self.scenario_step >= steps;
+                    if ui
+                        .add_enabled(!self.playing && !done, egui::Button::new("Next Step"))
+                        .clicked()
+                    {
+                        self.next_scenario_step();
+                    }
+                    ui.label(format!(
+                        "Step {}/{}, {} fills",
+                        self.scenario_step,
+                        steps,
+                        self.scenario_outcome.fills.len()
+                    ));
+                }
+            });
+        });
+
+        if self.playing {
+            for _ in 0..self.blocks_per_frame {
+                let outcome = self.sim.step();
+                self.record_outcome(outcome);
+            }
+            ctx.request_repaint();
+        }
+
+        // Side panel
+        egui::SidePanel::left("side_panel").show(ctx, |ui| {
+            // Create a single bid panel
+            ui.group(|ui| {
+                ui.label("Create a single Bids:");
+                ui.horizontal(|ui| {
+                    ui.label("Bidder");
+                    ui.add(egui::DragValue::new(&mut self.acting_account).speed(1));
+                });
+                ui.label("Amount");
+                ui.add(egui::DragValue::new(&mut self.new_bid_amount).speed(5));
+
+                let rules = &self.sim.market.rules;
+                ui.label("Discount (%)");
+                ui.add(discount_slider(&mut self.new_bid_discount, rules));
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.new_bid_expires, "Expires in");
+                    ui.add_enabled(
+                        self.new_bid_expires,
+                        egui::DragValue::new(&mut self.new_bid_lifetime)
+                            .range(1..=BlockNumber::MAX)
+                            .suffix(" blocks"),
+                    );
+                });
+
+                ui.label(format!(
+                    "Next bid id (block:index): {}",
+                    self.sim.market.next_id()
+                ));
+
+                let valid = match self.sim.market.check_bid(
+                    self.acting_account,
+                    self.new_bid_amount,
+                    self.new_bid_discount,
+                ) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        ui.colored_label(egui::Color32::RED, err.to_string());
+                        false
+                    }
+                };
+                if ui
+                    .add_enabled(valid, egui::Button::new("Add a Bid"))
+                    .clicked()
+                {
+                    let expires_at = self
+                        .new_bid_expires
+                        .then(|| self.sim.block().saturating_add(self.new_bid_lifetime));
+                    if let Err(err) = self.sim.market.place_bid_until(
+                        self.acting_account,
+                        self.new_bid_amount,
+                        self.new_bid_discount,
+                        expires_at,
+                    ) {
+                        self.last_error = Some(format!("Placing the bid failed: {}", err));
+                    }
+                }
+            });
+
+            ui.separator();
+
+            ui.group(|ui| {
+                ui.label("Add Random Bids:");
+                ui.add(egui::Slider::new(&mut self.num_new_bids, 1..=100).text("count"));
+                self.show_generator(ui);
+
+                let valid = match self.sim.generator.validate() {
+                    Ok(()) => true,
+                    Err(err) => {
+                        ui.colored_label(egui::Color32::RED, err.to_string());
+                        false
+                    }
+                };
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(valid, egui::Button::new("Generate Bids"))
+                        .clicked()
+                    {
+                        let placed = (0..self.num_new_bids)
+                            .filter(|_| {
+                                self.sim
+                                    .market
+                                    .place_random_bid(&mut self.sim.rng, &self.sim.generator)
+                                    .is_ok()
+                            })
+                            .count() as u64;
+
+                        if placed < self.num_new_bids {
+                            self.last_error = Some(format!(
+                                "{} of {} random bids rejected",
+                                self.num_new_bids - placed,
+                                self.num_new_bids
+                            ));
+                        }
+                    }
+                    ui.add_enabled(
+                        valid,
+                        egui::Checkbox::new(&mut self.sim.arrivals, "Arrive every block"),
+                    );
+                });
+            });
+
+            ui.separator();
+
+            ui.group(|ui| self.show_import(ui));
+
+            ui.separator();
+
+            ui.group(|ui| {
+                ui.label(format!(
+                    "Selected Bid ({} closed so far):",
+                    self.sim.market.book.history.closed().len()
+                ));
+                let Some(id) = self.selected_bid else {
+                    ui.label("Click a bid in the list to select it.");
+                    return;
+                };
+                ui.label(format!("Id: {}", id));
+
+                ui.horizontal(|ui| {
+                    ui.label("As Account:");
+                    ui.add(egui::DragValue::new(&mut self.acting_account).speed(1));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Amount:");
+                    ui.add(egui::DragValue::new(&mut self.amend_amount).speed(5));
+                });
+                let rules = &self.sim.market.rules;
+                let valid = match rules.check(self.amend_amount, self.amend_discount) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        ui.colored_label(egui::Color32::RED, err.to_string());
+                        false
+                    }
+                };
+                ui.add(discount_slider(&mut self.amend_discount, rules));
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(valid, egui::Button::new("Amend Bid"))
+                        .clicked()
+                    {
+                        match self.sim.market.amend_bid(
+                            self.acting_account,
+                            id,
+                            self.amend_amount,
+                            self.amend_discount,
+                        ) {
+                            Ok(new_id) => self.selected_bid = Some(new_id),
+                            Err(err) => {
+                                self.last_error =
+                                    Some(format!("Amending bid {} failed: {}", id, err))
+                            }
+                        }
+                    }
+                    if ui.button("Cancel Bid").clicked() {
+                        match self.sim.market.cancel_bid(self.acting_account, id) {
+                            Ok(_) => self.selected_bid = None,
+                            Err(err) => {
+                                self.last_error =
+                                    Some(format!("Cancelling bid {} failed: {}", id, err))
+                            }
+                        }
+                    }
+                });
+            });
+
+            ui.separator();
+
+            ui.group(|ui| {
+                ui.label("Liquidation Controls:");
+
+                ui.horizontal(|ui| {
+                    ui.label("Liquidation Account:");
+                    ui.add(egui::DragValue::new(&mut self.new_liquidation_account).speed(1));
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Liquidation Amount:");
+                    ui.add(egui::DragValue::new(&mut self.new_liquidation_amount).speed(10));
+                });
+
+                if ui.button("Queue Liquidation").clicked() {
+                    self.sim.market.queue_liquidation(
+                        self.new_liquidation_account,
+                        self.new_liquidation_amount,
+                    );
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("Collateral Price:");
+                    let changed = ui
+                        .add(
+                            egui::DragValue::new(&mut self.oracle_price)
+                                .speed(0.01)
+                                .range(0.0001..=f64::MAX),
+                        )
+                        .changed();
+                    if changed {
+                        match Price::from_f64(self.oracle_price) {
+                            Some(price) => self.sim.set_price(price),
+                            None => {
+                                self.last_error =
+                                    Some(format!("Invalid collateral price: {}", self.oracle_price))
+                            }
+                        }
+                    }
+                });
+
+                self.show_mechanism(ui);
+
+                if ui.button("Process Queue").clicked() {
+                    let results = self
+                        .sim
+                        .market
+                        .process_liquidations_with(&self.sim.mechanism, self.sim.price);
+                    for (id, result) in results {
+                        if let Err(err) = result {
+                            self.last_error = Some(format!("Liquidation {} failed: {}", id, err));
+                        }
+                    }
+                }
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("At Block:");
+                    ui.add(egui::DragValue::new(&mut self.schedule_block).speed(1));
+                    if ui.button("Schedule Liquidation").clicked() {
+                        self.sim.schedule(
+                            self.schedule_block,
+                            Event::Liquidate {
+                                account: self.new_liquidation_account,
+                                amount: self.new_liquidation_amount,
+                            },
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Price:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.scheduled_price)
+                            .speed(0.01)
+                            .range(0.0001..=f64::MAX),
+                    );
+                    if ui.button("Schedule Price Move").clicked() {
+                        match Price::from_f64(self.scheduled_price) {
+                            Some(price) => self
+                                .sim
+                                .schedule(self.schedule_block, Event::SetPrice { price }),
+                            None => {
+                                self.last_error = Some(format!(
+                                    "Invalid collateral price: {}",
+                                    self.scheduled_price
+                                ))
+                            }
+                        }
+                    }
+                });
+            });
+
+            ui.separator();
+
+            ui.heading("Liquidation Queue");
+            let metrics = &self.sim.market.metrics;
+            let assets = &self.sim.market.assets;
+            ui.label(format!(
+                "Filled {} of {} ({:.1}%), average discount {:.2}%, collateral sold {}",
+                assets.bid_asset.format(metrics.filled),
+                assets.bid_asset.format(metrics.requested),
+                metrics.fill_rate() * 100.0,
+                metrics.average_discount(),
+                assets.collateral.format(metrics.collateral_sold)
+            ));
+            self.show_queue(ui);
+
+            ui.separator();
+
+            ui.group(|ui| {
+                ui.label("Scheduled Events:");
+                for (block, event) in self.sim.scheduled().take(10) {
+                    ui.label(format!("Block {}: {:?}", block, event));
+                }
+            });
+
+            ui.separator();
+
+            ui.group(|ui| {
+                ui.label("Latest Events:");
+                if let Some(error) = &self.last_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                // Newest first; the events tab has them all
+                for event in self.sim.market.events.iter().rev().take(10) {
+                    ui.label(event.to_string());
+                }
+            });
+        });
+
+        // Central panel
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.tab, Tab::ActiveBids, "Active Bids");
+                ui.selectable_value(&mut self.tab, Tab::History, "Bid History");
+                ui.selectable_value(&mut self.tab, Tab::Accounts, "Accounts");
+                ui.selectable_value(&mut self.tab, Tab::Events, "Events");
+            });
+            ui.separator();
+
+            match self.tab {
+                Tab::ActiveBids => {}
+                Tab::History => return self.show_history(ui),
+                Tab::Accounts => return self.show_accounts(ui),
+                Tab::Events => return self.show_events(ui),
+            }
+
+            ui.heading("Active Bids");
+            {
+                // Show the bar chart grouped by discount
+                ui.heading("Bids by Discount");
+
+                // Aggregate the bids by discount, one bar per tier; a tier
+                // too large to total leaves the chart empty
+                let discount_map = match group_bids_by_discount(&self.sim.market.book.bids) {
+                    Ok(discount_map) => discount_map,
+                    Err(err) => {
+                        ui.colored_label(
+                            egui::Color32::RED,
+                            format!("Cannot total the bids of a discount: {}", err),
+                        );
+                        Default::default()
+                    }
+                };
+                let rules = &self.sim.market.rules;
+                let tiers: Vec<Discount> = rules.tiers().collect();
+                let step = rules.discount_step.to_f64();
+                let (first, last) = (rules.min_discount.to_f64(), rules.max_discount.to_f64());
+
+                Plot::new("bids_by_discount")
+                    .height(400.0) // set the height of the plot
+                    .width(800.0) // set the width of the plot
+                    .include_x(first - step / 2.0)
+                    .include_x(last + step / 2.0)
+                    .include_y(20.5)
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .show(ui, |plot_ui| {
+                        // Convert discount_map into a series of Bars
+                        let mut bars = Vec::with_capacity(tiers.len());
+
+                        for discount in &tiers {
+                            // If no bids for this discount, total_amount = 0
+                            let total_amount =
+                                discount_map.get(discount).copied().unwrap_or_default();
+
+                            // Bar::new(x_position, height)
+                            let bar = Bar::new(discount.to_f64(), total_amount.to_f64())
+                                .width(step * 0.6); // adjust for a nicer spacing
+                            bars.push(bar);
+                        }
+
+                        let chart = BarChart::new(bars).name("Total Bids by Discount");
+
+                        plot_ui.bar_chart(chart);
+                        plot_ui.plot_bounds();
+                    });
+
+                ui.separator();
+
+                ui.heading("Times Discounts Emptied");
+
+                let mut bars_emptied = Vec::with_capacity(tiers.len());
+                for discount in &tiers {
+                    // If discount never had any empties, it’s 0
+                    let empties_count = self
+                        .sim
+                        .market
+                        .metrics
+                        .emptied_discounts
+                        .get(discount)
+                        .copied()
+                        .unwrap_or(0);
+                    let bar = Bar::new(discount.to_f64(), empties_count as f64).width(step * 0.6);
+                    bars_emptied.push(bar);
+                }
+
+                Plot::new("discount_emptied_plot")
+                    .height(200.0) // set the height of the plot
+                    .width(800.0) // set the width of the plot
+                    .include_x(first - step / 2.0)
+                    .include_x(last + step / 2.0)
+                    .include_y(20.5)
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .show(ui, |plot_ui| {
+                        let chart = BarChart::new(bars_emptied).name("Empties by Discount");
+                        plot_ui.bar_chart(chart);
+                    });
+
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    let now = self.sim.block();
+                    for bid in &self.sim.market.book.bids {
+                        let selected = self.selected_bid == Some(bid.id());
+                        let expiry = match bid.expires_at {
+                            Some(expires_at) => {
+                                format!(" (expires in {} blocks)", expires_at.saturating_sub(now))
+                            }
+                            None => String::new(),
+                        };
+                        if ui
+                            .selectable_label(selected, format!("{:?}{}", bid, expiry))
+                            .clicked()
+                        {
+                            self.selected_bid = Some(bid.id());
+                            self.amend_amount = bid.amount;
+                            self.amend_discount = bid.discount;
+                        }
+                    }
+                });
+
+                ui.separator();
+            }
+        });
+    }
+}
+
+/// Opens the native window and runs the simulation until it is closed.
+pub fn run() -> eframe::Result {
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "Kylix Finance - Collateral Liquidation Simulation",
+        native_options,
+        Box::new(|_cc| Ok(Box::new(LiquidationApp::new()))),
+    )
+}
+
+// Lets amounts be edited with a `DragValue`, in units
+impl egui::emath::Numeric for Balance {
+    const INTEGRAL: bool = true;
+    const MIN: Self = Balance::ZERO;
+    const MAX: Self = Balance::MAX;
+
+    fn to_f64(self) -> f64 {
+        Balance::to_f64(self)
+    }
+
+    fn from_f64(num: f64) -> Self {
+        Balance::new(num.max(0.0) as u128)
+    }
+}
+
+// Lets discounts be edited with a `DragValue` or a `Slider`, in percent
+impl egui::emath::Numeric for Discount {
+    const INTEGRAL: bool = false;
+    const MIN: Self = Discount::ZERO;
+    const MAX: Self = Discount::FULL;
+
+    fn to_f64(self) -> f64 {
+        Discount::to_f64(self)
+    }
+
+    fn from_f64(num: f64) -> Self {
+        Discount::from_f64(num.clamp(0.0, 100.0)).unwrap_or_default()
+    }
+}
+
+// a discount below 100%, edited in steps of a basis point
+fn percent_value(discount: &mut Discount) -> egui::DragValue<'_> {
+    egui::DragValue::new(discount)
+        .range(Discount::ZERO..=Discount::from_bps(Discount::FULL.bps() - 1))
+        .speed(0.1)
+        .max_decimals(2)
+}
+
+// picks one of the tiers `rules` accept
+fn discount_slider<'a>(discount: &'a mut Discount, rules: &BidRules) -> egui::Slider<'a> {
+    egui::Slider::new(discount, rules.min_discount..=rules.max_discount)
+        .step_by(rules.discount_step.to_f64())
+        .max_decimals(2)
+        .text("discount")
+}
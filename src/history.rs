@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AccountId, Balance, BidId, BidStatus, LiquidationId, LiquidationReport, UserBid};
+
+/// A bid that has left the book for good.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ClosedBid {
+    /// The bid as it was when it closed, with its final status.
+    pub bid: UserBid,
+    /// Total bid asset the bid paid towards liquidations.
+    pub total_filled: Balance,
+    /// Liquidations that filled the bid, in fill order.
+    pub liquidations: Vec<LiquidationId>,
+}
+
+impl ClosedBid {
+    /// The final status of the bid: fulfilled, cancelled or expired.
+    pub fn status(&self) -> &BidStatus {
+        &self.bid.status
+    }
+}
+
+/// Ledger of fulfilled, cancelled and expired bids.
+///
+/// Liquidations filling a bid are tracked while the bid is still open, so its
+/// [`ClosedBid`] entry lists every liquidation that consumed it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BidLedger {
+    closed: Vec<ClosedBid>,
+    // liquidations that filled each bid still in the book
+    #[serde(with = "open_fills")]
+    open_fills: HashMap<BidId, Vec<LiquidationId>>,
+}
+
+// JSON maps only take strings as keys, so the open fills are written as a
+// list of bid ids and liquidations, in bid order
+mod open_fills {
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::{BidId, LiquidationId};
+
+    pub fn serialize<S: Serializer>(
+        open_fills: &HashMap<BidId, Vec<LiquidationId>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let sorted: BTreeMap<_, _> = open_fills.iter().collect();
+        serializer.collect_seq(sorted)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<BidId, Vec<LiquidationId>>, D::Error> {
+        let pairs = Vec::<(BidId, Vec<LiquidationId>)>::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+impl BidLedger {
+    /// Creates an empty ledger.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every closed bid, in the order they closed.
+    pub fn closed(&self) -> &[ClosedBid] {
+        &self.closed
+    }
+
+    /// Closed bids placed by `bidder`, in the order they closed.
+    pub fn by_bidder(&self, bidder: AccountId) -> impl Iterator<Item = &ClosedBid> {
+        self.closed
+            .iter()
+            .filter(move |closed| closed.bid.bidder == bidder)
+    }
+
+    /// Liquidations that have filled the still open bid `id` so far.
+    pub fn open_fills(&self, id: BidId) -> &[LiquidationId] {
+        self.open_fills.get(&id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Records every fill of a liquidation run and closes the bids it
+    /// consumed entirely.
+    pub fn record_liquidation(&mut self, report: &LiquidationReport) {
+        for fill in &report.fills {
+            self.open_fills
+                .entry(fill.bid_id())
+                .or_default()
+                .push(report.liquidation_id);
+        }
+        for bid in &report.closed {
+            self.close(bid.clone());
+        }
+    }
+
+    /// Records a bid cancelled by its owner.
+    pub fn record_cancelled(&mut self, bid: UserBid) {
+        self.close(bid);
+    }
+
+    /// Records a bid that reached its expiry block.
+    pub fn record_expired(&mut self, bid: UserBid) {
+        self.close(bid);
+    }
+
+    /// Moves the fills tracked for an open bid when it is re-queued under a
+    /// new id.
+    pub fn reindex(&mut self, old_id: BidId, new_id: BidId) {
+        if let Some(liquidations) = self.open_fills.remove(&old_id) {
+            self.open_fills.insert(new_id, liquidations);
+        }
+    }
+
+    fn close(&mut self, bid: UserBid) {
+        let liquidations = self.open_fills.remove(&bid.id()).unwrap_or_default();
+        self.closed.push(ClosedBid {
+            total_filled: bid.original_amount - bid.amount,
+            liquidations,
+            bid,
+        });
+    }
+}
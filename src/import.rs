@@ -0,0 +1,345 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{AccountId, Balance, BidId, BlockNumber, Discount};
+
+/// The names of the columns, or JSON fields, each bid field is read from.
+///
+/// The defaults are the field names themselves: `bidder`, `amount`,
+/// `discount`, `block`, `index` and the optional `expires_at`.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Columns {
+    pub bidder: String,
+    pub amount: String,
+    pub discount: String,
+    pub block: String,
+    pub index: String,
+    /// Read if the file has it; bids without a value never expire.
+    pub expires_at: String,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Self {
+            bidder: "bidder".to_string(),
+            amount: "amount".to_string(),
+            discount: "discount".to_string(),
+            block: "block".to_string(),
+            index: "index".to_string(),
+            expires_at: "expires_at".to_string(),
+        }
+    }
+}
+
+impl Columns {
+    fn required(&self) -> [&str; 5] {
+        [
+            &self.bidder,
+            &self.amount,
+            &self.discount,
+            &self.block,
+            &self.index,
+        ]
+    }
+}
+
+/// A bid read from a file, see [`crate::Market::import_bids`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ImportedBid {
+    /// The line of the file the bid was read from, counting from 1.
+    pub line: usize,
+    pub bidder: AccountId,
+    /// Amount in units of the bid asset, see [`Balance`].
+    pub amount: Balance,
+    pub discount: Discount,
+    /// The block and index the bid was placed at.
+    pub id: BidId,
+    pub expires_at: Option<BlockNumber>,
+}
+
+/// A row that could not be read or imported. The other rows are not
+/// affected.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct RowError {
+    /// The line of the file, counting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// The bids read from a file, and the rows that could not be read.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct BidImport {
+    /// Every valid row, in file order.
+    pub bids: Vec<ImportedBid>,
+    /// Every invalid row, in file order.
+    pub errors: Vec<RowError>,
+}
+
+/// Reasons a whole file of bids cannot be read.
+#[derive(Debug)]
+pub enum ImportError {
+    /// The file cannot be read.
+    Io(std::io::Error),
+    /// The CSV header cannot be read.
+    Header(String),
+    /// The CSV header lacks a required column.
+    MissingColumn(String),
+    /// The file extension is not `.csv`, `.jsonl` or `.ndjson`.
+    UnknownFormat(String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(f, "cannot read bids: {}", err),
+            ImportError::Header(err) => write!(f, "invalid header: {}", err),
+            ImportError::MissingColumn(column) => write!(f, "no {:?} column", column),
+            ImportError::UnknownFormat(path) => {
+                write!(f, "{} is not a .csv, .jsonl or .ndjson file", path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// Reads bids from CSV: a header naming the columns, see [`Columns`], then
+/// one bid per line. Fields may be quoted with `"`; a quoted field cannot
+/// span lines. Columns not mapped to a bid field are ignored.
+pub fn parse_bids_csv(text: &str, columns: &Columns) -> Result<BidImport, ImportError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(number, line)| (number + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty());
+    let mut import = BidImport::default();
+    let Some((_, header)) = lines.next() else {
+        return Err(ImportError::Header("the file is empty".to_string()));
+    };
+    let header = split_csv_line(header).map_err(ImportError::Header)?;
+    let position = |name: &str| header.iter().position(|column| column.trim() == name);
+    for name in columns.required() {
+        if position(name).is_none() {
+            return Err(ImportError::MissingColumn(name.to_string()));
+        }
+    }
+
+    for (line, text) in lines {
+        let row = match split_csv_line(text) {
+            Ok(fields) if fields.len() == header.len() => read_row(line, columns, |name| {
+                position(name).map(|position| fields[position].trim().to_string())
+            }),
+            Ok(fields) => Err(RowError {
+                line,
+                message: format!("{} fields, the header has {}", fields.len(), header.len()),
+            }),
+            Err(message) => Err(RowError { line, message }),
+        };
+        match row {
+            Ok(bid) => import.bids.push(bid),
+            Err(err) => import.errors.push(err),
+        }
+    }
+    Ok(import)
+}
+
+/// Reads bids from JSON Lines: one JSON object per line, with the fields
+/// named by `columns`. Values may be numbers or strings, e.g.
+/// `{"bidder": 1, "amount": "1000", "discount": 2.5, "block": 7, "index": 0}`.
+pub fn parse_bids_jsonl(text: &str, columns: &Columns) -> BidImport {
+    let mut import = BidImport::default();
+    for (line, text) in text
+        .lines()
+        .enumerate()
+        .map(|(number, line)| (number + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty())
+    {
+        let row = match serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(text) {
+            Ok(object) => read_row(line, columns, |name| match object.get(name)? {
+                serde_json::Value::Null => None,
+                serde_json::Value::String(value) => Some(value.clone()),
+                value => Some(value.to_string()),
+            }),
+            Err(err) => Err(RowError {
+                line,
+                message: err.to_string(),
+            }),
+        };
+        match row {
+            Ok(bid) => import.bids.push(bid),
+            Err(err) => import.errors.push(err),
+        }
+    }
+    import
+}
+
+/// Reads a file of bids, as CSV if its name ends in `.csv` and as JSON Lines
+/// if it ends in `.jsonl` or `.ndjson`.
+pub fn load_bids(path: impl AsRef<Path>, columns: &Columns) -> Result<BidImport, ImportError> {
+    let path = path.as_ref();
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    if !matches!(extension, Some("csv" | "jsonl" | "ndjson")) {
+        return Err(ImportError::UnknownFormat(path.display().to_string()));
+    }
+    let text = std::fs::read_to_string(path).map_err(ImportError::Io)?;
+    match extension {
+        Some("csv") => parse_bids_csv(&text, columns),
+        _ => Ok(parse_bids_jsonl(&text, columns)),
+    }
+}
+
+// reads a bid from the fields `get` finds by column name
+fn read_row(
+    line: usize,
+    columns: &Columns,
+    get: impl Fn(&str) -> Option<String>,
+) -> Result<ImportedBid, RowError> {
+    let value = |name: &str| get(name).filter(|value| !value.is_empty());
+    let required = |name: &str| {
+        value(name).ok_or_else(|| RowError {
+            line,
+            message: format!("missing {}", name),
+        })
+    };
+
+    let bidder = parse_field(line, &columns.bidder, &required(&columns.bidder)?)?;
+    let amount = parse_field(line, &columns.amount, &required(&columns.amount)?)?;
+    let discount = parse_field(line, &columns.discount, &required(&columns.discount)?)?;
+    let block = parse_field(line, &columns.block, &required(&columns.block)?)?;
+    let index = parse_field(line, &columns.index, &required(&columns.index)?)?;
+    let expires_at = value(&columns.expires_at)
+        .map(|expires_at| parse_field(line, &columns.expires_at, &expires_at))
+        .transpose()?;
+    Ok(ImportedBid {
+        line,
+        bidder,
+        amount: Balance::new(amount),
+        discount,
+        id: BidId::new(block, index),
+        expires_at,
+    })
+}
+
+fn parse_field<T: FromStr>(line: usize, name: &str, value: &str) -> Result<T, RowError> {
+    value.parse().map_err(|_| RowError {
+        line,
+        message: format!("invalid {} {:?}", name, value),
+    })
+}
+
+// splits a line of CSV into its fields, unquoting them
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quoted field".to_string());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let csv = "\
+block,index,bidder,amount,discount,expires_at,tx
+3,0,1,1000,5,,0xab
+3,1,2,\"2,500\",2.5%,20,0xcd
+
+4,0,x,100,5,,0xef
+4,1,2,100,5
+";
+        let import = parse_bids_csv(csv, &Columns::default()).unwrap();
+
+        assert_eq!(
+            import.bids,
+            vec![ImportedBid {
+                line: 2,
+                bidder: 1,
+                amount: Balance::new(1000),
+                discount: Discount::percent(5),
+                id: BidId::new(3, 0),
+                expires_at: None,
+            }]
+        );
+        let lines: Vec<_> = import.errors.iter().map(|err| err.line).collect();
+        assert_eq!(lines, vec![3, 5, 6]);
+        assert_eq!(import.errors[0].message, r#"invalid amount "2,500""#);
+        assert_eq!(import.errors[1].message, r#"invalid bidder "x""#);
+        assert_eq!(import.errors[2].message, "5 fields, the header has 7");
+    }
+
+    #[test]
+    fn test_columns_are_mapped() {
+        let columns = Columns {
+            bidder: "account".to_string(),
+            amount: "size".to_string(),
+            ..Default::default()
+        };
+        let csv = "account,size,discount,block,index\n7,300,1.5,0,2\n";
+        let import = parse_bids_csv(csv, &columns).unwrap();
+        assert_eq!(import.bids[0].bidder, 7);
+        assert_eq!(import.bids[0].amount, Balance::new(300));
+        assert_eq!(import.bids[0].discount, Discount::from_bps(150));
+        assert!(import.errors.is_empty());
+
+        assert!(matches!(
+            parse_bids_csv(csv, &Columns::default()),
+            Err(ImportError::MissingColumn(column)) if column == "bidder"
+        ));
+    }
+
+    #[test]
+    fn test_parse_jsonl() {
+        let jsonl = r#"{"bidder": 1, "amount": "1000", "discount": 2.5, "block": 7, "index": 0, "expires_at": 9}
+{"bidder": 2, "amount": 500, "discount": "5", "block": 7, "index": 1, "extra": true}
+{"bidder": 2, "amount": 500, "block": 7, "index": 2}
+not json
+"#;
+        let import = parse_bids_jsonl(jsonl, &Columns::default());
+
+        assert_eq!(import.bids.len(), 2);
+        assert_eq!(import.bids[0].discount, Discount::from_bps(250));
+        assert_eq!(import.bids[0].expires_at, Some(9));
+        assert_eq!(import.bids[1].id, BidId::new(7, 1));
+        let lines: Vec<_> = import.errors.iter().map(|err| err.line).collect();
+        assert_eq!(lines, vec![3, 4]);
+        assert_eq!(import.errors[0].message, "missing discount");
+    }
+
+    #[test]
+    fn test_split_csv_line() {
+        assert_eq!(
+            split_csv_line(r#"a, "b, c" ,"d ""e""""#).unwrap(),
+            vec!["a", "b, c ", r#"d "e""#]
+        );
+        assert!(split_csv_line(r#"a,"b"#).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! Collateral liquidation engine for the Kylix Finance marketplace.
+//!
+//! Bidders place [`Bid`]s offering to buy liquidated collateral at a discount.
+//! A [`Liquidation`] is matched against the book with [`liquidate`], which
+//! fills the lowest-discount bids first and settles each fill in collateral
+//! at the oracle [`Price`] less the bid's discount. A [`Market`] backs the
+//! book with account balances, escrowing bids and settling fills, and a
+//! [`Simulation`] runs a market block by block with scheduled [`Event`]s.
+//! Other ways of matching liquidations, such as a [`DutchAuction`], plug in
+//! through the [`LiquidationMechanism`] trait.
+//!
+//! Runs are reproducible: randomness comes from a seeded [`SimRng`] and time
+//! from the market's simulated [`Clock`], never from the host.
+//!
+//! The egui front-end lives in [`gui`] and is only compiled with the `gui`
+//! feature, so the engine can be used headless without pulling in eframe.
+
+pub mod accounts;
+pub mod allocation;
+pub mod balance;
+pub mod batch;
+pub mod bid;
+pub mod book;
+pub mod clock;
+pub mod discount;
+pub mod dust;
+pub mod events;
+pub mod export;
+pub mod generator;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod history;
+pub mod import;
+pub mod liquidation;
+pub mod market;
+pub mod mechanism;
+pub mod metrics;
+pub mod queue;
+pub mod report;
+pub mod rng;
+pub mod rules;
+pub mod scenario;
+pub mod settlement;
+pub mod simulation;
+pub mod snapshot;
+
+pub use accounts::{AccountBalance, AccountError, Accounts};
+pub use allocation::AllocationPolicy;
+pub use balance::{Asset, Assets, Balance, BalanceOverflow, ParseBalanceError};
+pub use batch::{Batch, BatchReport, Distribution, RunSummary, TierEmptying};
+pub use bid::{create_bid, create_random_bid, Bid, BidId, BidStatus};
+pub use book::{BidBook, BidError};
+pub use clock::Clock;
+pub use discount::{Discount, ParseDiscountError};
+pub use dust::{DustPolicy, DustRule};
+pub use events::{EventLog, LoggedEvent, MarketEvent};
+pub use export::{
+    book_depth, fill_history, liquidation_summaries, save_rows, write_rows, DepthRow, FillRow,
+    Format, LiquidationSummary, Row,
+};
+pub use generator::{AmountDistribution, BidGenerator, DiscountDistribution, GeneratorError};
+pub use history::{BidLedger, ClosedBid};
+pub use import::{
+    load_bids, parse_bids_csv, parse_bids_jsonl, BidImport, Columns, ImportError, ImportedBid,
+    RowError,
+};
+pub use liquidation::{
+    apply_liquidation, group_bids_by_discount, liquidate, preview_allocated, preview_auction,
+    preview_liquidation, Liquidation, LiquidationStatus,
+};
+pub use market::Market;
+pub use mechanism::{DiscountQueue, DutchAuction, LiquidationMechanism, Mechanism};
+pub use metrics::LiquidationMetrics;
+pub use queue::{LiquidationQueue, QueuedLiquidation};
+pub use report::{Fill, LiquidationReport};
+pub use rng::SimRng;
+pub use rules::{BidRules, RulesError};
+pub use scenario::{Scenario, ScenarioError, ScenarioOutcome};
+pub use settlement::{ParsePriceError, Price, SettlementError};
+pub use simulation::{BlockOutcome, Event, Simulation};
+pub use snapshot::{Snapshot, SnapshotError, SNAPSHOT_VERSION};
+
+/// The number of a simulated block.
+pub type BlockNumber = u64;
+/// The sequential number of a transaction within a block.
+pub type BlockIndex = u64;
+/// Identifier of an account taking part in the marketplace.
+pub type AccountId = u32;
+/// Identifier of a liquidation.
+pub type LiquidationId = u64;
+
+/// A bid with the concrete types used by the simulation.
+pub type UserBid = Bid<AccountId, Balance, BlockNumber>;
+/// A liquidation with the concrete types used by the simulation.
+pub type SystemLiquidation = Liquidation<AccountId, Balance>;
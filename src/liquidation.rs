@@ -0,0 +1,952 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AllocationPolicy, Balance, BalanceOverflow, Bid, BidId, BidStatus, Discount, DustRule, Fill,
+    LiquidationId, LiquidationReport, Price, SettlementError, SystemLiquidation, UserBid,
+};
+
+/// Represents a liquidation event that has occurred in the system.
+#[derive(Clone, Eq, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub struct Liquidation<AccountId, Balance> {
+    /// Identifier of the liquidation.
+    pub id: LiquidationId,
+    /// The account that placed the bid.
+    pub account_liquidated: AccountId,
+    /// The account that placed the bid.
+    pub amount: Balance,
+    /// status of the liquidation
+    pub status: LiquidationStatus,
+}
+
+/// Represents a liquidation event that has occurred in the system.
+#[derive(Clone, Eq, PartialEq, PartialOrd, Debug, Default, Serialize, Deserialize)]
+pub enum LiquidationStatus {
+    /// The liquidation has been created
+    Created,
+    /// The liquidation has been partially fulfilled but not yet completed.
+    PartiallyFilled,
+    /// The liquidation has been fully fulfilled and is now closed.
+    Fulfilled,
+    /// The liquidation has been cancelled and is no longer available.
+    Cancelled,
+    /// The liquidation has been untouched
+    #[default]
+    Untouched,
+}
+
+impl<AccountId: Default, Balance: Default> Default for Liquidation<AccountId, Balance> {
+    fn default() -> Self {
+        Liquidation {
+            id: Default::default(),
+            account_liquidated: Default::default(),
+            amount: Default::default(),
+            status: LiquidationStatus::Untouched,
+        }
+    }
+}
+
+/// Sums the remaining amount of every bid per discount level. Fails if a
+/// level adds up to more than a [`Balance`] holds.
+pub fn group_bids_by_discount(
+    bids: &BTreeSet<UserBid>,
+) -> Result<HashMap<Discount, Balance>, BalanceOverflow> {
+    let mut discount_map: HashMap<Discount, Balance> = HashMap::new();
+    for bid in bids {
+        let total = discount_map.entry(bid.discount).or_default();
+        *total = total.checked_add(bid.amount).ok_or(BalanceOverflow)?;
+    }
+    Ok(discount_map)
+}
+
+/// Fills the liquidation from the lowest-discount bids first, earliest bids
+/// first within a discount.
+///
+/// `liquidation.amount` is owed in bid asset and every bid pays for it
+/// one-to-one; in return the bidder receives collateral bought at `price`
+/// less the bid's discount, see [`Price::collateral_for`]. The returned
+/// [`LiquidationReport`] lists one [`Fill`] per bid touched, in fill order,
+/// and hands back the bids that were fully filled and left the book.
+///
+/// The fills are worked out with [`preview_liquidation`] before anything is
+/// changed, so on error neither the book nor the liquidation is modified.
+pub fn liquidate(
+    bids: &mut BTreeSet<UserBid>,
+    liquidation: &mut SystemLiquidation,
+    price: Price,
+) -> Result<LiquidationReport, SettlementError> {
+    let report = preview_liquidation(bids, liquidation, price)?;
+    apply_liquidation(bids, liquidation, report)
+}
+
+/// Applies the fills of a previewed report to the book and the liquidation.
+///
+/// Every filled bid has its amount reduced to the fill's remaining amount;
+/// partially filled bids stay in the book, the others are moved to
+/// `report.closed`, a bid consumed with dust keeping the dust refunded as its
+/// amount. `report.emptied_discounts` is filled in from the book after the
+/// run.
+///
+/// Fails with [`SettlementError::StalePreview`] if a filled bid is no longer
+/// in the book, in which case neither the book nor the liquidation is
+/// modified.
+pub fn apply_liquidation(
+    bids: &mut BTreeSet<UserBid>,
+    liquidation: &mut SystemLiquidation,
+    mut report: LiquidationReport,
+) -> Result<LiquidationReport, SettlementError> {
+    // The filled bids, found in one pass over the book
+    let filled: BTreeSet<BidId> = report.fills.iter().map(Fill::bid_id).collect();
+    let mut current: BTreeMap<BidId, UserBid> = bids
+        .iter()
+        .filter(|bid| filled.contains(&bid.id()))
+        .map(|bid| (bid.id(), bid.clone()))
+        .collect();
+    // checked up front so that a stale preview changes nothing
+    let mut closed = BTreeSet::new();
+    for fill in &report.fills {
+        let id = fill.bid_id();
+        if !current.contains_key(&id) || closed.contains(&id) {
+            return Err(SettlementError::StalePreview { bid: id });
+        }
+        if !fill.partial {
+            closed.insert(id);
+        }
+    }
+    let mut touched = Vec::new();
+    for fill in &report.fills {
+        let current_bid = current
+            .remove(&fill.bid_id())
+            .expect("checked against the book");
+        bids.remove(&current_bid);
+        touched.push(current_bid.discount);
+
+        let status = if fill.partial {
+            BidStatus::PartiallyFilled
+        } else {
+            BidStatus::Fulfilled
+        };
+        let filled_bid = Bid {
+            bidder: current_bid.bidder,
+            amount: fill.remaining_amount + fill.dust,
+            discount: current_bid.discount,
+            blocknumber: current_bid.blocknumber,
+            index: current_bid.index,
+            original_amount: current_bid.original_amount,
+            status,
+            expires_at: current_bid.expires_at,
+        };
+        if fill.partial {
+            // a later fill of the same bid starts from what this one left
+            current.insert(filled_bid.id(), filled_bid.clone());
+            bids.insert(filled_bid);
+        } else {
+            report.closed.push(filled_bid);
+        }
+    }
+
+    touched.sort();
+    touched.dedup();
+    report.emptied_discounts = touched
+        .into_iter()
+        .filter(|discount| !bids.iter().any(|bid| bid.discount == *discount))
+        .collect();
+
+    liquidation.amount = report.remaining_amount;
+    liquidation.status = report.status.clone();
+    Ok(report)
+}
+
+/// Works out what [`liquidate`] would do without touching the book or the
+/// liquidation.
+///
+/// The fills, totals and status are exactly those `liquidate` reports;
+/// `emptied_discounts` and `closed` are left empty as they describe the book
+/// after the run.
+pub fn preview_liquidation(
+    bids: &BTreeSet<UserBid>,
+    liquidation: &SystemLiquidation,
+    price: Price,
+) -> Result<LiquidationReport, SettlementError> {
+    preview_allocated(
+        bids,
+        liquidation,
+        price,
+        &AllocationPolicy::Fifo,
+        &DustRule::default(),
+    )
+}
+
+/// Like [`preview_liquidation`], with the discount tier the liquidation
+/// cannot fill completely shared out according to `policy`, and the bids it
+/// would leave as dust handled according to `dust`.
+pub fn preview_allocated(
+    bids: &BTreeSet<UserBid>,
+    liquidation: &SystemLiquidation,
+    price: Price,
+    policy: &AllocationPolicy,
+    dust: &DustRule,
+) -> Result<LiquidationReport, SettlementError> {
+    preview_fills(bids, liquidation, price, None, policy, dust)
+}
+
+/// Works out the fills of a Dutch auction round offering `discount`.
+///
+/// Only bids asking for at most `discount` take part, lowest discount first
+/// and each tier shared out according to `policy` then `dust`, and every one
+/// of them is settled at `discount` rather than its own.
+pub fn preview_auction(
+    bids: &BTreeSet<UserBid>,
+    liquidation: &SystemLiquidation,
+    price: Price,
+    discount: Discount,
+    policy: &AllocationPolicy,
+    dust: &DustRule,
+) -> Result<LiquidationReport, SettlementError> {
+    preview_fills(bids, liquidation, price, Some(discount), policy, dust)
+}
+
+// fills bids tier by tier in book order, each at its own discount or at the
+// auction's
+fn preview_fills(
+    bids: &BTreeSet<UserBid>,
+    liquidation: &SystemLiquidation,
+    price: Price,
+    auction: Option<Discount>,
+    policy: &AllocationPolicy,
+    dust: &DustRule,
+) -> Result<LiquidationReport, SettlementError> {
+    let mut report = LiquidationReport {
+        liquidation_id: liquidation.id,
+        remaining_amount: liquidation.amount,
+        ..Default::default()
+    };
+
+    if liquidation.amount.is_zero() {
+        report.status = LiquidationStatus::Fulfilled;
+        return Ok(report);
+    }
+
+    let mut rest = bids.iter().peekable();
+    while let Some(first) = rest.peek() {
+        if report.remaining_amount.is_zero() {
+            break;
+        }
+        let tier_discount = first.discount;
+        let discount = match auction {
+            // bids are sorted by discount, so no later bid takes the offer
+            Some(offered) if tier_discount > offered => break,
+            Some(offered) => offered,
+            None => tier_discount,
+        };
+        let mut tier = Vec::new();
+        while let Some(bid) = rest.next_if(|bid| bid.discount == tier_discount) {
+            tier.push(bid);
+        }
+        let amounts: Vec<_> = tier.iter().map(|bid| bid.amount).collect();
+        let shares = policy
+            .allocate(&amounts, report.remaining_amount)
+            .map_err(|_| SettlementError::TierOverflow {
+                discount: tier_discount,
+            })?;
+
+        let settled = dust.apply(&amounts, &shares);
+
+        for ((bid, share), (spent, refunded)) in tier.into_iter().zip(shares).zip(settled) {
+            if spent.is_zero() {
+                continue;
+            }
+            if discount >= Discount::FULL {
+                return Err(SettlementError::InvalidDiscount {
+                    bid: bid.id(),
+                    discount,
+                });
+            }
+            let collateral_received = price
+                .collateral_for(spent, discount)
+                .ok_or(SettlementError::CollateralOverflow { bid: bid.id() })?;
+            report.total_collateral = report
+                .total_collateral
+                .checked_add(collateral_received)
+                .ok_or(SettlementError::CollateralOverflow { bid: bid.id() })?;
+            report.total_filled += spent;
+            report.remaining_amount -= spent;
+            report.dust_refunded += refunded;
+            report.fills.push(Fill {
+                bidder: bid.bidder,
+                blocknumber: bid.blocknumber,
+                index: bid.index,
+                discount,
+                bid_asset_spent: spent,
+                collateral_received,
+                partial: spent + refunded < bid.amount,
+                remaining_amount: bid.amount - spent - refunded,
+                dust: refunded,
+                rounded_off: share.saturating_sub(spent),
+            });
+        }
+    }
+
+    report.status = if report.fills.is_empty() {
+        // nothing to fill this time; a liquidation already partially filled
+        // stays so
+        match liquidation.status {
+            LiquidationStatus::PartiallyFilled => LiquidationStatus::PartiallyFilled,
+            _ => LiquidationStatus::Untouched,
+        }
+    } else if !report.remaining_amount.is_zero() {
+        LiquidationStatus::PartiallyFilled
+    } else {
+        LiquidationStatus::Fulfilled
+    };
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_random_bid, AccountId, Balance, BidId, BlockIndex, DustPolicy, SimRng};
+
+    const ALICE: AccountId = 1;
+    const BOB: AccountId = 2;
+    const CHARLIE: AccountId = 3;
+
+    #[test]
+    fn just_a_simulation() {
+        let mut bids: BTreeSet<UserBid> = BTreeSet::new();
+        let mut rng = SimRng::new(7);
+        generate_random_bids(&mut rng, &mut bids, 3);
+        let amounts: Vec<_> = bids
+            .iter()
+            .map(|bid| (bid.index, bid.amount.units(), bid.discount))
+            .collect();
+        assert_eq!(
+            amounts,
+            vec![
+                (0, 4200, Discount::percent(2)),
+                (2, 700, Discount::percent(4)),
+                (1, 1500, Discount::percent(8))
+            ]
+        );
+
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            account_liquidated: 1,
+            amount: Balance::new(5000),
+            status: LiquidationStatus::Created,
+        };
+
+        // liquidate some bids
+        let report = liquidate(&mut bids, &mut liquidation, Price::ONE).unwrap();
+
+        let fills: Vec<_> = report
+            .fills
+            .iter()
+            .map(|fill| {
+                (
+                    fill.index,
+                    fill.bid_asset_spent.units(),
+                    fill.collateral_received.units(),
+                    fill.partial,
+                )
+            })
+            .collect();
+        assert_eq!(
+            fills,
+            vec![
+                (0, 4200, 4285, false),
+                (2, 700, 729, false),
+                (1, 100, 108, true)
+            ]
+        );
+        assert_eq!(report.total_filled, Balance::new(5000));
+        assert_eq!(report.total_collateral, Balance::new(4285 + 729 + 108));
+        assert_eq!(report.remaining_amount, Balance::ZERO);
+        assert_eq!(report.status, LiquidationStatus::Fulfilled);
+        assert_eq!(
+            report.emptied_discounts,
+            vec![Discount::percent(2), Discount::percent(4)]
+        );
+        assert_eq!(report.closed.len(), 2);
+        assert_eq!(liquidation.amount, Balance::ZERO);
+        assert_eq!(liquidation.status, LiquidationStatus::Fulfilled);
+
+        let left = bids.iter().next().unwrap();
+        assert_eq!(bids.len(), 1);
+        assert_eq!(left.amount, Balance::new(1400));
+        assert_eq!(left.status, BidStatus::PartiallyFilled);
+    }
+
+    #[test]
+    fn test_liquidate_zero_amount() {
+        let mut bids = BTreeSet::new();
+
+        bids.insert(Bid {
+            bidder: ALICE,
+            amount: Balance::new(100),
+            discount: Discount::percent(10),
+            blocknumber: 1,
+            index: 1,
+            original_amount: Balance::new(100),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            amount: Balance::ZERO,
+            status: LiquidationStatus::Created,
+            account_liquidated: ALICE,
+        };
+
+        liquidate(&mut bids, &mut liquidation, Price::ONE).unwrap();
+
+        assert_eq!(bids.len(), 1, "No bids should be removed if amount is 0");
+        assert_eq!(
+            liquidation.status,
+            LiquidationStatus::Fulfilled,
+            "If there's nothing to liquidate, status should be Fulfilled"
+        );
+    }
+
+    #[test]
+    fn test_liquidate_empty_bids() {
+        let mut bids = BTreeSet::new();
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            amount: Balance::new(1000),
+            status: LiquidationStatus::Created,
+            account_liquidated: ALICE,
+        };
+
+        liquidate(&mut bids, &mut liquidation, Price::ONE).unwrap();
+
+        assert_eq!(liquidation.status, LiquidationStatus::Untouched);
+        assert_eq!(liquidation.amount, Balance::new(1000));
+    }
+
+    #[test]
+    fn test_liquidate_single_bid_exact_amount() {
+        let mut bids = BTreeSet::new();
+        bids.insert(Bid {
+            bidder: BOB,
+            amount: Balance::new(1000),
+            discount: Discount::percent(10),
+            blocknumber: 1,
+            index: 1,
+            original_amount: Balance::new(1000),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            amount: Balance::new(1000),
+            status: LiquidationStatus::Created,
+            account_liquidated: ALICE,
+        };
+
+        liquidate(&mut bids, &mut liquidation, Price::ONE).unwrap();
+
+        assert_eq!(bids.len(), 0);
+        assert_eq!(liquidation.status, LiquidationStatus::Fulfilled);
+        assert_eq!(liquidation.amount, Balance::ZERO);
+    }
+
+    #[test]
+    fn test_liquidate_multiple_bids_with_remainder() {
+        let mut bids = BTreeSet::new();
+
+        // Add bids with different discounts
+        bids.insert(Bid {
+            bidder: BOB,
+            amount: Balance::new(500),
+            discount: Discount::percent(15),
+            blocknumber: 1,
+            index: 1,
+            original_amount: Balance::new(500),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+
+        bids.insert(Bid {
+            bidder: CHARLIE,
+            amount: Balance::new(300),
+            discount: Discount::percent(10),
+            blocknumber: 1,
+            index: 2,
+            original_amount: Balance::new(300),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            amount: Balance::new(1000),
+            status: LiquidationStatus::Created,
+            account_liquidated: ALICE,
+        };
+
+        liquidate(&mut bids, &mut liquidation, Price::ONE).unwrap();
+
+        assert_eq!(liquidation.status, LiquidationStatus::PartiallyFilled);
+        assert_eq!(liquidation.amount, Balance::new(200));
+    }
+
+    #[test]
+    fn test_partial_bid_fill() {
+        let mut bids = BTreeSet::new();
+        bids.insert(Bid {
+            bidder: BOB,
+            amount: Balance::new(1500),
+            discount: Discount::percent(10),
+            blocknumber: 1,
+            index: 1,
+            original_amount: Balance::new(1500),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            amount: Balance::new(1000),
+            status: LiquidationStatus::Created,
+            account_liquidated: ALICE,
+        };
+
+        liquidate(&mut bids, &mut liquidation, Price::ONE).unwrap();
+
+        assert_eq!(bids.len(), 1);
+        let remaining_bid = bids.iter().next().unwrap();
+        assert_eq!(remaining_bid.amount, Balance::new(500));
+        assert_eq!(remaining_bid.status, BidStatus::PartiallyFilled);
+        assert_eq!(liquidation.status, LiquidationStatus::Fulfilled);
+        assert_eq!(liquidation.amount, Balance::ZERO);
+    }
+
+    #[test]
+    fn test_multiple_bids_exact_amount() {
+        let mut bids = BTreeSet::new();
+
+        bids.insert(Bid {
+            bidder: BOB,
+            amount: Balance::new(600),
+            discount: Discount::percent(15),
+            blocknumber: 1,
+            index: 1,
+            original_amount: Balance::new(600),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+
+        bids.insert(Bid {
+            bidder: CHARLIE,
+            amount: Balance::new(400),
+            discount: Discount::percent(10),
+            blocknumber: 1,
+            index: 2,
+            original_amount: Balance::new(400),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            amount: Balance::new(1000),
+            status: LiquidationStatus::Created,
+            account_liquidated: ALICE,
+        };
+
+        liquidate(&mut bids, &mut liquidation, Price::ONE).unwrap();
+
+        assert_eq!(bids.len(), 0);
+        assert_eq!(liquidation.status, LiquidationStatus::Fulfilled);
+        assert_eq!(liquidation.amount, Balance::ZERO);
+    }
+
+    #[test]
+    fn test_settlement_per_bid() {
+        let mut bids = BTreeSet::new();
+        bids.insert(Bid {
+            bidder: BOB,
+            amount: Balance::new(900),
+            discount: Discount::percent(10),
+            blocknumber: 1,
+            index: 1,
+            original_amount: Balance::new(900),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+        bids.insert(Bid {
+            bidder: CHARLIE,
+            amount: Balance::new(1000),
+            discount: Discount::percent(20),
+            blocknumber: 1,
+            index: 2,
+            original_amount: Balance::new(1000),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            amount: Balance::new(1300),
+            status: LiquidationStatus::Created,
+            account_liquidated: ALICE,
+        };
+
+        // collateral is worth 2 bid asset
+        let price = Price::from_ratio(2, 1).unwrap();
+        let report = liquidate(&mut bids, &mut liquidation, price).unwrap();
+
+        assert_eq!(
+            report.fills,
+            vec![
+                Fill {
+                    bidder: BOB,
+                    blocknumber: 1,
+                    index: 1,
+                    discount: Discount::percent(10),
+                    bid_asset_spent: Balance::new(900),
+                    // 900 / (2 * 0.9)
+                    collateral_received: Balance::new(500),
+                    partial: false,
+                    remaining_amount: Balance::ZERO,
+                    dust: Balance::ZERO,
+                    rounded_off: Balance::ZERO,
+                },
+                Fill {
+                    bidder: CHARLIE,
+                    blocknumber: 1,
+                    index: 2,
+                    discount: Discount::percent(20),
+                    bid_asset_spent: Balance::new(400),
+                    // 400 / (2 * 0.8)
+                    collateral_received: Balance::new(250),
+                    partial: true,
+                    remaining_amount: Balance::new(600),
+                    dust: Balance::ZERO,
+                    rounded_off: Balance::ZERO,
+                },
+            ]
+        );
+        assert_eq!(report.total_filled, Balance::new(1300));
+        assert_eq!(report.total_collateral, Balance::new(750));
+        assert_eq!(report.remaining_amount, Balance::ZERO);
+        assert_eq!(report.status, LiquidationStatus::Fulfilled);
+        assert_eq!(report.emptied_discounts, vec![Discount::percent(10)]);
+        assert_eq!(liquidation.status, LiquidationStatus::Fulfilled);
+        assert_eq!(bids.iter().next().unwrap().amount, Balance::new(600));
+    }
+
+    #[test]
+    fn test_report_totals_with_remainder() {
+        let mut bids = BTreeSet::new();
+        bids.insert(Bid {
+            bidder: BOB,
+            amount: Balance::new(300),
+            discount: Discount::percent(10),
+            blocknumber: 1,
+            index: 1,
+            original_amount: Balance::new(300),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+        bids.insert(Bid {
+            bidder: CHARLIE,
+            amount: Balance::new(200),
+            discount: Discount::percent(10),
+            blocknumber: 1,
+            index: 2,
+            original_amount: Balance::new(200),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            amount: Balance::new(1000),
+            status: LiquidationStatus::Created,
+            account_liquidated: ALICE,
+        };
+
+        let report = liquidate(&mut bids, &mut liquidation, Price::ONE).unwrap();
+
+        assert_eq!(report.fills.len(), 2);
+        assert!(report.fills.iter().all(|fill| !fill.partial));
+        assert_eq!(report.total_filled, Balance::new(500));
+        assert_eq!(report.remaining_amount, Balance::new(500));
+        assert_eq!(report.status, LiquidationStatus::PartiallyFilled);
+        assert_eq!(report.emptied_discounts, vec![Discount::percent(10)]);
+        assert!(bids.is_empty());
+        assert_eq!(report.closed.len(), 2);
+        assert!(report
+            .closed
+            .iter()
+            .all(|bid| bid.status == BidStatus::Fulfilled && bid.amount.is_zero()));
+    }
+
+    #[test]
+    fn test_settlement_error_leaves_state_untouched() {
+        let mut bids = BTreeSet::new();
+        bids.insert(Bid {
+            bidder: BOB,
+            amount: Balance::new(500),
+            discount: Discount::percent(10),
+            blocknumber: 1,
+            index: 1,
+            original_amount: Balance::new(500),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+        bids.insert(Bid {
+            bidder: CHARLIE,
+            amount: Balance::new(500),
+            discount: Discount::percent(100),
+            blocknumber: 1,
+            index: 2,
+            original_amount: Balance::new(500),
+            status: BidStatus::Active,
+            expires_at: None,
+        });
+        let before = bids.clone();
+
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            amount: Balance::new(1000),
+            status: LiquidationStatus::Created,
+            account_liquidated: ALICE,
+        };
+
+        let result = liquidate(&mut bids, &mut liquidation, Price::ONE);
+
+        assert_eq!(
+            result,
+            Err(SettlementError::InvalidDiscount {
+                bid: BidId::new(1, 2),
+                discount: Discount::percent(100)
+            })
+        );
+        assert_eq!(bids, before);
+        assert_eq!(liquidation.amount, Balance::new(1000));
+        assert_eq!(liquidation.status, LiquidationStatus::Created);
+    }
+
+    #[test]
+    fn test_stale_preview_is_an_error() {
+        let bid = |bidder, index| Bid {
+            bidder,
+            amount: Balance::new(500),
+            discount: Discount::percent(5),
+            blocknumber: 1,
+            index,
+            original_amount: Balance::new(500),
+            status: BidStatus::Active,
+            expires_at: None,
+        };
+        let mut bids = BTreeSet::from([bid(ALICE, 0), bid(BOB, 1)]);
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            amount: Balance::new(800),
+            status: LiquidationStatus::Created,
+            account_liquidated: CHARLIE,
+        };
+        let preview = preview_liquidation(&bids, &liquidation, Price::ONE).unwrap();
+
+        // BOB's bid leaves the book between the preview and the run
+        bids.remove(&bid(BOB, 1));
+        let before = bids.clone();
+        let result = apply_liquidation(&mut bids, &mut liquidation, preview);
+
+        assert_eq!(
+            result,
+            Err(SettlementError::StalePreview {
+                bid: BidId::new(1, 1)
+            })
+        );
+        assert_eq!(bids, before);
+        assert_eq!(liquidation.amount, Balance::new(800));
+        assert_eq!(liquidation.status, LiquidationStatus::Created);
+    }
+
+    #[test]
+    fn test_overflowing_tier_is_an_error() {
+        let mut bids = BTreeSet::new();
+        for (index, amount) in [Balance::MAX, Balance::new(1)].into_iter().enumerate() {
+            bids.insert(Bid {
+                bidder: BOB,
+                amount,
+                discount: Discount::percent(10),
+                blocknumber: 1,
+                index: index as u64,
+                original_amount: amount,
+                status: BidStatus::Active,
+                expires_at: None,
+            });
+        }
+        let before = bids.clone();
+
+        assert_eq!(group_bids_by_discount(&bids), Err(BalanceOverflow));
+
+        let mut liquidation = SystemLiquidation {
+            id: 1,
+            amount: Balance::new(1000),
+            status: LiquidationStatus::Created,
+            account_liquidated: ALICE,
+        };
+        assert_eq!(
+            liquidate(&mut bids, &mut liquidation, Price::ONE),
+            Err(SettlementError::TierOverflow {
+                discount: Discount::percent(10)
+            })
+        );
+        assert_eq!(bids, before);
+        assert_eq!(liquidation.amount, Balance::new(1000));
+    }
+
+    #[test]
+    fn test_pro_rata_within_the_last_tier() {
+        let mut bids = BTreeSet::new();
+        for (index, (bidder, amount, discount)) in [
+            (ALICE, 300, 5),
+            (BOB, 100, 10),
+            (CHARLIE, 300, 10),
+            (ALICE, 500, 15),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            bids.insert(Bid {
+                bidder,
+                amount: Balance::new(amount),
+                discount: Discount::percent(discount),
+                blocknumber: 1,
+                index: index as u64,
+                original_amount: Balance::new(amount),
+                status: BidStatus::Active,
+                expires_at: None,
+            });
+        }
+        let liquidation = SystemLiquidation {
+            id: 1,
+            amount: Balance::new(500),
+            status: LiquidationStatus::Created,
+            account_liquidated: ALICE,
+        };
+        let spent = |policy| {
+            let report = preview_allocated(
+                &bids,
+                &liquidation,
+                Price::ONE,
+                &policy,
+                &DustRule::default(),
+            )
+            .unwrap();
+            report
+                .fills
+                .iter()
+                .map(|fill| (fill.index, fill.bid_asset_spent.units()))
+                .collect::<Vec<_>>()
+        };
+
+        // the 5% tier is filled completely whatever the policy, the 200 left
+        // is shared out within the 10% tier and never reaches 15%
+        assert_eq!(
+            spent(AllocationPolicy::Fifo),
+            vec![(0, 300), (1, 100), (2, 100)]
+        );
+        assert_eq!(
+            spent(AllocationPolicy::ProRata),
+            vec![(0, 300), (1, 50), (2, 150)]
+        );
+        assert_eq!(
+            spent(AllocationPolicy::ProRataMinFill {
+                min_fill: Balance::new(60)
+            }),
+            vec![(0, 300), (2, 200)]
+        );
+    }
+
+    #[test]
+    fn test_dust_left_by_partial_fills() {
+        let mut bids = BTreeSet::new();
+        for (index, bidder) in [ALICE, BOB].into_iter().enumerate() {
+            bids.insert(crate::create_bid(
+                bidder,
+                Balance::new(300),
+                Discount::percent(5),
+                1,
+                index as u64,
+            ));
+        }
+        let liquidation = SystemLiquidation {
+            id: 1,
+            amount: Balance::new(295),
+            status: LiquidationStatus::Created,
+            account_liquidated: CHARLIE,
+        };
+        let preview = |policy| {
+            let dust = DustRule {
+                min_remaining: Balance::new(10),
+                policy,
+            };
+            preview_allocated(
+                &bids,
+                &liquidation,
+                Price::ONE,
+                &AllocationPolicy::Fifo,
+                &dust,
+            )
+            .unwrap()
+        };
+
+        // the 5 left to ALICE stay in the book
+        let report = preview(DustPolicy::Keep);
+        assert_eq!(report.fills.len(), 1);
+        assert_eq!(report.fills[0].remaining_amount, Balance::new(5));
+        assert!(report.fills[0].partial);
+
+        // ALICE's bid closes and the 5 are refunded
+        let report = preview(DustPolicy::Consume);
+        let fill = &report.fills[0];
+        assert_eq!(fill.bid_asset_spent, Balance::new(295));
+        assert_eq!(fill.dust, Balance::new(5));
+        assert!(!fill.partial);
+        assert_eq!(report.dust_refunded, Balance::new(5));
+        let mut book = bids.clone();
+        let report = apply_liquidation(&mut book, &mut liquidation.clone(), report).unwrap();
+        assert_eq!(report.closed[0].amount, Balance::new(5));
+        assert_eq!(report.closed[0].status, BidStatus::Fulfilled);
+        assert_eq!(book.len(), 1);
+
+        // ALICE keeps 10 and BOB fills the rest
+        let report = preview(DustPolicy::Round);
+        let spent: Vec<_> = report
+            .fills
+            .iter()
+            .map(|fill| {
+                (
+                    fill.bidder,
+                    fill.bid_asset_spent.units(),
+                    fill.rounded_off.units(),
+                )
+            })
+            .collect();
+        assert_eq!(spent, vec![(ALICE, 290, 5), (BOB, 5, 0)]);
+        assert_eq!(report.status, LiquidationStatus::Fulfilled);
+    }
+
+    // helpers
+
+    fn generate_random_bids(
+        rng: &mut SimRng,
+        bids: &mut BTreeSet<Bid<AccountId, Balance, BlockIndex>>,
+        n: u64,
+    ) {
+        // inser 10 random bids
+        for i in 0..n {
+            bids.insert(create_random_bid(rng, ALICE, 1, i));
+        }
+    }
+}
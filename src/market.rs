@@ -0,0 +1,1065 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    create_bid, AccountError, AccountId, Accounts, Assets, Balance, BidBook, BidError,
+    BidGenerator, BidId, BidRules, BlockNumber, Clock, Discount, DiscountQueue, EventLog,
+    ImportedBid, LiquidationId, LiquidationMechanism, LiquidationMetrics, LiquidationQueue,
+    LiquidationReport, LiquidationStatus, MarketEvent, Price, RowError, SettlementError,
+    SystemLiquidation, UserBid,
+};
+
+/// A bid book backed by account balances.
+///
+/// Placing a bid escrows its amount from the bidder, cancelling it refunds
+/// what is left, and liquidations pay the escrowed bid asset to the
+/// liquidated account in exchange for its collateral. Everything that changes
+/// the book, the balances or the queue is recorded in `events`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Market {
+    /// Open and closed bids.
+    pub book: BidBook,
+    /// Balances of every account.
+    pub accounts: Accounts,
+    /// Liquidations waiting for, or filled by, the book.
+    pub liquidations: LiquidationQueue,
+    /// Simulated time; new bids are stamped with its current block and their
+    /// index among the block's transactions.
+    pub clock: Clock,
+    /// How well the liquidations queued so far were filled, see
+    /// [`Market::process_liquidations_with`].
+    pub metrics: LiquidationMetrics,
+    /// The assets traded, used to write balances as token amounts.
+    pub assets: Assets,
+    /// What bids are accepted, see [`BidRules`].
+    pub rules: BidRules,
+    /// Everything that happened in the market, see [`MarketEvent`].
+    pub events: EventLog,
+}
+
+impl Market {
+    /// Creates an empty market.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `event` as happening at the current block.
+    pub fn record(&mut self, event: MarketEvent) {
+        self.events.record(self.clock.now(), event);
+    }
+
+    /// Creates new bid asset and collateral in `account`, see
+    /// [`Accounts::mint`].
+    pub fn mint(
+        &mut self,
+        account: AccountId,
+        bid_asset: Balance,
+        collateral: Balance,
+    ) -> Result<(), AccountError> {
+        self.accounts.mint(account, bid_asset, collateral)?;
+        self.record(MarketEvent::AccountFunded {
+            account,
+            bid_asset,
+            collateral,
+        });
+        Ok(())
+    }
+
+    /// Checks that [`Market::place_bid`] would accept the bid, without
+    /// placing it.
+    pub fn check_bid(
+        &self,
+        bidder: AccountId,
+        amount: Balance,
+        discount: Discount,
+    ) -> Result<(), BidError> {
+        self.rules.check(amount, discount)?;
+        let id = self.next_id();
+        if self.book.get(id).is_some() {
+            return Err(BidError::DuplicateId { id });
+        }
+        let available = self.accounts.balance(bidder).bid_asset;
+        if available < amount {
+            return Err(BidError::Account(AccountError::InsufficientFunds {
+                account: bidder,
+                needed: amount,
+                available,
+            }));
+        }
+        Ok(())
+    }
+
+    /// Escrows `amount` from `bidder` and adds the bid to the book. Returns
+    /// the id of the new bid.
+    ///
+    /// The bid must follow the market's [`BidRules`] and the bidder must hold
+    /// `amount`; otherwise nothing changes.
+    pub fn place_bid(
+        &mut self,
+        bidder: AccountId,
+        amount: Balance,
+        discount: Discount,
+    ) -> Result<BidId, BidError> {
+        self.place_bid_until(bidder, amount, discount, None)
+    }
+
+    /// Like [`Market::place_bid`], with the bid expiring at block
+    /// `expires_at` if set, see [`Market::expire_bids`]. The expiry must be
+    /// after the current block.
+    pub fn place_bid_until(
+        &mut self,
+        bidder: AccountId,
+        amount: Balance,
+        discount: Discount,
+        expires_at: Option<BlockNumber>,
+    ) -> Result<BidId, BidError> {
+        self.rules.check(amount, discount)?;
+        let now = self.clock.now();
+        if let Some(expires_at) = expires_at.filter(|expires_at| *expires_at <= now) {
+            return Err(BidError::AlreadyExpired { expires_at, now });
+        }
+        self.accounts.escrow(bidder, amount)?;
+        let id = self.next_id();
+        let mut bid = create_bid(bidder, amount, discount, id.block, id.index);
+        bid.expires_at = expires_at;
+        if let Err(err) = self.book.insert(bid) {
+            self.accounts.release(bidder, amount)?;
+            return Err(err);
+        }
+        let id = self.take_id();
+        self.record(MarketEvent::BidPlaced {
+            id,
+            bidder,
+            amount,
+            discount,
+            expires_at,
+        });
+        Ok(id)
+    }
+
+    /// Adds bids read from a file, see [`crate::import`], to the book under
+    /// the ids they were placed with, escrowing their amounts.
+    ///
+    /// Each bid must follow the market's [`BidRules`], have an id no open
+    /// bid has, expire after the block it was placed in and be covered by the
+    /// bidder's balance; with `mint_missing` set, bidders short of funds are
+    /// first minted what they lack. The bids that fail are left out and
+    /// returned by line. If a bid was placed at the current block or later,
+    /// the clock moves to the block after the latest one so that the bids
+    /// placed next cannot take an imported id.
+    pub fn import_bids(&mut self, bids: &[ImportedBid], mint_missing: bool) -> Vec<RowError> {
+        let mut errors = Vec::new();
+        let mut latest = None;
+        for imported in bids {
+            match self.import_bid(imported, mint_missing) {
+                Ok(()) => latest = latest.max(Some(imported.id.block)),
+                Err(err) => errors.push(RowError {
+                    line: imported.line,
+                    message: err.to_string(),
+                }),
+            }
+        }
+        if let Some(latest) = latest.filter(|latest| *latest >= self.clock.now()) {
+            self.clock.advance(latest + 1 - self.clock.now());
+        }
+        errors
+    }
+
+    fn import_bid(&mut self, imported: &ImportedBid, mint_missing: bool) -> Result<(), BidError> {
+        let ImportedBid {
+            bidder,
+            amount,
+            discount,
+            id,
+            expires_at,
+            ..
+        } = *imported;
+        self.rules.check(amount, discount)?;
+        if let Some(expires_at) = expires_at.filter(|expires_at| *expires_at <= id.block) {
+            return Err(BidError::AlreadyExpired {
+                expires_at,
+                now: id.block,
+            });
+        }
+        if self.book.get(id).is_some() {
+            return Err(BidError::DuplicateId { id });
+        }
+        let available = self.accounts.balance(bidder).bid_asset;
+        if mint_missing && available < amount {
+            self.mint(bidder, amount - available, Balance::ZERO)?;
+        }
+        self.accounts.escrow(bidder, amount)?;
+        let mut bid = create_bid(bidder, amount, discount, id.block, id.index);
+        bid.expires_at = expires_at;
+        if let Err(err) = self.book.insert(bid) {
+            self.accounts.release(bidder, amount)?;
+            return Err(err);
+        }
+        self.record(MarketEvent::BidPlaced {
+            id,
+            bidder,
+            amount,
+            discount,
+            expires_at,
+        });
+        Ok(())
+    }
+
+    /// Places a bid drawn from `generator`, every number coming from `rng`.
+    pub fn place_random_bid<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        generator: &BidGenerator,
+    ) -> Result<BidId, BidError> {
+        let bidder = generator.bidder(rng)?;
+        let amount = generator.amount(rng)?;
+        let discount = generator.discount(rng)?;
+        self.place_bid(bidder, amount, discount)
+    }
+
+    /// Sweeps the bids expiring at the current block, places the bids
+    /// arriving during it, as many as drawn from the arrival rate of
+    /// `generator`, then moves the clock to the next block. Returns the
+    /// outcome of every bid.
+    pub fn generate_block<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        generator: &BidGenerator,
+    ) -> Result<Vec<Result<BidId, BidError>>, BidError> {
+        let arrivals = generator.arrivals(rng)?;
+        self.expire_bids()?;
+        let placed = (0..arrivals)
+            .map(|_| self.place_random_bid(rng, generator))
+            .collect();
+        self.clock.advance(1);
+        Ok(placed)
+    }
+
+    /// Cancels `bidder`'s bid and refunds its remaining amount. Returns the
+    /// amount refunded.
+    pub fn cancel_bid(&mut self, bidder: AccountId, id: BidId) -> Result<Balance, BidError> {
+        let refund = self.book.cancel_bid(bidder, id)?.amount;
+        self.accounts.release(bidder, refund)?;
+        self.record(MarketEvent::BidCancelled {
+            id,
+            bidder,
+            refunded: refund,
+        });
+        Ok(refund)
+    }
+
+    /// Removes the bids expiring at the current block or earlier, see
+    /// [`BidBook::expire`], and refunds what they have left to their bidders.
+    /// Returns the expired bids.
+    ///
+    /// Fails if a bidder's escrow cannot cover the refunds, which only
+    /// happens if the book was changed without going through the market;
+    /// nothing changes then and the bids stay in the book.
+    pub fn expire_bids(&mut self) -> Result<Vec<UserBid>, BidError> {
+        let now = self.clock.now();
+        let mut accounts = self.accounts.clone();
+        for bid in self.book.expiring(now) {
+            accounts.release(bid.bidder, bid.amount)?;
+        }
+        self.accounts = accounts;
+        let expired = self.book.expire(now);
+        for bid in &expired {
+            self.record(MarketEvent::BidExpired {
+                id: bid.id(),
+                bidder: bid.bidder,
+                refunded: bid.amount,
+            });
+        }
+        Ok(expired)
+    }
+
+    /// Amends `bidder`'s bid, see [`BidBook::amend_bid`], escrowing an
+    /// increase or refunding a decrease of its amount. Returns the id the bid
+    /// is queued under afterwards. The new amount and discount must follow
+    /// the market's [`BidRules`].
+    pub fn amend_bid(
+        &mut self,
+        bidder: AccountId,
+        id: BidId,
+        amount: Balance,
+        discount: Discount,
+    ) -> Result<BidId, BidError> {
+        self.rules.check(amount, discount)?;
+        let bid = self.book.get(id).ok_or(BidError::NotFound { id })?;
+        if bid.bidder != bidder {
+            return Err(BidError::NotOwner { id, bidder });
+        }
+        let old_amount = bid.amount;
+        if amount > old_amount {
+            self.accounts.escrow(bidder, amount - old_amount)?;
+        }
+        let requeue_id = self.next_id();
+        let new_id = match self
+            .book
+            .amend_bid(bidder, id, amount, discount, requeue_id)
+        {
+            Ok(new_id) => new_id,
+            Err(err) => {
+                // the book is unchanged, so neither is the escrow
+                if amount > old_amount {
+                    self.accounts
+                        .release(bidder, amount - old_amount)
+                        .expect("just escrowed");
+                }
+                return Err(err);
+            }
+        };
+        if amount < old_amount {
+            self.accounts.release(bidder, old_amount - amount)?;
+        }
+        if new_id == requeue_id {
+            self.take_id();
+        }
+        self.record(MarketEvent::BidAmended {
+            id,
+            new_id,
+            bidder,
+            amount,
+            discount,
+        });
+        Ok(new_id)
+    }
+
+    /// Runs the liquidation against the book with the [`DiscountQueue`] and
+    /// settles every fill between the bidders and
+    /// `liquidation.account_liquidated`.
+    ///
+    /// The settlement is checked before the book is touched, so if the
+    /// liquidated account cannot deliver the collateral nothing changes.
+    /// The liquidation is not queued, so it is left out of
+    /// [`Market::metrics`].
+    pub fn liquidate(
+        &mut self,
+        liquidation: &mut SystemLiquidation,
+        price: Price,
+    ) -> Result<LiquidationReport, SettlementError> {
+        self.liquidate_with(&DiscountQueue::default(), liquidation, 0, price)
+    }
+
+    /// Like [`Market::liquidate`], with the fills worked out by `mechanism`
+    /// for a liquidation queued `age` blocks ago.
+    ///
+    /// Every fill is recorded as an event, followed by the settlement of the
+    /// liquidation unless there was nothing to fill, or by its failure.
+    pub fn liquidate_with<M: LiquidationMechanism + ?Sized>(
+        &mut self,
+        mechanism: &M,
+        liquidation: &mut SystemLiquidation,
+        age: BlockNumber,
+        price: Price,
+    ) -> Result<LiquidationReport, SettlementError> {
+        let result = self.settle_with(mechanism, liquidation, age, price);
+        match &result {
+            Ok(report) => self.record_report(report),
+            Err(err) => self.record(MarketEvent::LiquidationFailed {
+                id: liquidation.id,
+                reason: err.to_string(),
+            }),
+        }
+        result
+    }
+
+    fn settle_with<M: LiquidationMechanism + ?Sized>(
+        &mut self,
+        mechanism: &M,
+        liquidation: &mut SystemLiquidation,
+        age: BlockNumber,
+        price: Price,
+    ) -> Result<LiquidationReport, SettlementError> {
+        let preview =
+            mechanism.preview(&self.book.bids, liquidation, age, price, &self.rules.dust)?;
+        self.accounts
+            .check_settlement(&preview, liquidation.account_liquidated)?;
+        let report = self.book.apply(liquidation, preview)?;
+        self.accounts
+            .settle(&report, liquidation.account_liquidated)
+            .expect("settlement checked against the preview");
+        Ok(report)
+    }
+
+    fn record_report(&mut self, report: &LiquidationReport) {
+        if report.fills.is_empty() && report.status != LiquidationStatus::Fulfilled {
+            return;
+        }
+        let liquidation = report.liquidation_id;
+        for fill in &report.fills {
+            let event = if fill.partial {
+                MarketEvent::BidPartiallyFilled {
+                    id: fill.bid_id(),
+                    bidder: fill.bidder,
+                    liquidation,
+                    discount: fill.discount,
+                    spent: fill.bid_asset_spent,
+                    collateral: fill.collateral_received,
+                    remaining: fill.remaining_amount,
+                }
+            } else {
+                MarketEvent::BidFilled {
+                    id: fill.bid_id(),
+                    bidder: fill.bidder,
+                    liquidation,
+                    discount: fill.discount,
+                    spent: fill.bid_asset_spent,
+                    collateral: fill.collateral_received,
+                    dust: fill.dust,
+                }
+            };
+            self.record(event);
+        }
+        self.record(MarketEvent::LiquidationSettled {
+            id: liquidation,
+            filled: report.total_filled,
+            collateral: report.total_collateral,
+            remaining: report.remaining_amount,
+            status: report.status.clone(),
+        });
+    }
+
+    /// Queues a liquidation of `amount` against `account` at the current
+    /// block, see [`Market::process_liquidations`].
+    pub fn queue_liquidation(&mut self, account: AccountId, amount: Balance) -> LiquidationId {
+        self.metrics.record_queued(amount);
+        let id = self.liquidations.push(account, amount, self.clock.now());
+        self.record(MarketEvent::LiquidationCreated {
+            id,
+            account,
+            amount,
+        });
+        id
+    }
+
+    /// Cancels a pending liquidation, see [`LiquidationQueue::cancel`].
+    /// Returns `false` if there is no pending liquidation with this id.
+    pub fn cancel_liquidation(&mut self, id: LiquidationId) -> bool {
+        let cancelled = self.liquidations.cancel(id);
+        if cancelled {
+            self.record(MarketEvent::LiquidationCancelled { id });
+        }
+        cancelled
+    }
+
+    /// Runs the pending liquidations against the book with the
+    /// [`DiscountQueue`], see [`Market::process_liquidations_with`].
+    pub fn process_liquidations(
+        &mut self,
+        price: Price,
+    ) -> Vec<(LiquidationId, Result<LiquidationReport, SettlementError>)> {
+        self.process_liquidations_with(&DiscountQueue::default(), price)
+    }
+
+    /// Runs the pending liquidations against the book with `mechanism`,
+    /// oldest first, until they are all filled or the book is empty.
+    ///
+    /// A liquidation that fails to settle stays pending and the queue moves
+    /// on to the next one. The fills are recorded in [`Market::metrics`].
+    /// Returns the outcome of every liquidation run, leaving out those the
+    /// mechanism found nothing to fill for.
+    pub fn process_liquidations_with<M: LiquidationMechanism + ?Sized>(
+        &mut self,
+        mechanism: &M,
+        price: Price,
+    ) -> Vec<(LiquidationId, Result<LiquidationReport, SettlementError>)> {
+        let mut results = Vec::new();
+        for id in self.liquidations.pending() {
+            if self.book.bids.is_empty() {
+                break;
+            }
+            let queued = self
+                .liquidations
+                .get(id)
+                .expect("pending liquidation is queued");
+            let age = self.clock.now().saturating_sub(queued.queued_at);
+            let mut liquidation = queued.liquidation.clone();
+            let result = self.liquidate_with(mechanism, &mut liquidation, age, price);
+            match &result {
+                Ok(report)
+                    if report.fills.is_empty() && report.status != LiquidationStatus::Fulfilled =>
+                {
+                    continue
+                }
+                Ok(report) => {
+                    self.metrics.record(report);
+                    self.liquidations.update(liquidation, report);
+                }
+                Err(_) => {}
+            }
+            results.push((id, result));
+        }
+        results
+    }
+
+    /// The id the next bid placed or re-queued gets.
+    pub fn next_id(&self) -> BidId {
+        BidId::new(self.clock.now(), self.clock.transactions())
+    }
+
+    fn take_id(&mut self) -> BidId {
+        BidId::new(self.clock.now(), self.clock.next_transaction())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{AccountError, Discount, DustPolicy, DustRule, LiquidationStatus, SimRng};
+
+    const ALICE: AccountId = 1;
+    const BOB: AccountId = 2;
+    const DAVE: AccountId = 4;
+
+    fn market() -> Market {
+        let mut market = Market::new();
+        market
+            .accounts
+            .mint(ALICE, Balance::new(10_000), Balance::ZERO)
+            .unwrap();
+        market
+            .accounts
+            .mint(BOB, Balance::new(10_000), Balance::ZERO)
+            .unwrap();
+        market
+            .accounts
+            .mint(DAVE, Balance::ZERO, Balance::new(100_000))
+            .unwrap();
+        market
+    }
+
+    fn liquidation(amount: Balance) -> SystemLiquidation {
+        SystemLiquidation {
+            id: 1,
+            account_liquidated: DAVE,
+            amount,
+            status: LiquidationStatus::Created,
+        }
+    }
+
+    // every bidder's escrow must equal what its open bids still offer
+    fn assert_escrow_matches_book(market: &Market) {
+        let mut open: BTreeMap<AccountId, Balance> = BTreeMap::new();
+        for bid in &market.book.bids {
+            *open.entry(bid.bidder).or_default() += bid.amount;
+        }
+        for (account, balance) in market.accounts.iter() {
+            assert_eq!(
+                balance.escrowed,
+                open.get(account).copied().unwrap_or_default(),
+                "escrow of account {}",
+                account
+            );
+        }
+    }
+
+    #[test]
+    fn test_place_bid_escrows() {
+        let mut market = market();
+
+        assert_eq!(
+            market.place_bid(ALICE, Balance::new(4_000), Discount::percent(10)),
+            Ok(BidId::new(0, 0))
+        );
+        assert_eq!(
+            market.place_bid(ALICE, Balance::new(1_000), Discount::percent(5)),
+            Ok(BidId::new(0, 1))
+        );
+
+        let alice = market.accounts.balance(ALICE);
+        assert_eq!(alice.bid_asset, Balance::new(5_000));
+        assert_eq!(alice.escrowed, Balance::new(5_000));
+        assert_escrow_matches_book(&market);
+    }
+
+    #[test]
+    fn test_place_bid_requires_balance() {
+        let mut market = market();
+
+        assert_eq!(
+            market.place_bid(ALICE, Balance::new(20_000), Discount::percent(10)),
+            Err(BidError::Account(AccountError::InsufficientFunds {
+                account: ALICE,
+                needed: Balance::new(20_000),
+                available: Balance::new(10_000),
+            }))
+        );
+        assert!(market.book.bids.is_empty());
+        assert_eq!(market.next_id(), BidId::new(0, 0));
+    }
+
+    #[test]
+    fn test_place_bid_follows_the_rules() {
+        let mut market = market();
+        market.rules = BidRules {
+            min_amount: Balance::new(100),
+            tick_size: Balance::new(10),
+            min_discount: Discount::percent(1),
+            max_discount: Discount::percent(20),
+            discount_step: Discount::from_bps(50),
+            ..Default::default()
+        };
+
+        for (amount, bps) in [
+            (0, 500),
+            (50, 500),
+            (105, 500),
+            (100, 0),
+            (100, 2_050),
+            (100, 125),
+        ] {
+            let (amount, discount) = (Balance::new(amount), Discount::from_bps(bps));
+            let err = market.check_bid(ALICE, amount, discount).unwrap_err();
+            assert_eq!(market.place_bid(ALICE, amount, discount), Err(err));
+        }
+        assert_eq!(
+            market.check_bid(ALICE, Balance::new(20_000), Discount::percent(5)),
+            Err(BidError::Account(AccountError::InsufficientFunds {
+                account: ALICE,
+                needed: Balance::new(20_000),
+                available: Balance::new(10_000),
+            }))
+        );
+        assert!(market.book.bids.is_empty());
+        assert_eq!(market.accounts.balance(ALICE).escrowed, Balance::ZERO);
+        assert_eq!(market.next_id(), BidId::new(0, 0));
+
+        assert_eq!(
+            market.check_bid(ALICE, Balance::new(110), Discount::percent(20)),
+            Ok(())
+        );
+        let id = market
+            .place_bid(ALICE, Balance::new(110), Discount::percent(20))
+            .unwrap();
+        assert_eq!(
+            market.amend_bid(ALICE, id, Balance::new(115), Discount::percent(20)),
+            Err(BidError::OffTick {
+                amount: Balance::new(115),
+                tick: Balance::new(10)
+            })
+        );
+        assert_eq!(market.book.get(id).unwrap().amount, Balance::new(110));
+        assert_escrow_matches_book(&market);
+    }
+
+    #[test]
+    fn test_cancel_bid_refunds() {
+        let mut market = market();
+        let index = market
+            .place_bid(ALICE, Balance::new(4_000), Discount::percent(10))
+            .unwrap();
+
+        assert_eq!(market.cancel_bid(ALICE, index), Ok(Balance::new(4_000)));
+
+        assert_eq!(
+            market.accounts.balance(ALICE),
+            crate::AccountBalance {
+                bid_asset: Balance::new(10_000),
+                escrowed: Balance::ZERO,
+                collateral: Balance::ZERO,
+            }
+        );
+    }
+
+    #[test]
+    fn test_amend_bid_adjusts_escrow() {
+        let mut market = market();
+        let index = market
+            .place_bid(ALICE, Balance::new(4_000), Discount::percent(10))
+            .unwrap();
+
+        let index = market
+            .amend_bid(ALICE, index, Balance::new(6_000), Discount::percent(10))
+            .unwrap();
+        assert_eq!(market.accounts.balance(ALICE).escrowed, Balance::new(6_000));
+
+        market
+            .amend_bid(ALICE, index, Balance::new(1_000), Discount::percent(10))
+            .unwrap();
+        assert_eq!(market.accounts.balance(ALICE).escrowed, Balance::new(1_000));
+        assert_eq!(
+            market.accounts.balance(ALICE).bid_asset,
+            Balance::new(9_000)
+        );
+
+        assert!(matches!(
+            market.amend_bid(ALICE, index, Balance::new(50_000), Discount::percent(10)),
+            Err(BidError::Account(_))
+        ));
+        assert_eq!(market.accounts.balance(ALICE).escrowed, Balance::new(1_000));
+        assert_escrow_matches_book(&market);
+
+        // the book refuses the amendment: the next id is taken by a bid
+        // that bypassed the market
+        market
+            .book
+            .insert(crate::create_bid(
+                BOB,
+                Balance::new(100),
+                Discount::percent(10),
+                market.next_id().block,
+                market.next_id().index,
+            ))
+            .unwrap();
+        assert_eq!(
+            market.amend_bid(ALICE, index, Balance::new(3_000), Discount::percent(10)),
+            Err(BidError::DuplicateId {
+                id: market.next_id()
+            })
+        );
+        assert_eq!(market.accounts.balance(ALICE).escrowed, Balance::new(1_000));
+    }
+
+    #[test]
+    fn test_expiry_without_escrow_changes_nothing() {
+        let mut market = market();
+        market
+            .place_bid_until(ALICE, Balance::new(1_000), Discount::percent(10), Some(2))
+            .unwrap();
+        // a bid that bypassed the market, so nothing is escrowed for it
+        let mut unescrowed = crate::create_bid(BOB, Balance::new(500), Discount::percent(5), 0, 1);
+        unescrowed.expires_at = Some(2);
+        market.book.insert(unescrowed).unwrap();
+        market.clock.advance(2);
+
+        assert_eq!(
+            market.expire_bids(),
+            Err(BidError::Account(AccountError::InsufficientFunds {
+                account: BOB,
+                needed: Balance::new(500),
+                available: Balance::ZERO,
+            }))
+        );
+        assert_eq!(market.book.bids.len(), 2);
+        assert_eq!(market.accounts.balance(ALICE).escrowed, Balance::new(1_000));
+        assert!(market.book.history.closed().is_empty());
+
+        market.book.cancel_bid(BOB, BidId::new(0, 1)).unwrap();
+        let expired = market.expire_bids().unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(market.accounts.balance(ALICE).escrowed, Balance::ZERO);
+        assert_escrow_matches_book(&market);
+    }
+
+    #[test]
+    fn test_liquidation_settles_balances() {
+        let mut market = market();
+        market
+            .place_bid(ALICE, Balance::new(900), Discount::percent(10))
+            .unwrap();
+        market
+            .place_bid(BOB, Balance::new(1_000), Discount::percent(20))
+            .unwrap();
+
+        let mut liquidation = liquidation(Balance::new(1_300));
+        let price = Price::from_ratio(2, 1).unwrap();
+        market.liquidate(&mut liquidation, price).unwrap();
+
+        let alice = market.accounts.balance(ALICE);
+        assert_eq!(alice.escrowed, Balance::ZERO);
+        assert_eq!(alice.collateral, Balance::new(500));
+
+        let bob = market.accounts.balance(BOB);
+        assert_eq!(bob.escrowed, Balance::new(600));
+        assert_eq!(bob.collateral, Balance::new(250));
+
+        let dave = market.accounts.balance(DAVE);
+        assert_eq!(dave.bid_asset, Balance::new(1_300));
+        assert_eq!(dave.collateral, Balance::new(100_000 - 750));
+        assert_escrow_matches_book(&market);
+    }
+
+    #[test]
+    fn test_consumed_dust_is_refunded() {
+        let mut market = market();
+        market.rules.dust = DustRule {
+            min_remaining: Balance::new(50),
+            policy: DustPolicy::Consume,
+        };
+        let id = market
+            .place_bid(ALICE, Balance::new(1_000), Discount::percent(10))
+            .unwrap();
+
+        market.queue_liquidation(DAVE, Balance::new(980));
+        let results = market.process_liquidations(Price::ONE);
+        let report = results[0].1.as_ref().unwrap();
+
+        assert_eq!(report.fills[0].dust, Balance::new(20));
+        assert!(market.book.bids.is_empty());
+        let alice = market.accounts.balance(ALICE);
+        assert_eq!(alice.escrowed, Balance::ZERO);
+        assert_eq!(alice.bid_asset, Balance::new(9_000 + 20));
+        let closed = &market.book.history.closed()[0];
+        assert_eq!(closed.bid.id(), id);
+        assert_eq!(closed.total_filled, Balance::new(980));
+        assert_eq!(market.metrics.dust_refunded, Balance::new(20));
+        assert_escrow_matches_book(&market);
+    }
+
+    #[test]
+    fn test_metrics_only_follow_the_queue() {
+        let mut market = market();
+        market
+            .place_bid(ALICE, Balance::new(2_000), Discount::percent(5))
+            .unwrap();
+
+        let mut direct = liquidation(Balance::new(500));
+        market.liquidate(&mut direct, Price::ONE).unwrap();
+        assert_eq!(market.metrics, LiquidationMetrics::default());
+        assert_eq!(market.metrics.fill_rate(), 1.0);
+
+        market.queue_liquidation(DAVE, Balance::new(1_000));
+        market.queue_liquidation(DAVE, Balance::new(1_000));
+        market.process_liquidations(Price::ONE);
+        let metrics = &market.metrics;
+        assert_eq!(metrics.requested, Balance::new(2_000));
+        assert_eq!(metrics.filled, Balance::new(1_500));
+        assert_eq!(metrics.fills, 2);
+        assert_eq!(metrics.fill_rate(), 0.75);
+        assert_eq!(metrics.unfilled(), Balance::new(500));
+    }
+
+    #[test]
+    fn test_events_follow_the_market() {
+        let mut market = market();
+        let first = market
+            .place_bid(ALICE, Balance::new(500), Discount::percent(5))
+            .unwrap();
+        let second = market
+            .place_bid(BOB, Balance::new(1_000), Discount::percent(10))
+            .unwrap();
+        market.clock.advance(1);
+        let id = market.queue_liquidation(DAVE, Balance::new(800));
+        market.process_liquidations(Price::ONE);
+        market.cancel_bid(BOB, second).unwrap();
+        assert!(!market.cancel_liquidation(id));
+
+        let kinds: Vec<_> = market
+            .events
+            .iter()
+            .map(|logged| (logged.block, logged.index, logged.event.kind()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (0, 0, "bid_placed"),
+                (0, 1, "bid_placed"),
+                (1, 0, "liquidation_created"),
+                (1, 1, "bid_filled"),
+                (1, 2, "bid_partially_filled"),
+                (1, 3, "liquidation_settled"),
+                (1, 4, "bid_cancelled"),
+            ]
+        );
+        assert!(matches!(
+            market.events.events()[3].event,
+            MarketEvent::BidFilled { id, .. } if id == first
+        ));
+        assert_eq!(
+            market.events.events()[4].event,
+            MarketEvent::BidPartiallyFilled {
+                id: second,
+                bidder: BOB,
+                liquidation: id,
+                discount: Discount::percent(10),
+                spent: Balance::new(300),
+                collateral: Balance::new(333),
+                remaining: Balance::new(700),
+            }
+        );
+        assert_eq!(
+            market.events.events()[6].event,
+            MarketEvent::BidCancelled {
+                id: second,
+                bidder: BOB,
+                refunded: Balance::new(700),
+            }
+        );
+    }
+
+    #[test]
+    fn test_failed_liquidations_are_recorded() {
+        let mut market = market();
+        market
+            .place_bid(ALICE, Balance::new(900), Discount::percent(10))
+            .unwrap();
+        let id = market.queue_liquidation(BOB, Balance::new(900));
+        market.process_liquidations(Price::ONE);
+
+        assert!(matches!(
+            market.events.iter().last().map(|logged| &logged.event),
+            Some(MarketEvent::LiquidationFailed { id: failed, .. }) if *failed == id
+        ));
+        assert!(market.cancel_liquidation(id));
+        assert_eq!(
+            market.events.iter().last().unwrap().event,
+            MarketEvent::LiquidationCancelled { id }
+        );
+    }
+
+    #[test]
+    fn test_import_bids() {
+        let mut market = market();
+        let csv = "\
+bidder,amount,discount,block,index,expires_at
+1,4000,5,7,0,
+2,1000,5,7,1,12
+1,4000,5,7,0,
+1,8000,6,8,0,
+3,500,50,8,1,
+3,500,5,8,2,8
+3,500,4,9,0,
+";
+        let import = crate::parse_bids_csv(csv, &crate::Columns::default()).unwrap();
+        assert!(import.errors.is_empty());
+
+        let errors = market.import_bids(&import.bids, false);
+        let lines: Vec<_> = errors.iter().map(|err| err.line).collect();
+        // a duplicate id, a bid beyond ALICE's balance, a discount out of
+        // range, an expiry at the bid's own block, and no funds for the
+        // unknown bidder
+        assert_eq!(lines, vec![4, 5, 6, 7, 8]);
+        assert_eq!(market.book.bids.len(), 2);
+        assert_eq!(
+            market.book.get(BidId::new(7, 1)).unwrap().expires_at,
+            Some(12)
+        );
+        assert_eq!(market.clock.now(), 8);
+        assert_eq!(market.next_id(), BidId::new(8, 0));
+        assert_escrow_matches_book(&market);
+
+        // minting covers the unknown bidder
+        let errors = market.import_bids(&import.bids[6..], true);
+        assert!(errors.is_empty());
+        assert_eq!(market.accounts.balance(3).escrowed, Balance::new(500));
+        assert_eq!(market.clock.now(), 10);
+        assert_escrow_matches_book(&market);
+    }
+
+    #[test]
+    fn test_liquidation_without_collateral_changes_nothing() {
+        let mut market = market();
+        market
+            .place_bid(ALICE, Balance::new(900), Discount::percent(10))
+            .unwrap();
+        let before = market.book.bids.clone();
+
+        let mut liquidation = liquidation(Balance::new(900));
+        liquidation.account_liquidated = BOB;
+
+        assert_eq!(
+            market.liquidate(&mut liquidation, Price::ONE),
+            Err(SettlementError::Account(AccountError::InsufficientFunds {
+                account: BOB,
+                needed: Balance::new(1_000),
+                available: Balance::ZERO,
+            }))
+        );
+        assert_eq!(market.book.bids, before);
+        assert_eq!(liquidation.amount, Balance::new(900));
+        assert_eq!(market.accounts.balance(ALICE).escrowed, Balance::new(900));
+    }
+
+    #[test]
+    fn test_bids_are_stamped_with_the_clock() {
+        let mut market = market();
+        let first = market
+            .place_bid(ALICE, Balance::new(100), Discount::percent(10))
+            .unwrap();
+        market
+            .place_bid(BOB, Balance::new(100), Discount::percent(20))
+            .unwrap();
+        market.clock.advance(3);
+        let second = market
+            .place_bid(ALICE, Balance::new(100), Discount::percent(10))
+            .unwrap();
+
+        assert_eq!(first, BidId::new(0, 0));
+        assert_eq!(second, BidId::new(3, 0));
+        assert_eq!(market.book.get(second).unwrap().blocknumber, 3);
+
+        // re-queuing takes the next transaction of the current block
+        let third = market
+            .place_bid(BOB, Balance::new(100), Discount::percent(10))
+            .unwrap();
+        assert_eq!(
+            market.amend_bid(ALICE, first, Balance::new(200), Discount::percent(10)),
+            Ok(BidId::new(3, 2))
+        );
+        let queue: Vec<_> = market.book.bids.iter().map(|bid| bid.id()).collect();
+        assert_eq!(
+            queue,
+            vec![second, third, BidId::new(3, 2), BidId::new(0, 1)]
+        );
+    }
+
+    #[test]
+    fn test_supply_conserved_across_liquidations() {
+        let mut rng = SimRng::new(2024);
+        let mut market = Market::new();
+        for bidder in 1..=10 {
+            market
+                .accounts
+                .mint(bidder, Balance::new(1_000_000), Balance::ZERO)
+                .unwrap();
+        }
+        market
+            .accounts
+            .mint(100, Balance::ZERO, Balance::new(u32::MAX as u128 / 2))
+            .unwrap();
+        let bid_asset = market.accounts.total_bid_asset();
+        let collateral = market.accounts.total_collateral();
+
+        for round in 0..50 {
+            for _ in 0..20 {
+                let bidder = rng.random_range(1..=10);
+                let amount = Balance::new(rng.random_range(1..=99) * 100);
+                let discount = Discount::percent(rng.random_range(1..=20));
+                let _ = market.place_bid(bidder, amount, discount);
+            }
+            if round % 3 == 0 {
+                if let Some(bid) = market.book.bids.iter().last().cloned() {
+                    market.cancel_bid(bid.bidder, bid.id()).unwrap();
+                }
+            }
+
+            let mut liquidation = SystemLiquidation {
+                id: round,
+                account_liquidated: 100,
+                amount: Balance::new(rng.random_range(1..=50_000)),
+                status: LiquidationStatus::Created,
+            };
+            let price = Price::from_ratio(rng.random_range(1..=500), 100).unwrap();
+            market.liquidate(&mut liquidation, price).unwrap();
+
+            assert_eq!(market.accounts.total_bid_asset(), bid_asset);
+            assert_eq!(market.accounts.total_collateral(), collateral);
+            assert_escrow_matches_book(&market);
+            market.clock.advance(1);
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_book() {
+        let run = |seed| {
+            let mut rng = SimRng::new(seed);
+            let mut market = market();
+            let generator = BidGenerator {
+                bidders: 2,
+                ..Default::default()
+            };
+            for _ in 0..10 {
+                market.generate_block(&mut rng, &generator).unwrap();
+            }
+            market.queue_liquidation(DAVE, Balance::new(5_000));
+            let reports = market.process_liquidations(Price::ONE);
+            (market.book.bids, reports)
+        };
+
+        assert_eq!(run(9), run(9));
+        assert_ne!(run(9).0, run(10).0);
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    preview_allocated, preview_auction, AllocationPolicy, BlockNumber, Discount, DustRule,
+    LiquidationReport, Price, SettlementError, SystemLiquidation, UserBid,
+};
+
+/// A way of matching a liquidation against the bid book.
+///
+/// A mechanism only works out the fills. The [`crate::Market`] checks they
+/// can be settled and applies them, so every mechanism produces the same
+/// [`LiquidationReport`] and feeds the same [`crate::LiquidationMetrics`].
+pub trait LiquidationMechanism {
+    /// Short name used in logs and reports.
+    fn name(&self) -> &'static str;
+
+    /// Works out the fills of `liquidation`, queued `age` blocks ago, against
+    /// `bids` at `price` without changing anything. Bids a fill would leave
+    /// as dust are handled according to the market's `dust` rule.
+    ///
+    /// Every fill must name an open bid and spend at most its amount; the
+    /// fills are applied in the order given.
+    fn preview(
+        &self,
+        bids: &BTreeSet<UserBid>,
+        liquidation: &SystemLiquidation,
+        age: BlockNumber,
+        price: Price,
+        dust: &DustRule,
+    ) -> Result<LiquidationReport, SettlementError>;
+}
+
+/// Fills the lowest-discount bids first, each at its own discount, as soon as
+/// the liquidation is processed. With the default [`AllocationPolicy::Fifo`]
+/// this is [`crate::liquidate`].
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscountQueue {
+    /// How the last discount tier reached is shared out.
+    pub allocation: AllocationPolicy,
+}
+
+impl LiquidationMechanism for DiscountQueue {
+    fn name(&self) -> &'static str {
+        "discount queue"
+    }
+
+    fn preview(
+        &self,
+        bids: &BTreeSet<UserBid>,
+        liquidation: &SystemLiquidation,
+        _age: BlockNumber,
+        price: Price,
+        dust: &DustRule,
+    ) -> Result<LiquidationReport, SettlementError> {
+        preview_allocated(bids, liquidation, price, &self.allocation, dust)
+    }
+}
+
+/// A Dutch auction on the collateral.
+///
+/// The discount offered starts at `start_discount` when the liquidation is
+/// queued and rises by `step` every `step_blocks` blocks, up to
+/// `max_discount`. Bids asking for at most the discount on offer take it,
+/// lowest discount first with the last tier shared out by `allocation`, and
+/// are all settled at the offered discount.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DutchAuction {
+    pub start_discount: Discount,
+    pub step: Discount,
+    pub step_blocks: BlockNumber,
+    pub max_discount: Discount,
+    pub allocation: AllocationPolicy,
+}
+
+impl Default for DutchAuction {
+    fn default() -> Self {
+        Self {
+            start_discount: Discount::ZERO,
+            step: Discount::percent(1),
+            step_blocks: 1,
+            max_discount: Discount::percent(20),
+            allocation: AllocationPolicy::Fifo,
+        }
+    }
+}
+
+impl DutchAuction {
+    /// The discount offered `age` blocks after the liquidation was queued.
+    pub fn discount_at(&self, age: BlockNumber) -> Discount {
+        let steps = age / self.step_blocks.max(1);
+        let bps = (self.start_discount.bps() as u64)
+            .saturating_add(steps.saturating_mul(self.step.bps() as u64));
+        Discount::from_bps(bps.min(self.max_discount.bps() as u64) as u16)
+    }
+}
+
+impl LiquidationMechanism for DutchAuction {
+    fn name(&self) -> &'static str {
+        "dutch auction"
+    }
+
+    fn preview(
+        &self,
+        bids: &BTreeSet<UserBid>,
+        liquidation: &SystemLiquidation,
+        age: BlockNumber,
+        price: Price,
+        dust: &DustRule,
+    ) -> Result<LiquidationReport, SettlementError> {
+        preview_auction(
+            bids,
+            liquidation,
+            price,
+            self.discount_at(age),
+            &self.allocation,
+            dust,
+        )
+    }
+}
+
+/// The mechanisms simulations and scenarios can be configured with.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Mechanism {
+    /// See [`DiscountQueue`].
+    DiscountQueue(DiscountQueue),
+    /// See [`DutchAuction`].
+    DutchAuction(DutchAuction),
+}
+
+impl Default for Mechanism {
+    fn default() -> Self {
+        Mechanism::DiscountQueue(DiscountQueue::default())
+    }
+}
+
+impl LiquidationMechanism for Mechanism {
+    fn name(&self) -> &'static str {
+        match self {
+            Mechanism::DiscountQueue(queue) => queue.name(),
+            Mechanism::DutchAuction(auction) => auction.name(),
+        }
+    }
+
+    fn preview(
+        &self,
+        bids: &BTreeSet<UserBid>,
+        liquidation: &SystemLiquidation,
+        age: BlockNumber,
+        price: Price,
+        dust: &DustRule,
+    ) -> Result<LiquidationReport, SettlementError> {
+        match self {
+            Mechanism::DiscountQueue(queue) => queue.preview(bids, liquidation, age, price, dust),
+            Mechanism::DutchAuction(auction) => {
+                auction.preview(bids, liquidation, age, price, dust)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AccountId, Balance, LiquidationStatus, Market, Simulation};
+
+    const ALICE: AccountId = 1;
+    const BOB: AccountId = 2;
+    const DAVE: AccountId = 4;
+
+    // ALICE bids 1000 at 5%, BOB 1000 at 10%, and DAVE owes 1500
+    fn simulation(mechanism: Mechanism) -> Simulation {
+        let mut market = Market::new();
+        market
+            .accounts
+            .mint(ALICE, Balance::new(10_000), Balance::ZERO)
+            .unwrap();
+        market
+            .accounts
+            .mint(BOB, Balance::new(10_000), Balance::ZERO)
+            .unwrap();
+        market
+            .accounts
+            .mint(DAVE, Balance::ZERO, Balance::new(100_000))
+            .unwrap();
+        market
+            .place_bid(ALICE, Balance::new(1_000), Discount::percent(5))
+            .unwrap();
+        market
+            .place_bid(BOB, Balance::new(1_000), Discount::percent(10))
+            .unwrap();
+        market.queue_liquidation(DAVE, Balance::new(1_500));
+        let mut sim = Simulation::new(market, 0);
+        sim.mechanism = mechanism;
+        sim
+    }
+
+    #[test]
+    fn test_auction_discount_rises() {
+        let auction = DutchAuction {
+            start_discount: Discount::percent(2),
+            step: Discount::percent(3),
+            step_blocks: 2,
+            max_discount: Discount::percent(10),
+            ..Default::default()
+        };
+        let discounts: Vec<_> = (0..8).map(|age| auction.discount_at(age)).collect();
+        let percents: Vec<_> = discounts.iter().map(|discount| discount.to_f64()).collect();
+        assert_eq!(percents, vec![2.0, 2.0, 5.0, 5.0, 8.0, 8.0, 10.0, 10.0]);
+
+        // half a percent every block
+        let auction = DutchAuction {
+            step: Discount::from_bps(50),
+            step_blocks: 1,
+            ..auction
+        };
+        assert_eq!(auction.discount_at(3), Discount::from_bps(350));
+    }
+
+    #[test]
+    fn test_discount_queue_fills_at_once() {
+        let mut sim = simulation(Mechanism::default());
+
+        let outcome = sim.step();
+
+        let report = outcome.liquidations[0].1.as_ref().unwrap();
+        let discounts: Vec<_> = report.fills.iter().map(|fill| fill.discount).collect();
+        assert_eq!(discounts, vec![Discount::percent(5), Discount::percent(10)]);
+        assert_eq!(report.status, LiquidationStatus::Fulfilled);
+    }
+
+    #[test]
+    fn test_dutch_auction_waits_for_takers() {
+        let mut sim = simulation(Mechanism::DutchAuction(DutchAuction {
+            start_discount: Discount::percent(8),
+            step: Discount::percent(1),
+            step_blocks: 1,
+            max_discount: Discount::percent(20),
+            allocation: AllocationPolicy::Fifo,
+        }));
+
+        let outcomes = sim.run(4);
+
+        // block 0 offers 8%: only ALICE takes it, and gets 8% rather than 5%
+        let report = outcomes[0].liquidations[0].1.as_ref().unwrap();
+        assert_eq!(report.fills.len(), 1);
+        assert_eq!(report.fills[0].bidder, ALICE);
+        assert_eq!(report.fills[0].discount, Discount::percent(8));
+        assert_eq!(report.status, LiquidationStatus::PartiallyFilled);
+        // block 1 offers 9%: nobody takes it and nothing is reported
+        assert!(outcomes[1].liquidations.is_empty());
+        // block 2 offers 10%: BOB fills the rest
+        let report = outcomes[2].liquidations[0].1.as_ref().unwrap();
+        assert_eq!(report.fills[0].bidder, BOB);
+        assert_eq!(report.fills[0].discount, Discount::percent(10));
+        assert_eq!(report.status, LiquidationStatus::Fulfilled);
+        assert!(outcomes[3].liquidations.is_empty());
+    }
+
+    #[test]
+    fn test_mechanisms_report_the_same_metrics() {
+        let mut queue = simulation(Mechanism::default());
+        let mut auction = simulation(Mechanism::DutchAuction(DutchAuction {
+            start_discount: Discount::percent(8),
+            ..Default::default()
+        }));
+        queue.run(5);
+        auction.run(5);
+
+        let queue = &queue.market.metrics;
+        let auction = &auction.market.metrics;
+        assert_eq!(queue.fill_rate(), 1.0);
+        assert_eq!(auction.fill_rate(), 1.0);
+        // (1000 * 5 + 500 * 10) / 1500 against (1000 * 8 + 500 * 10) / 1500
+        assert!((queue.average_discount() - 6.667).abs() < 0.001);
+        assert!((auction.average_discount() - 8.667).abs() < 0.001);
+        assert!(auction.collateral_sold > queue.collateral_sold);
+    }
+
+    #[test]
+    fn test_mechanism_from_json() {
+        let mechanism: Mechanism =
+            serde_json::from_str(r#"{ "kind": "dutch_auction", "start_discount": 3 }"#).unwrap();
+        assert_eq!(
+            mechanism,
+            Mechanism::DutchAuction(DutchAuction {
+                start_discount: Discount::percent(3),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            serde_json::from_str::<Mechanism>(r#"{ "kind": "discount_queue" }"#).unwrap(),
+            Mechanism::default()
+        );
+        assert_eq!(
+            serde_json::from_str::<Mechanism>(
+                r#"{ "kind": "discount_queue", "allocation": { "kind": "pro_rata" } }"#
+            )
+            .unwrap(),
+            Mechanism::DiscountQueue(DiscountQueue {
+                allocation: AllocationPolicy::ProRata
+            })
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Balance, Discount, LiquidationReport};
+
+/// Running totals describing how well liquidations get filled, whichever
+/// [`crate::LiquidationMechanism`] fills them. The totals saturate rather
+/// than overflow.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct LiquidationMetrics {
+    /// Total amount of the liquidations queued.
+    pub requested: Balance,
+    /// Bid asset raised by the fills.
+    pub filled: Balance,
+    /// Collateral sold by the fills.
+    pub collateral_sold: Balance,
+    /// Bid asset refunded to bidders as dust, see [`crate::DustPolicy`].
+    pub dust_refunded: Balance,
+    /// Number of fills.
+    pub fills: u64,
+    /// Sum of the discount of every fill, in basis points, weighted by the
+    /// bid asset it paid.
+    pub discount_volume: u128,
+    /// How many times each discount level was emptied by a liquidation.
+    pub emptied_discounts: BTreeMap<Discount, u64>,
+}
+
+impl LiquidationMetrics {
+    /// Records a liquidation of `amount` being queued.
+    pub fn record_queued(&mut self, amount: Balance) {
+        self.requested = self.requested.saturating_add(amount);
+    }
+
+    /// Records the fills of a liquidation run.
+    pub fn record(&mut self, report: &LiquidationReport) {
+        for fill in &report.fills {
+            self.filled = self.filled.saturating_add(fill.bid_asset_spent);
+            self.collateral_sold = self
+                .collateral_sold
+                .saturating_add(fill.collateral_received);
+            self.discount_volume = self.discount_volume.saturating_add(
+                (fill.discount.bps() as u128).saturating_mul(fill.bid_asset_spent.units()),
+            );
+            self.dust_refunded = self.dust_refunded.saturating_add(fill.dust);
+            self.fills += 1;
+        }
+        for discount in &report.emptied_discounts {
+            *self.emptied_discounts.entry(*discount).or_default() += 1;
+        }
+    }
+
+    /// Fraction of the amount queued that has been filled, between 0 and 1.
+    pub fn fill_rate(&self) -> f64 {
+        if self.requested.is_zero() {
+            1.0
+        } else {
+            self.filled.to_f64() / self.requested.to_f64()
+        }
+    }
+
+    /// Amount queued that has not been filled, cancelled liquidations
+    /// included.
+    pub fn unfilled(&self) -> Balance {
+        self.requested.saturating_sub(self.filled)
+    }
+
+    /// Average discount the fills were settled at, weighted by the bid asset
+    /// they paid, in percent.
+    pub fn average_discount(&self) -> f64 {
+        average_discount(self.discount_volume, self.filled)
+    }
+}
+
+// the average discount in percent of fills paying `paid` in total, from the
+// sum of their discounts in basis points weighted by what they paid; zero if
+// nothing was paid
+pub(crate) fn average_discount(discount_volume: u128, paid: Balance) -> f64 {
+    if paid.is_zero() {
+        0.0
+    } else {
+        discount_volume as f64 / paid.to_f64() / Discount::BPS_PER_PERCENT as f64
+    }
+}
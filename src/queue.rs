@@ -0,0 +1,276 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AccountId, Balance, BlockNumber, LiquidationId, LiquidationReport, LiquidationStatus,
+    SystemLiquidation,
+};
+
+/// A liquidation waiting in, or processed by, a [`LiquidationQueue`].
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct QueuedLiquidation {
+    /// The liquidation itself; its amount is what is still outstanding.
+    pub liquidation: SystemLiquidation,
+    /// The amount the liquidation was created with.
+    pub original_amount: Balance,
+    /// Total collateral sold so far.
+    pub collateral_sold: Balance,
+    /// The block the liquidation was queued in.
+    pub queued_at: BlockNumber,
+}
+
+impl QueuedLiquidation {
+    /// Bid asset raised so far.
+    pub fn filled(&self) -> Balance {
+        self.original_amount - self.liquidation.amount
+    }
+
+    /// Fraction of the liquidation filled so far, between 0 and 1.
+    pub fn progress(&self) -> f32 {
+        if self.original_amount.is_zero() {
+            1.0
+        } else {
+            (self.filled().to_f64() / self.original_amount.to_f64()) as f32
+        }
+    }
+
+    /// Whether the liquidation still waits for bids.
+    pub fn is_pending(&self) -> bool {
+        matches!(
+            self.liquidation.status,
+            LiquidationStatus::Created
+                | LiquidationStatus::PartiallyFilled
+                | LiquidationStatus::Untouched
+        )
+    }
+}
+
+/// First-in first-out queue of liquidations sharing one bid book.
+///
+/// Every liquidation ever queued is kept, so the queue doubles as a record of
+/// their outcome; only pending ones are processed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LiquidationQueue {
+    entries: VecDeque<QueuedLiquidation>,
+    next_id: LiquidationId,
+}
+
+impl LiquidationQueue {
+    /// Creates an empty queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a liquidation of `amount` against `account` in `block` and
+    /// returns its id.
+    pub fn push(
+        &mut self,
+        account: AccountId,
+        amount: Balance,
+        block: BlockNumber,
+    ) -> LiquidationId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push_back(QueuedLiquidation {
+            liquidation: SystemLiquidation {
+                id,
+                account_liquidated: account,
+                amount,
+                status: LiquidationStatus::Created,
+            },
+            original_amount: amount,
+            collateral_sold: Balance::ZERO,
+            queued_at: block,
+        });
+        id
+    }
+
+    /// The liquidation with this id.
+    pub fn get(&self, id: LiquidationId) -> Option<&QueuedLiquidation> {
+        self.entries.iter().find(|entry| entry.liquidation.id == id)
+    }
+
+    /// Every liquidation queued so far, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &QueuedLiquidation> {
+        self.entries.iter()
+    }
+
+    /// Ids of the liquidations still pending, in processing order.
+    pub fn pending(&self) -> Vec<LiquidationId> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.is_pending())
+            .map(|entry| entry.liquidation.id)
+            .collect()
+    }
+
+    /// Cancels a pending liquidation. Returns `false` if there is no pending
+    /// liquidation with this id.
+    pub fn cancel(&mut self, id: LiquidationId) -> bool {
+        match self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.liquidation.id == id && entry.is_pending())
+        {
+            Some(entry) => {
+                entry.liquidation.status = LiquidationStatus::Cancelled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Writes back the state of a liquidation after it has been run.
+    pub fn update(&mut self, liquidation: SystemLiquidation, report: &LiquidationReport) {
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.liquidation.id == liquidation.id)
+        {
+            entry.collateral_sold = entry
+                .collateral_sold
+                .saturating_add(report.total_collateral);
+            entry.liquidation = liquidation;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Discount, Market, Price};
+
+    const ALICE: AccountId = 1;
+    const BOB: AccountId = 2;
+    const DAVE: AccountId = 4;
+    const ERIN: AccountId = 5;
+
+    fn market() -> Market {
+        let mut market = Market::new();
+        market
+            .accounts
+            .mint(ALICE, Balance::new(10_000), Balance::ZERO)
+            .unwrap();
+        market
+            .accounts
+            .mint(DAVE, Balance::ZERO, Balance::new(100_000))
+            .unwrap();
+        market
+            .accounts
+            .mint(ERIN, Balance::ZERO, Balance::new(100_000))
+            .unwrap();
+        market
+    }
+
+    #[test]
+    fn test_queue_assigns_ids() {
+        let mut queue = LiquidationQueue::new();
+
+        assert_eq!(queue.push(DAVE, Balance::new(100), 0), 0);
+        assert_eq!(queue.push(ERIN, Balance::new(200), 0), 1);
+
+        assert_eq!(queue.pending(), vec![0, 1]);
+        assert_eq!(queue.get(1).unwrap().liquidation.account_liquidated, ERIN);
+        assert_eq!(queue.get(1).unwrap().progress(), 0.0);
+    }
+
+    #[test]
+    fn test_queue_is_processed_in_order() {
+        let mut market = market();
+        market
+            .place_bid(ALICE, Balance::new(1_000), Discount::percent(10))
+            .unwrap();
+        market
+            .place_bid(ALICE, Balance::new(1_000), Discount::percent(10))
+            .unwrap();
+        let first = market.queue_liquidation(DAVE, Balance::new(1_500));
+        let second = market.queue_liquidation(ERIN, Balance::new(1_000));
+
+        let results = market.process_liquidations(Price::ONE);
+
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+
+        let first = market.liquidations.get(first).unwrap();
+        assert_eq!(first.liquidation.status, LiquidationStatus::Fulfilled);
+        assert_eq!(first.filled(), Balance::new(1_500));
+        assert_eq!(first.progress(), 1.0);
+
+        let second = market.liquidations.get(second).unwrap();
+        assert_eq!(
+            second.liquidation.status,
+            LiquidationStatus::PartiallyFilled
+        );
+        assert_eq!(second.filled(), Balance::new(500));
+        assert_eq!(second.progress(), 0.5);
+        assert_eq!(market.liquidations.pending(), vec![1]);
+
+        assert_eq!(market.accounts.balance(DAVE).bid_asset, Balance::new(1_500));
+        assert_eq!(market.accounts.balance(ERIN).bid_asset, Balance::new(500));
+    }
+
+    #[test]
+    fn test_processing_stops_when_book_is_empty() {
+        let mut market = market();
+        market
+            .place_bid(ALICE, Balance::new(500), Discount::percent(10))
+            .unwrap();
+        let first = market.queue_liquidation(DAVE, Balance::new(1_000));
+        let second = market.queue_liquidation(ERIN, Balance::new(1_000));
+
+        let results = market.process_liquidations(Price::ONE);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, first);
+        assert_eq!(
+            market.liquidations.get(second).unwrap().liquidation.status,
+            LiquidationStatus::Created
+        );
+
+        // new bids let the queue resume where it stopped
+        market
+            .place_bid(ALICE, Balance::new(2_000), Discount::percent(10))
+            .unwrap();
+        market.process_liquidations(Price::ONE);
+        assert!(market.liquidations.pending().is_empty());
+    }
+
+    #[test]
+    fn test_failed_liquidation_does_not_block_queue() {
+        let mut market = market();
+        market
+            .place_bid(ALICE, Balance::new(1_000), Discount::percent(10))
+            .unwrap();
+        // BOB holds no collateral to be sold
+        let broke = market.queue_liquidation(BOB, Balance::new(500));
+        let next = market.queue_liquidation(DAVE, Balance::new(500));
+
+        let results = market.process_liquidations(Price::ONE);
+
+        assert!(results[0].1.is_err());
+        assert!(results[1].1.is_ok());
+        assert_eq!(market.liquidations.pending(), vec![broke]);
+        assert_eq!(
+            market.liquidations.get(next).unwrap().liquidation.status,
+            LiquidationStatus::Fulfilled
+        );
+    }
+
+    #[test]
+    fn test_cancel_liquidation() {
+        let mut market = market();
+        market
+            .place_bid(ALICE, Balance::new(1_000), Discount::percent(10))
+            .unwrap();
+        let id = market.queue_liquidation(DAVE, Balance::new(500));
+
+        assert!(market.liquidations.cancel(id));
+        assert!(!market.liquidations.cancel(id));
+        assert!(market.process_liquidations(Price::ONE).is_empty());
+        assert_eq!(
+            market.book.bids.iter().next().unwrap().amount,
+            Balance::new(1_000)
+        );
+    }
+}
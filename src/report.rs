@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AccountId, Balance, BidId, BlockIndex, BlockNumber, Discount, LiquidationId, LiquidationStatus,
+    UserBid,
+};
+
+/// A single bid filled, fully or partially, by a liquidation.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Fill {
+    /// The account that placed the bid.
+    pub bidder: AccountId,
+    /// The block the filled bid was placed in.
+    pub blocknumber: BlockNumber,
+    /// Index of the filled bid within its block.
+    pub index: BlockIndex,
+    /// Discount percentage the fill was settled at: the bid's own discount,
+    /// or the discount offered by a Dutch auction.
+    pub discount: Discount,
+    /// Bid asset paid by the bidder towards the liquidation.
+    pub bid_asset_spent: Balance,
+    /// Collateral transferred to the bidder, see [`crate::Price::collateral_for`].
+    pub collateral_received: Balance,
+    /// Whether the bid was left in the book with some amount remaining.
+    pub partial: bool,
+    /// Amount of the bid left in the book after this fill.
+    pub remaining_amount: Balance,
+    /// Dust refunded to the bidder as the fill consumed the whole bid, see
+    /// [`crate::DustPolicy::Consume`].
+    pub dust: Balance,
+    /// How much the fill was rounded down so that the bid keeps the minimum
+    /// remaining amount, see [`crate::DustPolicy::Round`].
+    pub rounded_off: Balance,
+}
+
+impl Fill {
+    /// The id of the filled bid.
+    pub fn bid_id(&self) -> BidId {
+        BidId::new(self.blocknumber, self.index)
+    }
+}
+
+/// Everything that happened during a call to [`crate::liquidate`].
+#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct LiquidationReport {
+    /// The liquidation that was run.
+    pub liquidation_id: LiquidationId,
+    /// Every bid touched, in fill order.
+    pub fills: Vec<Fill>,
+    /// Total bid asset paid towards the liquidation.
+    pub total_filled: Balance,
+    /// Total collateral transferred to the bidders.
+    pub total_collateral: Balance,
+    /// Total dust refunded to the bidders whose bids were consumed.
+    pub dust_refunded: Balance,
+    /// Amount of the liquidation still outstanding.
+    pub remaining_amount: Balance,
+    /// Status of the liquidation after the run.
+    pub status: LiquidationStatus,
+    /// Discount levels that had bids before the run and have none after it.
+    pub emptied_discounts: Vec<Discount>,
+    /// Bids fully filled by this run, removed from the book with the
+    /// [`crate::BidStatus::Fulfilled`] status.
+    pub closed: Vec<UserBid>,
+}
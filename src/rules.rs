@@ -0,0 +1,248 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Balance, BidError, Discount, DustRule};
+
+/// What a [`crate::Market`] accepts as a bid.
+///
+/// Every bid placed or amended is checked against the rules before anything
+/// is escrowed; bids inserted straight into a [`crate::BidBook`] are not.
+/// The discounts accepted, `min_discount` and every `discount_step` above it
+/// up to `max_discount`, are the market's tiers, see [`BidRules::tiers`].
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BidRules {
+    /// Smallest amount a bid can offer.
+    pub min_amount: Balance,
+    /// Amounts must be a multiple of the tick size.
+    pub tick_size: Balance,
+    /// Lowest discount a bid can ask for.
+    pub min_discount: Discount,
+    /// Highest discount a bid can ask for, below 100%.
+    pub max_discount: Discount,
+    /// Gap between two tiers.
+    pub discount_step: Discount,
+    /// What partial fills do with the bids they would leave as dust.
+    pub dust: DustRule,
+}
+
+impl Default for BidRules {
+    fn default() -> Self {
+        Self {
+            min_amount: Balance::new(1),
+            tick_size: Balance::new(1),
+            min_discount: Discount::percent(1),
+            max_discount: Discount::percent(20),
+            discount_step: Discount::percent(1),
+            dust: DustRule::default(),
+        }
+    }
+}
+
+/// Reasons a set of [`BidRules`] cannot be used.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum RulesError {
+    /// Amounts must be counted in ticks greater than zero.
+    ZeroTick,
+    /// The discount range is empty or reaches 100%.
+    DiscountRange { min: Discount, max: Discount },
+    /// Tiers must be a step greater than zero apart.
+    ZeroDiscountStep,
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::ZeroTick => write!(f, "tick size must be greater than zero"),
+            RulesError::ZeroDiscountStep => write!(f, "discount step must be greater than zero"),
+            RulesError::DiscountRange { min, max } => {
+                write!(
+                    f,
+                    "discounts from {}% to {}% are not a valid range",
+                    min, max
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+impl BidRules {
+    /// Checks that the rules can accept some bid.
+    pub fn validate(&self) -> Result<(), RulesError> {
+        if self.tick_size.is_zero() {
+            return Err(RulesError::ZeroTick);
+        }
+        if self.discount_step == Discount::ZERO {
+            return Err(RulesError::ZeroDiscountStep);
+        }
+        if self.min_discount > self.max_discount || self.max_discount >= Discount::FULL {
+            return Err(RulesError::DiscountRange {
+                min: self.min_discount,
+                max: self.max_discount,
+            });
+        }
+        Ok(())
+    }
+
+    /// Every discount a bid can ask for, lowest first.
+    pub fn tiers(&self) -> impl Iterator<Item = Discount> {
+        let (min, max) = (self.min_discount.bps(), self.max_discount.bps());
+        let step = self.discount_step.bps().max(1) as usize;
+        (min..=max).step_by(step).map(Discount::from_bps)
+    }
+
+    /// Checks a bid of `amount` at `discount` against the rules.
+    pub fn check(&self, amount: Balance, discount: Discount) -> Result<(), BidError> {
+        if amount.is_zero() {
+            return Err(BidError::ZeroAmount);
+        }
+        if amount < self.min_amount {
+            return Err(BidError::BelowMinimum {
+                amount,
+                min: self.min_amount,
+            });
+        }
+        if !amount.units().is_multiple_of(self.tick_size.units()) {
+            return Err(BidError::OffTick {
+                amount,
+                tick: self.tick_size,
+            });
+        }
+        if !(self.min_discount..=self.max_discount).contains(&discount) {
+            return Err(BidError::DiscountOutOfRange {
+                discount,
+                min: self.min_discount,
+                max: self.max_discount,
+            });
+        }
+        let step = self.discount_step.bps();
+        if step == 0 || !(discount.bps() - self.min_discount.bps()).is_multiple_of(step) {
+            return Err(BidError::OffStep {
+                discount,
+                step: self.discount_step,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_bids() {
+        let rules = BidRules {
+            min_amount: Balance::new(100),
+            tick_size: Balance::new(50),
+            min_discount: Discount::percent(2),
+            max_discount: Discount::percent(30),
+            discount_step: Discount::percent(1),
+            dust: DustRule::default(),
+        };
+        assert_eq!(rules.check(Balance::new(150), Discount::percent(2)), Ok(()));
+        assert_eq!(
+            rules.check(Balance::new(100), Discount::percent(30)),
+            Ok(())
+        );
+        assert_eq!(
+            rules.check(Balance::ZERO, Discount::percent(5)),
+            Err(BidError::ZeroAmount)
+        );
+        assert_eq!(
+            rules.check(Balance::new(50), Discount::percent(5)),
+            Err(BidError::BelowMinimum {
+                amount: Balance::new(50),
+                min: Balance::new(100)
+            })
+        );
+        assert_eq!(
+            rules.check(Balance::new(120), Discount::percent(5)),
+            Err(BidError::OffTick {
+                amount: Balance::new(120),
+                tick: Balance::new(50)
+            })
+        );
+        for percent in [0, 1, 31, 100] {
+            let discount = Discount::percent(percent);
+            assert_eq!(
+                rules.check(Balance::new(100), discount),
+                Err(BidError::DiscountOutOfRange {
+                    discount,
+                    min: Discount::percent(2),
+                    max: Discount::percent(30)
+                })
+            );
+        }
+        assert_eq!(
+            rules.check(Balance::new(100), Discount::from_bps(250)),
+            Err(BidError::OffStep {
+                discount: Discount::from_bps(250),
+                step: Discount::percent(1)
+            })
+        );
+    }
+
+    #[test]
+    fn test_half_percent_tiers() {
+        let rules = BidRules {
+            min_discount: Discount::from_bps(50),
+            max_discount: Discount::percent(30),
+            discount_step: Discount::from_bps(50),
+            ..Default::default()
+        };
+        assert_eq!(rules.validate(), Ok(()));
+
+        let tiers: Vec<_> = rules.tiers().collect();
+        assert_eq!(tiers.len(), 60);
+        assert_eq!(tiers[0], Discount::from_bps(50));
+        assert_eq!(tiers[4], Discount::from_bps(250));
+        assert_eq!(tiers.last(), Some(&Discount::percent(30)));
+        for tier in tiers {
+            assert_eq!(rules.check(Balance::new(10), tier), Ok(()));
+        }
+        assert_eq!(
+            rules.check(Balance::new(10), Discount::from_bps(275)),
+            Err(BidError::OffStep {
+                discount: Discount::from_bps(275),
+                step: Discount::from_bps(50)
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_rules() {
+        assert_eq!(BidRules::default().validate(), Ok(()));
+        assert_eq!(BidRules::default().tiers().count(), 20);
+        let zero_tick = BidRules {
+            tick_size: Balance::ZERO,
+            ..Default::default()
+        };
+        assert_eq!(zero_tick.validate(), Err(RulesError::ZeroTick));
+        let zero_step = BidRules {
+            discount_step: Discount::ZERO,
+            ..Default::default()
+        };
+        assert_eq!(zero_step.validate(), Err(RulesError::ZeroDiscountStep));
+        let reversed = BidRules {
+            min_discount: Discount::percent(10),
+            max_discount: Discount::percent(5),
+            ..Default::default()
+        };
+        assert_eq!(
+            reversed.validate(),
+            Err(RulesError::DiscountRange {
+                min: Discount::percent(10),
+                max: Discount::percent(5)
+            })
+        );
+        let free = BidRules {
+            max_discount: Discount::FULL,
+            ..Default::default()
+        };
+        assert!(free.validate().is_err());
+    }
+}
@@ -0,0 +1,787 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AccountBalance, AccountId, Assets, Balance, BidError, BidGenerator, BidId, BidRules,
+    BlockIndex, BlockNumber, ClosedBid, Discount, Event, Fill, LiquidationId, LiquidationMetrics,
+    LiquidationReport, LoggedEvent, Market, Mechanism, Price, QueuedLiquidation, SettlementError,
+    Simulation, UserBid,
+};
+
+/// A scripted run of the market: accounts to fund, bids to start from, a
+/// timeline of events at given blocks and a sequence of steps to apply.
+/// Random bids are drawn from `generator`, see [`BidGenerator`], with numbers
+/// seeded by `seed`, so running a scenario twice gives the same outcome. The
+/// queue is matched against the book with `mechanism`, see [`Mechanism`].
+///
+/// The timeline is scheduled on the scenario's [`Simulation`] and happens as
+/// `run_blocks` steps run the blocks it names, see [`Simulation::step`].
+/// Scenarios are written in JSON, e.g.
+///
+/// ```json
+/// {
+///   "accounts": [
+///     { "account": 1, "bid_asset": 10000 },
+///     { "account": 100, "collateral": 50000 }
+///   ],
+///   "bids": [{ "bidder": 1, "amount": 1000, "discount": 5 }],
+///   "price": "1.5",
+///   "seed": 42,
+///   "generator": {
+///     "amount": { "kind": "log_normal", "median": 800, "sigma": 0.5 },
+///     "bidders": 1,
+///     "arrival_rate": 0.5
+///   },
+///   "mechanism": { "kind": "dutch_auction", "start_discount": 2, "step": 1 },
+///   "assets": { "bid_asset": { "symbol": "USDC", "decimals": 2 } },
+///   "rules": { "min_amount": 100, "tick_size": 10, "max_discount": 30 },
+///   "arrivals": true,
+///   "timeline": [
+///     { "block": 20, "event": "liquidate", "account": 100, "amount": 2000 },
+///     { "block": 25, "event": "set_price", "price": "1.2" }
+///   ],
+///   "steps": [
+///     { "action": "place_bid", "bidder": 1, "amount": 500, "discount": 10, "expires_at": 20 },
+///     { "action": "random_bids", "count": 5 },
+///     { "action": "generate_blocks", "blocks": 10 },
+///     { "action": "cancel_bid", "bidder": 1, "block": 0, "index": 0 },
+///     { "action": "liquidate", "account": 100, "amount": 400 },
+///     { "action": "run_blocks", "blocks": 20 }
+///   ]
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Funds minted before anything else happens.
+    #[serde(default)]
+    pub accounts: Vec<Funding>,
+    /// Bids placed, in order, before the first step.
+    #[serde(default)]
+    pub bids: Vec<BidPlacement>,
+    /// Oracle price liquidations start settling at.
+    #[serde(default)]
+    pub price: Price,
+    /// Seed of the random number generator.
+    #[serde(default)]
+    pub seed: u64,
+    /// How random bids are drawn.
+    #[serde(default)]
+    pub generator: BidGenerator,
+    /// How liquidations are matched against the book.
+    #[serde(default)]
+    pub mechanism: Mechanism,
+    /// What bids the market accepts, see [`BidRules`].
+    #[serde(default)]
+    pub rules: BidRules,
+    /// Symbols and decimals of the assets. Amounts in the scenario are
+    /// always given in units, see [`Balance`].
+    #[serde(default)]
+    pub assets: Assets,
+    /// Whether random bids arrive every block run by `run_blocks` steps.
+    #[serde(default)]
+    pub arrivals: bool,
+    /// Events scheduled at given blocks, see [`Simulation::schedule`].
+    #[serde(default)]
+    pub timeline: Vec<TimedEvent>,
+    /// What happens during the run, in order.
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+/// An event of a scenario's timeline, e.g.
+/// `{ "block": 20, "event": "liquidate", "account": 100, "amount": 2000 }`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimedEvent {
+    /// The block the event happens in.
+    pub block: BlockNumber,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Assets minted to an account when a scenario starts.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Funding {
+    pub account: AccountId,
+    #[serde(default)]
+    pub bid_asset: Balance,
+    #[serde(default)]
+    pub collateral: Balance,
+}
+
+/// A bid placed when a scenario starts.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BidPlacement {
+    pub bidder: AccountId,
+    pub amount: Balance,
+    pub discount: Discount,
+    #[serde(default)]
+    pub expires_at: Option<BlockNumber>,
+}
+
+/// A single action of a scenario. Bids expiring at the current block are
+/// swept before every step, see [`Market::expire_bids`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Step {
+    /// Places a bid, see [`Market::place_bid_until`].
+    PlaceBid {
+        bidder: AccountId,
+        amount: Balance,
+        discount: Discount,
+        #[serde(default)]
+        expires_at: Option<BlockNumber>,
+    },
+    /// Cancels the bid placed as transaction `index` of `block`, see
+    /// [`Market::cancel_bid`].
+    CancelBid {
+        bidder: AccountId,
+        #[serde(default)]
+        block: BlockNumber,
+        index: BlockIndex,
+    },
+    /// Amends the bid placed as transaction `index` of `block`, see
+    /// [`Market::amend_bid`].
+    AmendBid {
+        bidder: AccountId,
+        #[serde(default)]
+        block: BlockNumber,
+        index: BlockIndex,
+        amount: Balance,
+        discount: Discount,
+    },
+    /// Places `count` random bids, see [`Market::place_random_bid`].
+    RandomBids { count: u32 },
+    /// Lets random bids arrive for a number of blocks, see
+    /// [`Market::generate_block`].
+    GenerateBlocks { blocks: BlockNumber },
+    /// Moves the market clock forward.
+    AdvanceBlocks { blocks: BlockNumber },
+    /// Changes the oracle price used by the following liquidations.
+    SetPrice { price: Price },
+    /// Queues a liquidation and processes the queue.
+    Liquidate { account: AccountId, amount: Balance },
+    /// Processes the liquidations still pending.
+    ProcessQueue,
+    /// Runs blocks of the simulation, applying the timeline and letting
+    /// random bids arrive if `arrivals` is set, see [`Simulation::run`].
+    RunBlocks { blocks: BlockNumber },
+}
+
+/// A fill made while running a scenario.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FillRecord {
+    /// The step that triggered the liquidation.
+    pub step: usize,
+    /// The liquidation the bid was filled by.
+    pub liquidation_id: LiquidationId,
+    #[serde(flatten)]
+    pub fill: Fill,
+}
+
+/// A step that could not be applied. The run carries on with the next step.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StepError {
+    pub step: usize,
+    pub message: String,
+}
+
+/// Everything a scenario run produced.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ScenarioOutcome {
+    /// Seed the run was made with.
+    pub seed: u64,
+    /// Block the market clock ended at.
+    pub block: BlockNumber,
+    /// Every fill, in the order they happened.
+    pub fills: Vec<FillRecord>,
+    /// The open bids at the end of the run, lowest discount first.
+    pub book: Vec<UserBid>,
+    /// Bids fulfilled, cancelled or expired during the run, in the order
+    /// they closed.
+    pub closed: Vec<ClosedBid>,
+    /// Every liquidation with its final status.
+    pub liquidations: Vec<QueuedLiquidation>,
+    /// Final balance of every account.
+    pub accounts: BTreeMap<AccountId, AccountBalance>,
+    /// How well the liquidations were filled.
+    pub metrics: LiquidationMetrics,
+    /// The assets of the run.
+    pub assets: Assets,
+    /// Steps that failed.
+    pub errors: Vec<StepError>,
+    /// Every event of the run, see [`crate::EventLog`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<LoggedEvent>,
+}
+
+/// Reasons a scenario cannot be loaded or started.
+#[derive(Debug)]
+pub enum ScenarioError {
+    /// The scenario file cannot be read.
+    Io(std::io::Error),
+    /// The scenario is not valid JSON or does not match the format.
+    Parse(serde_json::Error),
+    /// The accounts or initial bids cannot be set up.
+    Setup(String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(err) => write!(f, "cannot read scenario: {}", err),
+            ScenarioError::Parse(err) => write!(f, "invalid scenario: {}", err),
+            ScenarioError::Setup(err) => write!(f, "cannot set up scenario: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl Scenario {
+    /// Parses a scenario from JSON.
+    pub fn from_json(json: &str) -> Result<Self, ScenarioError> {
+        serde_json::from_str(json).map_err(ScenarioError::Parse)
+    }
+
+    /// Reads and parses a scenario file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        let json = std::fs::read_to_string(path).map_err(ScenarioError::Io)?;
+        Self::from_json(&json)
+    }
+
+    /// Sets up the simulation the scenario starts from: the accounts funded,
+    /// the initial bids placed, the settings applied and the timeline
+    /// scheduled, at block 0.
+    pub fn simulation(&self) -> Result<Simulation, ScenarioError> {
+        self.generator
+            .validate()
+            .map_err(|err| ScenarioError::Setup(err.to_string()))?;
+        self.rules
+            .validate()
+            .map_err(|err| ScenarioError::Setup(err.to_string()))?;
+        let mut market = Market::new();
+        market.assets = self.assets.clone();
+        market.rules = self.rules.clone();
+        for funding in &self.accounts {
+            market
+                .mint(funding.account, funding.bid_asset, funding.collateral)
+                .map_err(|err| ScenarioError::Setup(err.to_string()))?;
+        }
+        for bid in &self.bids {
+            market
+                .place_bid_until(bid.bidder, bid.amount, bid.discount, bid.expires_at)
+                .map_err(|err| ScenarioError::Setup(err.to_string()))?;
+        }
+
+        let mut sim = Simulation::new(market, self.seed);
+        sim.generator = self.generator.clone();
+        sim.mechanism = self.mechanism;
+        sim.price = self.price;
+        sim.arrivals = self.arrivals;
+        for timed in &self.timeline {
+            sim.schedule(timed.block, timed.event.clone());
+        }
+        Ok(sim)
+    }
+
+    /// Runs the scenario on a fresh [`Simulation`], see
+    /// [`Scenario::simulation`] and [`Scenario::apply_step`].
+    pub fn run(&self) -> Result<ScenarioOutcome, ScenarioError> {
+        let mut sim = self.simulation()?;
+        let mut outcome = ScenarioOutcome {
+            seed: self.seed,
+            ..Default::default()
+        };
+        for step in 0..self.steps.len() {
+            self.apply_step(step, &mut sim, &mut outcome);
+        }
+        outcome.record_end(&sim.market);
+        Ok(outcome)
+    }
+
+    /// Applies step number `step` to `sim`, after sweeping the bids expiring
+    /// at the current block, and records its fills and errors in `outcome`.
+    /// Steps past the last one do nothing.
+    pub fn apply_step(&self, step: usize, sim: &mut Simulation, outcome: &mut ScenarioOutcome) {
+        let Some(action) = self.steps.get(step) else {
+            return;
+        };
+        let market = &mut sim.market;
+        if let Err(err) = market.expire_bids() {
+            outcome.errors.push(StepError {
+                step,
+                message: format!("expiring bids: {}", err),
+            });
+        }
+        let result = match action {
+            Step::PlaceBid {
+                bidder,
+                amount,
+                discount,
+                expires_at,
+            } => market
+                .place_bid_until(*bidder, *amount, *discount, *expires_at)
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+            Step::CancelBid {
+                bidder,
+                block,
+                index,
+            } => market
+                .cancel_bid(*bidder, BidId::new(*block, *index))
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+            Step::AmendBid {
+                bidder,
+                block,
+                index,
+                amount,
+                discount,
+            } => market
+                .amend_bid(*bidder, BidId::new(*block, *index), *amount, *discount)
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+            Step::RandomBids { count } => {
+                let placed: Vec<_> = (0..*count)
+                    .map(|_| market.place_random_bid(&mut sim.rng, &sim.generator))
+                    .collect();
+                rejections(&placed)
+            }
+            Step::GenerateBlocks { blocks } => (0..*blocks)
+                .try_fold(Vec::new(), |mut placed, _| {
+                    placed.extend(market.generate_block(&mut sim.rng, &sim.generator)?);
+                    Ok(placed)
+                })
+                .map_err(|err: BidError| err.to_string())
+                .and_then(|placed| rejections(&placed)),
+            Step::AdvanceBlocks { blocks } => {
+                market.clock.advance(*blocks);
+                Ok(())
+            }
+            Step::SetPrice { price } => {
+                sim.set_price(*price);
+                Ok(())
+            }
+            Step::Liquidate { account, amount } => {
+                market.queue_liquidation(*account, *amount);
+                process(sim, step, outcome)
+            }
+            Step::ProcessQueue => process(sim, step, outcome),
+            Step::RunBlocks { blocks } => {
+                let mut first_error = None;
+                for block in sim.run(*blocks) {
+                    for error in block.errors {
+                        first_error.get_or_insert(format!("block {}: {}", block.block, error));
+                    }
+                    record_liquidations(block.liquidations, step, outcome, &mut first_error);
+                }
+                first_error.map_or(Ok(()), Err)
+            }
+        };
+        if let Err(message) = result {
+            outcome.errors.push(StepError { step, message });
+        }
+    }
+}
+
+impl ScenarioOutcome {
+    // records the state the market ended in
+    fn record_end(&mut self, market: &Market) {
+        self.block = market.clock.now();
+        self.book = market.book.bids.iter().cloned().collect();
+        self.closed = market.book.history.closed().to_vec();
+        self.liquidations = market.liquidations.iter().cloned().collect();
+        self.accounts = market
+            .accounts
+            .iter()
+            .map(|(account, balance)| (*account, *balance))
+            .collect();
+        self.metrics = market.metrics.clone();
+        self.assets = market.assets.clone();
+        self.events = market.events.events().to_vec();
+    }
+}
+
+// processes the queue and records the fills, failing with the first error
+fn process(sim: &mut Simulation, step: usize, outcome: &mut ScenarioOutcome) -> Result<(), String> {
+    let results = sim
+        .market
+        .process_liquidations_with(&sim.mechanism, sim.price);
+    let mut first_error = None;
+    record_liquidations(results, step, outcome, &mut first_error);
+    first_error.map_or(Ok(()), Err)
+}
+
+// records the fills of liquidations run during `step`, keeping the first
+// failure
+fn record_liquidations(
+    results: Vec<(LiquidationId, Result<LiquidationReport, SettlementError>)>,
+    step: usize,
+    outcome: &mut ScenarioOutcome,
+    first_error: &mut Option<String>,
+) {
+    for (id, result) in results {
+        match result {
+            Ok(report) => record(&report, step, outcome),
+            Err(err) => {
+                first_error.get_or_insert(format!("liquidation {}: {}", id, err));
+            }
+        }
+    }
+}
+
+// fails with the number of random bids that could not be placed
+fn rejections<T, E>(placed: &[Result<T, E>]) -> Result<(), String> {
+    match placed.iter().filter(|result| result.is_err()).count() {
+        0 => Ok(()),
+        rejected => Err(format!(
+            "{} of {} random bids rejected",
+            rejected,
+            placed.len()
+        )),
+    }
+}
+
+fn record(report: &LiquidationReport, step: usize, outcome: &mut ScenarioOutcome) {
+    outcome
+        .fills
+        .extend(report.fills.iter().map(|fill| FillRecord {
+            step,
+            liquidation_id: report.liquidation_id,
+            fill: fill.clone(),
+        }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Asset, LiquidationStatus};
+
+    const SCENARIO: &str = r#"{
+        "accounts": [
+            { "account": 1, "bid_asset": 10000 },
+            { "account": 2, "bid_asset": 10000 },
+            { "account": 100, "collateral": 50000 }
+        ],
+        "bids": [
+            { "bidder": 1, "amount": 1000, "discount": 5 },
+            { "bidder": 2, "amount": 2000, "discount": 10 }
+        ],
+        "price": "2",
+        "steps": [
+            { "action": "place_bid", "bidder": 1, "amount": 500, "discount": 8 },
+            { "action": "cancel_bid", "bidder": 2, "index": 0 },
+            { "action": "liquidate", "account": 100, "amount": 1200 },
+            { "action": "set_price", "price": "1" },
+            { "action": "amend_bid", "bidder": 2, "index": 1, "amount": 100, "discount": 10 },
+            { "action": "liquidate", "account": 100, "amount": 1000 }
+        ]
+    }"#;
+
+    #[test]
+    fn test_bids_expire_between_steps() {
+        let scenario = Scenario::from_json(
+            r#"{
+                "accounts": [{ "account": 1, "bid_asset": 10000 }],
+                "bids": [{ "bidder": 1, "amount": 1000, "discount": 5, "expires_at": 2 }],
+                "steps": [
+                    { "action": "place_bid", "bidder": 1, "amount": 500, "discount": 8, "expires_at": 5 },
+                    { "action": "advance_blocks", "blocks": 2 },
+                    { "action": "process_queue" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let outcome = scenario.run().unwrap();
+
+        assert_eq!(outcome.book.len(), 1);
+        assert_eq!(outcome.book[0].expires_at, Some(5));
+        assert_eq!(outcome.closed.len(), 1);
+        assert_eq!(outcome.closed[0].status(), &crate::BidStatus::Expired);
+        assert_eq!(outcome.accounts[&1].escrowed, Balance::new(500));
+
+        let events: Vec<_> = outcome
+            .events
+            .iter()
+            .map(|logged| (logged.block, logged.event.kind()))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (0, "account_funded"),
+                (0, "bid_placed"),
+                (0, "bid_placed"),
+                (2, "bid_expired")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_timeline_runs_with_the_blocks() {
+        let scenario = Scenario::from_json(
+            r#"{
+                "accounts": [
+                    { "account": 1, "bid_asset": 10000 },
+                    { "account": 100, "collateral": 10000 }
+                ],
+                "bids": [{ "bidder": 1, "amount": 1000, "discount": 5 }],
+                "timeline": [
+                    { "block": 4, "event": "liquidate", "account": 100, "amount": 300 },
+                    { "block": 1, "event": "cancel_bid", "bidder": 2, "id": { "block": 0, "index": 0 } },
+                    { "block": 2, "event": "liquidate", "account": 100, "amount": 500 },
+                    { "block": 3, "event": "set_price", "price": "2" },
+                    { "block": 3, "event": "place_bid", "bidder": 1, "amount": 400, "discount": 1 }
+                ],
+                "steps": [
+                    { "action": "run_blocks", "blocks": 3 },
+                    { "action": "run_blocks", "blocks": 2 }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let outcome = scenario.run().unwrap();
+
+        assert_eq!(outcome.block, 5);
+        assert_eq!(outcome.errors.len(), 1);
+        assert_eq!(outcome.errors[0].step, 0);
+        assert!(outcome.errors[0]
+            .message
+            .starts_with("block 1: cancelling bid 0:0"));
+        let fills: Vec<_> = outcome
+            .fills
+            .iter()
+            .map(|record| {
+                (
+                    record.step,
+                    record.liquidation_id,
+                    record.fill.blocknumber,
+                    record.fill.bid_asset_spent.units(),
+                )
+            })
+            .collect();
+        assert_eq!(fills, vec![(0, 0, 0, 500), (1, 1, 3, 300)]);
+        // 300 / (2 * 0.99)
+        assert_eq!(outcome.fills[1].fill.collateral_received, Balance::new(151));
+
+        // a scenario sets up the same simulation every time
+        let mut sim = scenario.simulation().unwrap();
+        assert_eq!(sim.scheduled().count(), 5);
+        let mut stepped = ScenarioOutcome::default();
+        scenario.apply_step(0, &mut sim, &mut stepped);
+        assert_eq!(sim.block(), 3);
+        assert_eq!(stepped.fills.len(), 1);
+        scenario.apply_step(1, &mut sim, &mut stepped);
+        scenario.apply_step(2, &mut sim, &mut stepped);
+        assert_eq!(sim.block(), 5);
+        assert_eq!(stepped.fills.len(), 2);
+    }
+
+    #[test]
+    fn test_run_scenario() {
+        let outcome = Scenario::from_json(SCENARIO).unwrap().run().unwrap();
+
+        // cancelling someone else's bid fails, the rest goes on
+        assert_eq!(outcome.errors.len(), 1);
+        assert_eq!(outcome.errors[0].step, 1);
+
+        let fills: Vec<_> = outcome
+            .fills
+            .iter()
+            .map(|record| {
+                (
+                    record.step,
+                    record.liquidation_id,
+                    record.fill.index,
+                    record.fill.bid_asset_spent.units(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            fills,
+            vec![
+                (2, 0, 0, 1000),
+                (2, 0, 2, 200),
+                (5, 1, 2, 300),
+                (5, 1, 1, 100)
+            ]
+        );
+        // 1000 / (2 * 0.95)
+        assert_eq!(outcome.fills[0].fill.collateral_received, Balance::new(526));
+
+        assert!(outcome.book.is_empty());
+        assert_eq!(outcome.closed.len(), 3);
+        assert_eq!(
+            outcome.liquidations[1].liquidation.status,
+            LiquidationStatus::PartiallyFilled
+        );
+        assert_eq!(outcome.liquidations[1].filled(), Balance::new(400));
+        assert_eq!(outcome.accounts[&100].bid_asset, Balance::new(1600));
+    }
+
+    #[test]
+    fn test_seeded_scenario_is_reproducible() {
+        let scenario = |seed| Scenario {
+            seed,
+            ..Scenario::from_json(
+                r#"{
+                    "accounts": [
+                        { "account": 1, "bid_asset": 1000000 },
+                        { "account": 2, "bid_asset": 1000000 },
+                        { "account": 100, "collateral": 1000000 }
+                    ],
+                    "generator": { "bidders": 2, "arrival_rate": 4 },
+                    "steps": [
+                        { "action": "random_bids", "count": 20 },
+                        { "action": "advance_blocks", "blocks": 5 },
+                        { "action": "random_bids", "count": 20 },
+                        { "action": "generate_blocks", "blocks": 3 },
+                        { "action": "liquidate", "account": 100, "amount": 30000 }
+                    ]
+                }"#,
+            )
+            .unwrap()
+        };
+        let json = |seed| serde_json::to_string(&scenario(seed).run().unwrap()).unwrap();
+
+        assert_eq!(json(3), json(3));
+        assert_ne!(json(3), json(4));
+
+        let outcome = scenario(3).run().unwrap();
+        assert!(outcome.errors.is_empty());
+        assert_eq!(outcome.seed, 3);
+        assert_eq!(outcome.block, 8);
+        assert!(outcome.book.iter().any(|bid| bid.blocknumber == 5));
+        assert!(outcome.book.iter().all(|bid| bid.bidder <= 2));
+    }
+
+    #[test]
+    fn test_same_scenario_under_both_mechanisms() {
+        let queue = Scenario::from_json(
+            r#"{
+                "accounts": [
+                    { "account": 1, "bid_asset": 10000 },
+                    { "account": 2, "bid_asset": 10000 },
+                    { "account": 100, "collateral": 10000 }
+                ],
+                "bids": [
+                    { "bidder": 1, "amount": 1000, "discount": 4 },
+                    { "bidder": 2, "amount": 1000, "discount": 6 }
+                ],
+                "steps": [
+                    { "action": "liquidate", "account": 100, "amount": 1500 },
+                    { "action": "advance_blocks", "blocks": 5 },
+                    { "action": "process_queue" },
+                    { "action": "advance_blocks", "blocks": 5 },
+                    { "action": "process_queue" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let auction = Scenario {
+            mechanism: Mechanism::DutchAuction(crate::DutchAuction::default()),
+            ..queue.clone()
+        };
+
+        let queue = queue.run().unwrap();
+        let auction = auction.run().unwrap();
+
+        // the queue fills everything at once at the bids' discounts
+        let steps: Vec<_> = queue.fills.iter().map(|record| record.step).collect();
+        assert_eq!(steps, vec![0, 0]);
+        assert_eq!(queue.metrics.requested, Balance::new(1500));
+        assert_eq!(queue.metrics.fill_rate(), 1.0);
+        // the auction offers nothing at first, then 5% and 10%
+        let fills: Vec<_> = auction
+            .fills
+            .iter()
+            .map(|record| (record.step, record.fill.discount))
+            .collect();
+        assert_eq!(
+            fills,
+            vec![(2, Discount::percent(5)), (4, Discount::percent(10))]
+        );
+        assert_eq!(auction.metrics.requested, Balance::new(1500));
+        assert_eq!(auction.metrics.fill_rate(), 1.0);
+        assert!(auction.metrics.average_discount() > queue.metrics.average_discount());
+        assert_eq!(queue.metrics.fills, auction.metrics.fills);
+    }
+
+    #[test]
+    fn test_token_amounts() {
+        // 18 decimals, a million tokens each, well beyond u64 units
+        let scenario = Scenario::from_json(
+            r#"{
+                "assets": { "bid_asset": { "symbol": "DAI", "decimals": 18 } },
+                "accounts": [
+                    { "account": 1, "bid_asset": "1000000000000000000000000" },
+                    { "account": 100, "collateral": "1000000000000000000000000" }
+                ],
+                "bids": [{ "bidder": 1, "amount": "400000000000000000000000", "discount": 20 }],
+                "steps": [
+                    { "action": "liquidate", "account": 100, "amount": "100000000000000000000000" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let outcome = scenario.run().unwrap();
+
+        let dai = &outcome.assets.bid_asset;
+        assert_eq!(dai.symbol, "DAI");
+        assert_eq!(outcome.assets.collateral, Asset::new("COL", 0));
+        assert_eq!(
+            dai.format(outcome.fills[0].fill.bid_asset_spent),
+            "100000 DAI"
+        );
+        assert_eq!(
+            outcome.fills[0].fill.collateral_received,
+            Balance::new(125_000 * 10u128.pow(18))
+        );
+        let json = serde_json::to_string(&outcome).unwrap();
+        assert!(json.contains(r#""requested":"100000000000000000000000""#));
+    }
+
+    #[test]
+    fn test_invalid_scenarios() {
+        assert!(matches!(
+            Scenario::from_json(r#"{ "steps": [{ "action": "explode" }] }"#),
+            Err(ScenarioError::Parse(_))
+        ));
+        assert!(matches!(
+            Scenario::from_json(r#"{ "price": "-1" }"#),
+            Err(ScenarioError::Parse(_))
+        ));
+
+        let unfunded =
+            Scenario::from_json(r#"{ "bids": [{ "bidder": 1, "amount": 10, "discount": 1 }] }"#)
+                .unwrap();
+        assert!(matches!(unfunded.run(), Err(ScenarioError::Setup(_))));
+
+        let no_bidders = Scenario::from_json(r#"{ "generator": { "bidders": 0 } }"#).unwrap();
+        assert!(matches!(no_bidders.run(), Err(ScenarioError::Setup(_))));
+    }
+
+    #[test]
+    fn test_invalid_generator_fails_the_step() {
+        let scenario =
+            Scenario::from_json(r#"{ "steps": [{ "action": "generate_blocks", "blocks": 3 }] }"#)
+                .unwrap();
+        // a simulation set up elsewhere, e.g. restored from a snapshot
+        let mut sim = Simulation::new(Market::new(), 1);
+        sim.generator.arrival_rate = f64::NAN;
+        let mut outcome = ScenarioOutcome::default();
+
+        scenario.apply_step(0, &mut sim, &mut outcome);
+
+        assert_eq!(outcome.errors.len(), 1);
+        assert_eq!(outcome.errors[0].step, 0);
+        assert!(sim.market.book.bids.is_empty());
+    }
+}
@@ -0,0 +1,328 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{AccountError, Balance, BidId, Discount};
+
+/// Oracle price of one unit of collateral expressed in bid-asset units.
+///
+/// The price is a fixed-point number with [`Price::DECIMALS`] decimal places,
+/// stored as a raw `u128`. A price is never zero.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Price(u128);
+
+impl Price {
+    /// Number of decimal places of the fixed-point representation.
+    pub const DECIMALS: u32 = 12;
+    /// Raw value of a price of exactly one.
+    pub const SCALE: u128 = 10u128.pow(Self::DECIMALS);
+    /// A price of one bid-asset unit per collateral unit.
+    pub const ONE: Price = Price(Self::SCALE);
+
+    /// Creates a price from its raw fixed-point value, `None` if it is zero.
+    pub fn from_raw(raw: u128) -> Option<Self> {
+        (raw > 0).then_some(Price(raw))
+    }
+
+    /// Creates a price of `numerator / denominator`, rounded down to the
+    /// nearest raw unit. `None` if the result is zero or overflows.
+    pub fn from_ratio(numerator: u128, denominator: u128) -> Option<Self> {
+        if denominator == 0 {
+            return None;
+        }
+        Self::from_raw(numerator.checked_mul(Self::SCALE)? / denominator)
+    }
+
+    /// Creates a price from a float, rounded to the nearest raw unit. Meant for
+    /// user input; `None` if the value is not a positive finite number.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() || value <= 0.0 {
+            return None;
+        }
+        Self::from_raw((value * Self::SCALE as f64).round() as u128)
+    }
+
+    /// The raw fixed-point value.
+    pub fn raw(self) -> u128 {
+        self.0
+    }
+
+    /// The price as a float, for display.
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::SCALE as f64
+    }
+
+    /// Collateral bought by spending `spent` bid asset at this price less
+    /// `discount`, i.e. at `price * (1 - bps / 10_000)`.
+    ///
+    /// The result is computed exactly as
+    /// `floor(spent * SCALE * 10_000 / (raw * (10_000 - bps)))` with a single
+    /// division and a wide intermediate product, so there is no intermediate
+    /// rounding or overflow. Rounding down means the
+    /// bidder never receives more collateral than they paid for; the
+    /// sub-unit remainder stays with the liquidated account.
+    ///
+    /// Returns `None` if the discount is 100% or more (the collateral would be
+    /// free) or if the result does not fit in a [`Balance`].
+    pub fn collateral_for(self, spent: Balance, discount: Discount) -> Option<Balance> {
+        let full = Discount::FULL.bps() as u128;
+        let discounted_bps = full.checked_sub(discount.bps() as u128)?;
+        if discounted_bps == 0 {
+            return None;
+        }
+        let denominator = self.0.checked_mul(discounted_bps)?;
+        spent.mul_div(Self::SCALE * full, denominator)
+    }
+}
+
+impl Default for Price {
+    fn default() -> Self {
+        Price::ONE
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let integer = self.0 / Self::SCALE;
+        let fraction = self.0 % Self::SCALE;
+        let fraction = format!("{:0width$}", fraction, width = Self::DECIMALS as usize);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            write!(f, "{}", integer)
+        } else {
+            write!(f, "{}.{}", integer, fraction)
+        }
+    }
+}
+
+impl FromStr for Price {
+    type Err = ParsePriceError;
+
+    /// Parses a positive decimal such as `"1.25"` exactly. More than
+    /// [`Price::DECIMALS`] decimal places are rejected rather than rounded.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParsePriceError(s.to_string());
+        let (integer, fraction) = s.trim().split_once('.').unwrap_or((s.trim(), ""));
+        if integer.is_empty() && fraction.is_empty()
+            || fraction.len() > Self::DECIMALS as usize
+            || !integer
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let integer: u128 = if integer.is_empty() {
+            0
+        } else {
+            integer.parse().map_err(|_| invalid())?
+        };
+        let fraction: u128 = if fraction.is_empty() {
+            0
+        } else {
+            let padding = Self::DECIMALS - fraction.len() as u32;
+            fraction.parse::<u128>().map_err(|_| invalid())? * 10u128.pow(padding)
+        };
+        integer
+            .checked_mul(Self::SCALE)
+            .and_then(|raw| raw.checked_add(fraction))
+            .and_then(Self::from_raw)
+            .ok_or_else(invalid)
+    }
+}
+
+/// Prices are written as decimal strings so they round-trip exactly.
+impl Serialize for Price {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Price {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// The string is not a positive decimal price.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParsePriceError(String);
+
+impl fmt::Display for ParsePriceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid price {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParsePriceError {}
+
+/// Reasons a liquidation cannot be settled.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum SettlementError {
+    /// The bid's discount leaves no positive price to settle at.
+    InvalidDiscount { bid: BidId, discount: Discount },
+    /// The collateral owed to the bid does not fit in a [`Balance`].
+    CollateralOverflow { bid: BidId },
+    /// The bids at this discount add up to more than a [`Balance`] holds.
+    TierOverflow { discount: Discount },
+    /// The bidders or the liquidated account cannot cover the fills.
+    Account(AccountError),
+    /// A fill of a report worked out beforehand is for a bid that is no
+    /// longer in the book.
+    StalePreview { bid: BidId },
+}
+
+impl fmt::Display for SettlementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettlementError::InvalidDiscount { bid, discount } => {
+                write!(
+                    f,
+                    "bid {} has an unsettleable discount of {}%",
+                    bid, discount
+                )
+            }
+            SettlementError::CollateralOverflow { bid } => {
+                write!(f, "collateral owed to bid {} overflows", bid)
+            }
+            SettlementError::TierOverflow { discount } => {
+                write!(
+                    f,
+                    "bids at {}% add up to more than a balance holds",
+                    discount
+                )
+            }
+            SettlementError::Account(err) => err.fmt(f),
+            SettlementError::StalePreview { bid } => {
+                write!(
+                    f,
+                    "bid {} filled by the preview is no longer in the book",
+                    bid
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SettlementError {}
+
+impl From<AccountError> for SettlementError {
+    fn from(err: AccountError) -> Self {
+        SettlementError::Account(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collateral_at_par() {
+        assert_eq!(
+            Price::ONE.collateral_for(Balance::new(1000), Discount::percent(0)),
+            Some(Balance::new(1000))
+        );
+    }
+
+    #[test]
+    fn test_collateral_with_discount() {
+        // 900 bid asset at 10% off a price of 1 buys 1000 collateral.
+        assert_eq!(
+            Price::ONE.collateral_for(Balance::new(900), Discount::percent(10)),
+            Some(Balance::new(1000))
+        );
+        // 1000 bid asset at 20% off a price of 2.5 buys 1000 / 2 = 500.
+        let price = Price::from_ratio(5, 2).unwrap();
+        assert_eq!(
+            price.collateral_for(Balance::new(1000), Discount::percent(20)),
+            Some(Balance::new(500))
+        );
+    }
+
+    #[test]
+    fn test_collateral_rounds_down() {
+        // 1000 / 0.9 = 1111.11.. -> 1111
+        assert_eq!(
+            Price::ONE.collateral_for(Balance::new(1000), Discount::percent(10)),
+            Some(Balance::new(1111))
+        );
+        // 100 / 3 = 33.33.. -> 33
+        let price = Price::from_ratio(3, 1).unwrap();
+        assert_eq!(
+            price.collateral_for(Balance::new(100), Discount::percent(0)),
+            Some(Balance::new(33))
+        );
+        // 2 / 3 = 0.66.. -> 0
+        assert_eq!(
+            price.collateral_for(Balance::new(2), Discount::percent(0)),
+            Some(Balance::new(0))
+        );
+    }
+
+    #[test]
+    fn test_collateral_invalid_discount() {
+        assert_eq!(
+            Price::ONE.collateral_for(Balance::new(100), Discount::percent(100)),
+            None
+        );
+        assert_eq!(
+            Price::ONE.collateral_for(Balance::new(100), Discount::percent(150)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_collateral_with_token_decimals() {
+        // a billion tokens of 18 decimals, beyond a 128-bit `spent * SCALE`
+        let spent = Balance::new(10u128.pow(27));
+        let price = Price::from_ratio(5, 2).unwrap();
+        assert_eq!(
+            price.collateral_for(spent, Discount::percent(20)),
+            Some(Balance::new(5 * 10u128.pow(26)))
+        );
+    }
+
+    #[test]
+    fn test_collateral_overflow() {
+        let tiny = Price::from_raw(1).unwrap();
+        assert_eq!(
+            tiny.collateral_for(Balance::MAX, Discount::percent(0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_price_constructors() {
+        assert_eq!(Price::from_raw(0), None);
+        assert_eq!(Price::from_ratio(1, 0), None);
+        assert_eq!(Price::from_ratio(1, 3).unwrap().raw(), 333_333_333_333);
+        assert_eq!(Price::from_f64(1.5), Price::from_ratio(3, 2));
+        assert_eq!(Price::from_f64(-1.0), None);
+        assert_eq!(Price::from_ratio(3, 2).unwrap().to_string(), "1.5");
+        assert_eq!(Price::ONE.to_string(), "1");
+    }
+
+    #[test]
+    fn test_price_parsing() {
+        assert_eq!("1".parse(), Ok(Price::ONE));
+        assert_eq!(
+            "1.5".parse::<Price>().unwrap(),
+            Price::from_ratio(3, 2).unwrap()
+        );
+        assert_eq!(
+            ".25".parse::<Price>().unwrap(),
+            Price::from_ratio(1, 4).unwrap()
+        );
+        assert_eq!("0.000000000001".parse::<Price>().unwrap().raw(), 1);
+        assert!("0".parse::<Price>().is_err());
+        assert!("0.0000000000001".parse::<Price>().is_err());
+        assert!("-1".parse::<Price>().is_err());
+        assert!("1.2.3".parse::<Price>().is_err());
+        assert!(".".parse::<Price>().is_err());
+
+        let price: Price = "2.125".parse().unwrap();
+        assert_eq!(price.to_string().parse(), Ok(price));
+    }
+}
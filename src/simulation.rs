@@ -0,0 +1,372 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AccountId, Balance, BidError, BidGenerator, BidId, BlockNumber, Discount, LiquidationId,
+    LiquidationReport, Market, MarketEvent, Mechanism, Price, SettlementError, SimRng,
+};
+
+/// Something scheduled to happen at a given block of a [`Simulation`].
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Event {
+    /// A bid arrives, see [`Market::place_bid_until`].
+    PlaceBid {
+        bidder: AccountId,
+        amount: Balance,
+        discount: Discount,
+        #[serde(default)]
+        expires_at: Option<BlockNumber>,
+    },
+    /// `count` random bids arrive, see [`Market::place_random_bid`].
+    RandomBids { count: u32 },
+    /// A bid is cancelled, see [`Market::cancel_bid`].
+    CancelBid { bidder: AccountId, id: BidId },
+    /// A liquidation is queued, see [`Market::queue_liquidation`].
+    Liquidate { account: AccountId, amount: Balance },
+    /// The oracle price moves, see [`Simulation::set_price`].
+    SetPrice { price: Price },
+}
+
+/// What happened during one block of a [`Simulation`].
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct BlockOutcome {
+    /// The block that was run.
+    pub block: BlockNumber,
+    /// Ids of the bids that expired at the start of the block, see
+    /// [`Market::expire_bids`].
+    pub expired: Vec<BidId>,
+    /// Ids of the bids placed during the block, scheduled or random.
+    pub placed: Vec<BidId>,
+    /// Liquidations run at the end of the block, see
+    /// [`Market::process_liquidations_with`].
+    pub liquidations: Vec<(LiquidationId, Result<LiquidationReport, SettlementError>)>,
+    /// Expiries, events or random bids that could not be applied.
+    pub errors: Vec<String>,
+}
+
+/// Runs a [`Market`] block by block.
+///
+/// Every block first sweeps the bids expiring at it, then applies the events
+/// scheduled for it, in the order they were scheduled, then lets random bids
+/// arrive at the generator's arrival rate if `arrivals` is set, then
+/// processes the liquidation queue at the current price with the configured
+/// [`Mechanism`]. Bids placed during a block are stamped with it and indexed
+/// in the order they arrive, see [`crate::Clock`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Simulation {
+    /// The market being simulated.
+    pub market: Market,
+    /// Source of every random number of the run.
+    pub rng: SimRng,
+    /// How random bids are drawn.
+    pub generator: BidGenerator,
+    /// Whether random bids arrive every block.
+    pub arrivals: bool,
+    /// Oracle price the liquidation queue is processed at, see
+    /// [`Simulation::set_price`].
+    pub price: Price,
+    /// How the liquidation queue is matched against the book.
+    pub mechanism: Mechanism,
+    schedule: BTreeMap<BlockNumber, Vec<Event>>,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new(Market::new(), 0)
+    }
+}
+
+impl Simulation {
+    /// Creates a simulation of `market` drawing random numbers from `seed`,
+    /// without random arrivals.
+    pub fn new(market: Market, seed: u64) -> Self {
+        Self {
+            market,
+            rng: SimRng::new(seed),
+            generator: BidGenerator::default(),
+            arrivals: false,
+            price: Price::ONE,
+            mechanism: Mechanism::default(),
+            schedule: BTreeMap::new(),
+        }
+    }
+
+    /// The block the next step runs.
+    pub fn block(&self) -> BlockNumber {
+        self.market.clock.now()
+    }
+
+    /// Schedules `event` for `block`. Events scheduled for a block already
+    /// run happen during the next step.
+    pub fn schedule(&mut self, block: BlockNumber, event: Event) {
+        self.schedule.entry(block).or_default().push(event);
+    }
+
+    /// Every event still to happen, soonest first.
+    pub fn scheduled(&self) -> impl Iterator<Item = (BlockNumber, &Event)> {
+        self.schedule
+            .iter()
+            .flat_map(|(block, events)| events.iter().map(move |event| (*block, event)))
+    }
+
+    /// Moves the oracle price and records the change in the market's
+    /// events.
+    pub fn set_price(&mut self, price: Price) {
+        self.price = price;
+        self.market.record(MarketEvent::PriceChanged { price });
+    }
+
+    /// Runs the current block and moves the clock to the next one.
+    pub fn step(&mut self) -> BlockOutcome {
+        let mut outcome = BlockOutcome {
+            block: self.block(),
+            ..Default::default()
+        };
+
+        match self.market.expire_bids() {
+            Ok(expired) => outcome.expired = expired.iter().map(|bid| bid.id()).collect(),
+            Err(err) => outcome.errors.push(format!("expiring bids: {}", err)),
+        }
+
+        let later = self.schedule.split_off(&(outcome.block + 1));
+        let due = std::mem::replace(&mut self.schedule, later);
+        for event in due.into_values().flatten() {
+            self.apply(event, &mut outcome);
+        }
+
+        if self.arrivals {
+            match self.generator.arrivals(&mut self.rng) {
+                Ok(arrivals) => {
+                    for _ in 0..arrivals {
+                        let placed = self.market.place_random_bid(&mut self.rng, &self.generator);
+                        record_bid(placed, &mut outcome);
+                    }
+                }
+                Err(err) => outcome.errors.push(err.to_string()),
+            }
+        }
+
+        outcome.liquidations = self
+            .market
+            .process_liquidations_with(&self.mechanism, self.price);
+        self.market.clock.advance(1);
+        outcome
+    }
+
+    /// Runs `blocks` blocks and returns what happened in each.
+    pub fn run(&mut self, blocks: BlockNumber) -> Vec<BlockOutcome> {
+        (0..blocks).map(|_| self.step()).collect()
+    }
+
+    fn apply(&mut self, event: Event, outcome: &mut BlockOutcome) {
+        match event {
+            Event::PlaceBid {
+                bidder,
+                amount,
+                discount,
+                expires_at,
+            } => record_bid(
+                self.market
+                    .place_bid_until(bidder, amount, discount, expires_at),
+                outcome,
+            ),
+            Event::RandomBids { count } => {
+                for _ in 0..count {
+                    let placed = self.market.place_random_bid(&mut self.rng, &self.generator);
+                    record_bid(placed, outcome);
+                }
+            }
+            Event::CancelBid { bidder, id } => {
+                if let Err(err) = self.market.cancel_bid(bidder, id) {
+                    outcome
+                        .errors
+                        .push(format!("cancelling bid {}: {}", id, err));
+                }
+            }
+            Event::Liquidate { account, amount } => {
+                self.market.queue_liquidation(account, amount);
+            }
+            Event::SetPrice { price } => self.set_price(price),
+        }
+    }
+}
+
+fn record_bid(placed: Result<BidId, BidError>, outcome: &mut BlockOutcome) {
+    match placed {
+        Ok(id) => outcome.placed.push(id),
+        Err(err) => outcome.errors.push(format!("placing a bid: {}", err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LiquidationStatus;
+
+    const ALICE: AccountId = 1;
+    const DAVE: AccountId = 4;
+
+    fn simulation() -> Simulation {
+        let mut market = Market::new();
+        for bidder in 1..=10 {
+            market
+                .accounts
+                .mint(bidder, Balance::new(1_000_000), Balance::ZERO)
+                .unwrap();
+        }
+        market
+            .accounts
+            .mint(DAVE, Balance::ZERO, Balance::new(1_000_000))
+            .unwrap();
+        Simulation::new(market, 5)
+    }
+
+    #[test]
+    fn test_scheduled_events_run_at_their_block() {
+        let mut sim = simulation();
+        sim.schedule(
+            2,
+            Event::PlaceBid {
+                bidder: ALICE,
+                amount: Balance::new(1_000),
+                discount: Discount::percent(10),
+                expires_at: None,
+            },
+        );
+        sim.schedule(
+            2,
+            Event::PlaceBid {
+                bidder: ALICE,
+                amount: Balance::new(500),
+                discount: Discount::percent(5),
+                expires_at: None,
+            },
+        );
+        sim.schedule(
+            4,
+            Event::SetPrice {
+                price: Price::from_ratio(2, 1).unwrap(),
+            },
+        );
+        sim.schedule(
+            4,
+            Event::Liquidate {
+                account: DAVE,
+                amount: Balance::new(700),
+            },
+        );
+        assert_eq!(sim.scheduled().count(), 4);
+
+        let outcomes = sim.run(5);
+
+        assert!(outcomes[..2]
+            .iter()
+            .all(|outcome| outcome.placed.is_empty()));
+        assert_eq!(outcomes[2].placed, vec![BidId::new(2, 0), BidId::new(2, 1)]);
+        assert_eq!(outcomes[4].liquidations.len(), 1);
+        let report = outcomes[4].liquidations[0].1.as_ref().unwrap();
+        assert_eq!(report.status, LiquidationStatus::Fulfilled);
+        // 500 at 5% then 200 at 10%, both at a price of 2
+        assert_eq!(report.total_collateral, Balance::new(263 + 111));
+        assert_eq!(sim.block(), 5);
+        assert_eq!(sim.scheduled().count(), 0);
+    }
+
+    #[test]
+    fn test_missed_events_run_next_step() {
+        let mut sim = simulation();
+        sim.run(3);
+        sim.schedule(1, Event::RandomBids { count: 4 });
+
+        let outcome = sim.step();
+
+        assert_eq!(outcome.block, 3);
+        assert_eq!(outcome.placed.len(), 4);
+        assert!(outcome.placed.iter().all(|id| id.block == 3));
+    }
+
+    #[test]
+    fn test_arrivals_are_reproducible() {
+        let run = || {
+            let mut sim = simulation();
+            sim.arrivals = true;
+            sim.schedule(
+                50,
+                Event::Liquidate {
+                    account: DAVE,
+                    amount: Balance::new(100_000),
+                },
+            );
+            let outcomes = sim.run(100);
+            (sim.market.book.bids, outcomes)
+        };
+
+        let (book, outcomes) = run();
+        assert_eq!((book.clone(), outcomes.clone()), run());
+
+        // indices restart every block and follow the arrival order
+        for outcome in &outcomes {
+            let indices: Vec<_> = outcome.placed.iter().map(|id| id.index).collect();
+            assert_eq!(indices, (0..indices.len() as u64).collect::<Vec<_>>());
+            assert!(outcome.placed.iter().all(|id| id.block == outcome.block));
+        }
+        assert!(outcomes[50..]
+            .iter()
+            .any(|outcome| !outcome.liquidations.is_empty()));
+    }
+
+    #[test]
+    fn test_expired_bids_are_swept_and_refunded() {
+        let mut sim = simulation();
+        sim.schedule(
+            1,
+            Event::PlaceBid {
+                bidder: ALICE,
+                amount: Balance::new(1_000),
+                discount: Discount::percent(10),
+                expires_at: Some(3),
+            },
+        );
+        sim.schedule(
+            1,
+            Event::PlaceBid {
+                bidder: ALICE,
+                amount: Balance::new(1_000),
+                discount: Discount::percent(10),
+                expires_at: Some(1),
+            },
+        );
+
+        let outcomes = sim.run(4);
+
+        assert_eq!(outcomes[1].placed, vec![BidId::new(1, 0)]);
+        assert_eq!(outcomes[1].errors.len(), 1);
+        assert!(outcomes[2].expired.is_empty());
+        assert_eq!(outcomes[3].expired, vec![BidId::new(1, 0)]);
+        assert!(sim.market.book.bids.is_empty());
+        let alice = sim.market.accounts.balance(ALICE);
+        assert_eq!(alice.escrowed, Balance::ZERO);
+        assert_eq!(alice.bid_asset, Balance::new(1_000_000));
+        let closed = &sim.market.book.history.closed()[0];
+        assert_eq!(closed.status(), &crate::BidStatus::Expired);
+        assert_eq!(closed.total_filled, Balance::ZERO);
+    }
+
+    #[test]
+    fn test_unrefundable_expiries_are_errors() {
+        let mut sim = simulation();
+        let mut bid = crate::create_bid(99, Balance::new(1_000), Discount::percent(10), 0, 0);
+        bid.expires_at = Some(1);
+        sim.market.book.insert(bid).unwrap();
+
+        let outcomes = sim.run(2);
+
+        assert!(outcomes[0].errors.is_empty());
+        assert!(outcomes[1].expired.is_empty());
+        assert_eq!(outcomes[1].errors.len(), 1);
+        assert!(outcomes[1].errors[0].starts_with("expiring bids: account 99"));
+        assert_eq!(sim.market.book.bids.len(), 1);
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Simulation;
+
+/// Version of the snapshot format written by this build.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The whole state of a [`Simulation`]: the market with its book, history,
+/// accounts, liquidation queue, metrics and events, the random number
+/// generator with its position, the generator and mechanism settings, the
+/// price and the events still scheduled.
+///
+/// A simulation restored from a snapshot carries on exactly as the saved one
+/// would have. Snapshots are written as JSON with the format `version` first,
+/// e.g. `{"version": 1, "simulation": {...}}`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Snapshot {
+    /// Version of the format the snapshot was written with.
+    pub version: u32,
+    /// The saved simulation.
+    pub simulation: Simulation,
+}
+
+/// Reasons a snapshot cannot be saved or loaded.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The snapshot file cannot be read or written.
+    Io(std::io::Error),
+    /// The snapshot is not valid JSON or does not match its version's format.
+    Parse(serde_json::Error),
+    /// The snapshot was written in a format this build cannot read.
+    UnsupportedVersion { found: u32, supported: u32 },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "cannot access snapshot: {}", err),
+            SnapshotError::Parse(err) => write!(f, "invalid snapshot: {}", err),
+            SnapshotError::UnsupportedVersion { found, supported } => write!(
+                f,
+                "snapshot version {} is not supported, this build reads version {}",
+                found, supported
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(err: std::io::Error) -> Self {
+        SnapshotError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(err: serde_json::Error) -> Self {
+        SnapshotError::Parse(err)
+    }
+}
+
+// just the version, read before the rest to pick how to parse it
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of `simulation` in the current format.
+    pub fn new(simulation: Simulation) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            simulation,
+        }
+    }
+
+    /// Writes the snapshot as JSON.
+    pub fn to_json(&self) -> Result<String, SnapshotError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parses a snapshot from JSON.
+    ///
+    /// The version is checked first: snapshots of the current version are
+    /// read as they are, and snapshots written in another version are
+    /// refused. When the format changes, snapshots of the versions before it
+    /// are migrated here.
+    pub fn from_json(json: &str) -> Result<Self, SnapshotError> {
+        let Version { version } = serde_json::from_str(json)?;
+        match version {
+            SNAPSHOT_VERSION => Ok(serde_json::from_str(json)?),
+            found => Err(SnapshotError::UnsupportedVersion {
+                found,
+                supported: SNAPSHOT_VERSION,
+            }),
+        }
+    }
+
+    /// Writes the snapshot to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        Ok(std::fs::write(path, self.to_json()?)?)
+    }
+
+    /// Reads a snapshot file, see [`Snapshot::from_json`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Balance, BidGenerator, Discount, Event, Market, Price};
+
+    fn simulation() -> Simulation {
+        let mut market = Market::new();
+        for bidder in 1..=5 {
+            market
+                .mint(bidder, Balance::new(100_000), Balance::ZERO)
+                .unwrap();
+        }
+        market
+            .mint(100, Balance::ZERO, Balance::new(1_000_000))
+            .unwrap();
+        let mut sim = Simulation::new(market, 11);
+        sim.generator = BidGenerator {
+            bidders: 5,
+            arrival_rate: 2.0,
+            ..Default::default()
+        };
+        sim.arrivals = true;
+        for block in [3, 8, 14] {
+            sim.schedule(
+                block,
+                Event::Liquidate {
+                    account: 100,
+                    amount: Balance::new(4_000),
+                },
+            );
+        }
+        sim.schedule(
+            12,
+            Event::SetPrice {
+                price: Price::from_ratio(3, 2).unwrap(),
+            },
+        );
+        sim.schedule(
+            6,
+            Event::PlaceBid {
+                bidder: 1,
+                amount: Balance::new(2_000),
+                discount: Discount::percent(3),
+                expires_at: Some(10),
+            },
+        );
+        sim
+    }
+
+    #[test]
+    fn test_restored_simulation_carries_on() {
+        let mut sim = simulation();
+        sim.run(7);
+
+        let json = Snapshot::new(sim.clone()).to_json().unwrap();
+        let mut restored = Snapshot::from_json(&json).unwrap().simulation;
+
+        assert_eq!(restored.block(), sim.block());
+        assert_eq!(restored.market.book.bids, sim.market.book.bids);
+        assert_eq!(restored.market.metrics, sim.market.metrics);
+        assert_eq!(restored.market.events.events(), sim.market.events.events());
+        assert_eq!(restored.run(10), sim.run(10));
+        assert_eq!(restored.market.book.bids, sim.market.book.bids);
+        assert_eq!(
+            restored.market.book.history.closed(),
+            sim.market.book.history.closed()
+        );
+        assert_eq!(
+            restored.market.accounts.iter().collect::<Vec<_>>(),
+            sim.market.accounts.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(restored.price, sim.price);
+        assert_eq!(restored.rng.seed(), 11);
+    }
+
+    #[test]
+    fn test_other_versions_are_refused() {
+        let json = Snapshot::new(simulation()).to_json().unwrap();
+        let newer = json.replacen(r#""version":1"#, r#""version":2"#, 1);
+        assert!(matches!(
+            Snapshot::from_json(&newer),
+            Err(SnapshotError::UnsupportedVersion {
+                found: 2,
+                supported: SNAPSHOT_VERSION
+            })
+        ));
+        assert!(matches!(
+            Snapshot::from_json(r#"{"simulation": {}}"#),
+            Err(SnapshotError::Parse(_))
+        ));
+    }
+}